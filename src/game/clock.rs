@@ -0,0 +1,297 @@
+//! keeping track of time in the game, and of things that need to happen at certain times
+//!
+//! time is counted in minutes since the game started. everything bigger than that (days, seasons,
+//! years) is worked out from it when needed
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+pub const MINUTES_PER_HOUR: u64 = 60;
+pub const HOURS_PER_DAY: u64 = 24;
+pub const DAYS_PER_SEASON: u64 = 30;
+pub const SEASONS_PER_YEAR: u64 = 4;
+
+pub const MINUTES_PER_DAY: u64 = MINUTES_PER_HOUR * HOURS_PER_DAY;
+pub const MINUTES_PER_SEASON: u64 = MINUTES_PER_DAY * DAYS_PER_SEASON;
+pub const MINUTES_PER_YEAR: u64 = MINUTES_PER_SEASON * SEASONS_PER_YEAR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Autumn => "autumn",
+            Season::Winter => "winter",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// a point in time broken up into human sized pieces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    pub year: u64,
+    pub season: Season,
+    /// day of the season, starting at 1
+    pub day: u64,
+    pub hour: u64,
+    pub minute: u64,
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "year {}, {} day {}, {:02}:{:02}",
+            self.year, self.season, self.day, self.hour, self.minute
+        )
+    }
+}
+
+/// the simulation clock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clock {
+    /// minutes since the game started
+    now: u64,
+    /// the year the game started in, so dates line up with history
+    start_year: u64,
+    /// how many actions the player has taken
+    turns: u64,
+    pub paused: bool,
+}
+
+impl Clock {
+    /// a paused clock at the very start of `start_year`
+    pub fn new(start_year: u64) -> Self {
+        Clock {
+            now: 0,
+            start_year,
+            turns: 0,
+            paused: true,
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    pub fn advance(&mut self, minutes: u64) {
+        self.now += minutes;
+    }
+
+    /// count one more player action
+    pub fn next_turn(&mut self) {
+        self.turns += 1;
+    }
+
+    pub fn date(&self) -> Date {
+        let season = match (self.now % MINUTES_PER_YEAR) / MINUTES_PER_SEASON {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        };
+
+        Date {
+            year: self.start_year + self.now / MINUTES_PER_YEAR,
+            season,
+            day: (self.now % MINUTES_PER_SEASON) / MINUTES_PER_DAY + 1,
+            hour: (self.now % MINUTES_PER_DAY) / MINUTES_PER_HOUR,
+            minute: self.now % MINUTES_PER_HOUR,
+        }
+    }
+}
+
+/// handed out when scheduling something so it can be cancelled later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduleId(u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Scheduled<E> {
+    id: ScheduleId,
+    /// the minute this should next happen at
+    at: u64,
+    /// how often it repeats, if it repeats at all
+    every: Option<u64>,
+    event: E,
+}
+
+/// a list of events waiting for the clock to reach them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scheduler<E> {
+    scheduled: Vec<Scheduled<E>>,
+    next_id: u64,
+}
+
+impl<E: Clone> Scheduler<E> {
+    pub fn new() -> Self {
+        Scheduler {
+            scheduled: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    fn push(&mut self, at: u64, every: Option<u64>, event: E) -> ScheduleId {
+        let id = ScheduleId(self.next_id);
+        self.next_id += 1;
+
+        self.scheduled.push(Scheduled {
+            id,
+            at,
+            every,
+            event,
+        });
+
+        id
+    }
+
+    /// have `event` happen once, at minute `at`
+    #[allow(unused)]
+    pub fn once(&mut self, at: u64, event: E) -> ScheduleId {
+        self.push(at, None, event)
+    }
+
+    /// have `event` happen at minute `first`, and then again every `every` minutes after that
+    pub fn every(&mut self, first: u64, every: u64, event: E) -> ScheduleId {
+        // an event repeating every 0 minutes would never let advance return
+        self.push(first, Some(every.max(1)), event)
+    }
+
+    /// stop something from happening. does nothing if it already happened
+    #[allow(unused)]
+    pub fn cancel(&mut self, id: ScheduleId) {
+        self.scheduled.retain(|s| s.id != id);
+    }
+
+    /// everything that should have happened up to and including minute `now`, in the order it
+    /// happened. one-off events are forgotten afterwards, repeating ones are moved along
+    pub fn advance(&mut self, now: u64) -> Vec<E> {
+        // (minute, order it was scheduled in, event)
+        let mut due = Vec::new();
+
+        for scheduled in &mut self.scheduled {
+            while scheduled.at <= now {
+                due.push((scheduled.at, scheduled.id.0, scheduled.event.clone()));
+
+                match scheduled.every {
+                    Some(every) => scheduled.at += every,
+                    None => break,
+                }
+            }
+        }
+
+        self.scheduled.retain(|s| s.every.is_some() || s.at > now);
+
+        due.sort_by_key(|(at, id, _)| (*at, *id));
+        due.into_iter().map(|(_, _, event)| event).collect()
+    }
+}
+
+impl<E: Clone> Default for Scheduler<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_come_out_in_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.every(MINUTES_PER_SEASON, MINUTES_PER_SEASON, "season");
+        scheduler.every(0, MINUTES_PER_DAY, "day");
+        scheduler.once(MINUTES_PER_DAY + 1, "once");
+
+        let events = scheduler.advance(MINUTES_PER_YEAR);
+        assert_eq!(events.iter().filter(|&&e| e == "day").count(), 121);
+        assert_eq!(events.iter().filter(|&&e| e == "season").count(), 4);
+        assert_eq!(&events[..3], ["day", "day", "once"]);
+        // the season and the day turn over on the same minute, and the season was scheduled first
+        let season = events.iter().position(|&e| e == "season").unwrap();
+        // after the first 30 days and the one-off
+        assert_eq!(season, 30 + 1);
+        assert_eq!(&events[season..=season + 1], ["season", "day"]);
+
+        // nothing happens twice, and one-offs are gone
+        assert!(scheduler.advance(MINUTES_PER_YEAR).is_empty());
+        let events = scheduler.advance(MINUTES_PER_YEAR + MINUTES_PER_DAY);
+        assert_eq!(events, ["day"]);
+    }
+
+    #[test]
+    fn zero_intervals_still_move_along() {
+        let mut scheduler = Scheduler::new();
+        scheduler.every(5, 0, ());
+
+        assert_eq!(scheduler.advance(9).len(), 5);
+        assert_eq!(scheduler.advance(10).len(), 1);
+    }
+
+    #[test]
+    fn cancelled_events_stop() {
+        let mut scheduler = Scheduler::new();
+        let day = scheduler.every(0, MINUTES_PER_DAY, "day");
+        let hour = scheduler.every(0, MINUTES_PER_HOUR, "hour");
+        assert_eq!(scheduler.advance(0), ["day", "hour"]);
+
+        scheduler.cancel(day);
+        assert!(scheduler
+            .advance(MINUTES_PER_YEAR)
+            .iter()
+            .all(|&e| e == "hour"));
+        scheduler.cancel(hour);
+        assert!(scheduler.advance(2 * MINUTES_PER_YEAR).is_empty());
+    }
+
+    #[test]
+    fn dates_roll_over() {
+        let mut clock = Clock::new(250);
+        clock.advance(MINUTES_PER_SEASON - 1);
+        assert_eq!(
+            clock.date(),
+            Date {
+                year: 250,
+                season: Season::Spring,
+                day: 30,
+                hour: 23,
+                minute: 59,
+            }
+        );
+
+        clock.advance(1);
+        assert_eq!((clock.date().season, clock.date().day), (Season::Summer, 1));
+
+        clock.advance(MINUTES_PER_YEAR - MINUTES_PER_SEASON - 1);
+        assert_eq!(
+            (clock.date().year, clock.date().season),
+            (250, Season::Winter)
+        );
+        clock.advance(1);
+        assert_eq!(
+            clock.date(),
+            Date {
+                year: 251,
+                season: Season::Spring,
+                day: 1,
+                hour: 0,
+                minute: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,1098 @@
+//! fighting it out with another ship
+//!
+//! a battle takes both ships off the world map and onto a patch of open sea of their own. every
+//! turn each side can fire whichever broadsides are loaded and bear on the enemy, then either puts
+//! the helm over, boards or strikes her colours. it goes on until one of them sinks, is taken or
+//! sails off the edge of the patch and gets away
+
+use std::fmt::{Display, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, MIDNIGHT_BLUE, NAVY, WHITE, YELLOW};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::Point;
+use crate::render::{Frame, RenderPacket, RenderTick, Tile};
+use crate::worldgen::climate::Vector;
+use crate::worldgen::history::{EventKind, Fate};
+use crate::worldgen::names;
+
+use super::crew::{Skill, SKILL_COUNT};
+use super::economy::Good;
+use super::npc::{NpcId, Role};
+use super::ship::{Ship, MAX_HULL, MAX_SAILS};
+use super::{compass, contract, role_tile, treasure, Game, PLAYER_CHAR};
+
+const ARENA_WIDTH: isize = 60;
+const ARENA_HEIGHT: isize = 40;
+/// how far apart the two ships start out
+const START_DISTANCE: isize = 12;
+/// how far a broadside reaches
+const RANGE: f64 = 8.;
+/// grapeshot scatters too much to do any good further out than this
+const GRAPE_RANGE: f64 = 4.;
+/// a broadside bears on anything within this cosine of straight out from the side of the ship
+const ARC: f64 = 0.7;
+/// turns it takes to run the guns back in and load them again
+const RELOAD_TURNS: u32 = 3;
+/// how many game minutes a turn of fighting takes
+const MINUTES_PER_TURN: u64 = 10;
+/// hands it takes to work a gun at full speed
+const CREW_PER_GUN: u32 = 2;
+/// pointing any closer than this cosine into the wind leaves a ship in irons, going nowhere
+const IN_IRONS: f64 = -0.7;
+/// how many lines of the battle log fit under the arena
+const LOG_LINES: usize = 8;
+/// how many of the crew make it ashore when the player's ship goes down
+const SURVIVORS: u32 = 5;
+/// how much winning a fight cheers up the crew, and losing one gets them down
+const VICTORY_MORALE: i32 = 10;
+/// the gold in the strongbox of a ship that's been taken, by what she was
+const STRONGBOX: [(Role, (u32, u32)); 3] = [
+    (Role::Merchant, (50, 200)),
+    (Role::Navy, (20, 100)),
+    (Role::Pirate, (100, 400)),
+];
+
+/// whoever picked a fight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggressor {
+    /// the player sailed into her
+    Player,
+    /// she ran the player down
+    Enemy,
+}
+
+/// the way a ship can be pointing, in the same order as the directions of [`compass`]
+const HEADINGS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+/// what's drawn just ahead of a ship on each of the [`HEADINGS`], so you can tell which way she's
+/// pointing
+const BOWS: [char; 8] = ['-', '\\', '|', '/', '-', '\\', '|', '/'];
+
+/// what the guns are loaded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shot {
+    /// solid shot, for smashing hulls
+    Round,
+    /// for cutting up rigging, so the enemy can't get away
+    Chain,
+    /// for clearing decks, up close
+    Grape,
+}
+
+impl Shot {
+    fn next(self) -> Self {
+        match self {
+            Shot::Round => Shot::Chain,
+            Shot::Chain => Shot::Grape,
+            Shot::Grape => Shot::Round,
+        }
+    }
+}
+
+impl Display for Shot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Shot::Round => "round shot",
+            Shot::Chain => "chain shot",
+            Shot::Grape => "grapeshot",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// one side of a ship, for broadsides and for which way to turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Port,
+    Starboard,
+}
+
+impl Side {
+    const BOTH: [Side; 2] = [Side::Port, Side::Starboard];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// which of the [`HEADINGS`] this side looks out along, for a ship on `heading`
+    fn facing(self, heading: usize) -> usize {
+        match self {
+            Side::Port => (heading + 6) % 8,
+            Side::Starboard => (heading + 2) % 8,
+        }
+    }
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Port => write!(f, "port"),
+            Side::Starboard => write!(f, "starboard"),
+        }
+    }
+}
+
+/// what a ship does with the rest of her turn, once the guns have had their say
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// turn to one side, or hold her course with None
+    Helm(Option<Side>),
+    Board,
+    Strike,
+}
+
+/// how the fight went. the other ship is whoever the fight was with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Outcome {
+    /// whether the player came out on top. with [`Fate::Escaped`], whether the player was the one
+    /// who got away
+    player_won: bool,
+    fate: Fate,
+}
+
+fn unit((x, y): (isize, isize)) -> Vector {
+    let (x, y) = (x as f64, y as f64);
+    let length = x.hypot(y);
+
+    (x / length, y / length)
+}
+
+fn distance(a: (isize, isize), b: (isize, isize)) -> f64 {
+    ((a.0 - b.0) as f64).hypot((a.1 - b.1) as f64)
+}
+
+fn in_arena((x, y): (isize, isize)) -> bool {
+    (0..ARENA_WIDTH).contains(&x) && (0..ARENA_HEIGHT).contains(&y)
+}
+
+/// the heading after putting the helm over to `helm`
+fn turn(heading: usize, helm: Option<Side>) -> usize {
+    match helm {
+        Some(Side::Port) => (heading + 7) % 8,
+        Some(Side::Starboard) => (heading + 1) % 8,
+        None => heading,
+    }
+}
+
+/// how far off `target` is, if the `side` broadside of a ship at `from` on `heading` would bear
+/// on it
+fn bears(from: (isize, isize), heading: usize, side: Side, target: (isize, isize)) -> Option<f64> {
+    let d = distance(from, target);
+    if d == 0. || d > RANGE {
+        return None;
+    }
+
+    let facing = unit(HEADINGS[side.facing(heading)]);
+    let along = ((target.0 - from.0) as f64 * facing.0 + (target.1 - from.1) as f64 * facing.1) / d;
+
+    (along >= ARC).then_some(d)
+}
+
+/// one of the two ships in a fight
+struct Combatant {
+    ship: Ship,
+    /// None for the player
+    role: Option<Role>,
+    position: (isize, isize),
+    heading: usize,
+    /// turns until each broadside is loaded again, indexed by [`Side::index`]
+    reload: [u32; 2],
+    shot: Shot,
+    /// how many hands were aboard when the fight started
+    mustered: u32,
+    /// how much better than an ordinary crew the hands are at every skill, indexed by
+    /// [`Skill::index`]
+    drill: [f64; SKILL_COUNT],
+}
+
+impl Combatant {
+    fn new(ship: Ship, role: Option<Role>, position: (isize, isize), heading: usize) -> Self {
+        Combatant {
+            mustered: ship.crew,
+            ship,
+            role,
+            position,
+            heading,
+            reload: [0; 2],
+            shot: Shot::Round,
+            drill: [1.; SKILL_COUNT],
+        }
+    }
+
+    fn drill(&self, skill: Skill) -> f64 {
+        self.drill[skill.index()]
+    }
+
+    /// how many tiles the ship makes in a turn on `heading`
+    fn speed(&self, heading: usize, wind: Vector) -> isize {
+        if self.ship.sails == 0 {
+            return 0;
+        }
+
+        // a full hold sits the ship too low in the water to get up to speed
+        let laden = self.ship.cargo_total() > self.ship.hold / 2;
+        let strength = wind.0.hypot(wind.1);
+        let direction = unit(HEADINGS[heading]);
+        let along = if strength > 0. {
+            (direction.0 * wind.0 + direction.1 * wind.1) / strength
+        } else {
+            0.
+        };
+
+        if along < IN_IRONS {
+            0
+        } else if along > 0.3 && self.ship.sails > MAX_SAILS / 2 && !laden {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// a rough idea of how much fight the ship has left in her
+    fn strength(&self) -> f64 {
+        f64::from(self.ship.hull) * f64::from(self.ship.crew) * f64::from(self.ship.guns.max(1))
+    }
+
+    /// how much of her usual rate of fire the ship keeps up with the hands she has left
+    fn gunnery(&self) -> f64 {
+        let needed = (self.ship.guns * CREW_PER_GUN).max(1);
+
+        (f64::from(self.ship.crew) / f64::from(needed)).min(1.)
+    }
+
+    /// the state of a broadside, for the side panel
+    fn guns_text(&self, side: Side) -> String {
+        match self.reload[side.index()] {
+            0 => format!("{side} loaded"),
+            turns => format!("{side} loading ({turns})"),
+        }
+    }
+}
+
+/// fire the `side` broadside of `us` at `them`. None, without firing, if it isn't loaded or
+/// doesn't bear
+fn broadside(
+    us: &mut Combatant,
+    them: &mut Combatant,
+    side: Side,
+    rng: &mut RandomNumberGenerator,
+) -> Option<String> {
+    if us.reload[side.index()] > 0 || us.ship.guns == 0 {
+        return None;
+    }
+    let d = bears(us.position, us.heading, side, them.position)?;
+    us.reload[side.index()] = RELOAD_TURNS;
+
+    // half the guns are on either side
+    let guns = us.ship.guns.div_ceil(2);
+    let chance = (0.85 - 0.6 * d / RANGE) * us.gunnery() * us.drill(Skill::Gunnery);
+    let hits = (0..guns).filter(|_| rng.range(0., 1.) < chance).count();
+
+    let (mut hull, mut sails, mut crew) = (0, 0, 0);
+    for _ in 0..hits {
+        match us.shot {
+            Shot::Round => {
+                hull += rng.range(2, 5);
+                if rng.range(0, 4) == 0 {
+                    crew += 1;
+                }
+            }
+            Shot::Chain => sails += rng.range(3, 7),
+            Shot::Grape if d <= GRAPE_RANGE => crew += rng.range(1, 3),
+            Shot::Grape => {}
+        }
+    }
+    them.ship.hull = them.ship.hull.saturating_sub(hull);
+    them.ship.sails = them.ship.sails.saturating_sub(sails);
+    them.ship.crew = them.ship.crew.saturating_sub(crew);
+
+    let mut report = format!(
+        "{} fires her {side} broadside of {}: {hits} hits",
+        us.ship.name, us.shot
+    );
+    if us.shot == Shot::Grape && d > GRAPE_RANGE {
+        report.push_str(", but the grapeshot falls short");
+    }
+    for (amount, what) in [
+        (hull, "to the hull"),
+        (sails, "to the rigging"),
+        (crew, "killed"),
+    ] {
+        if amount > 0 {
+            let _ = write!(report, ", {amount} {what}");
+        }
+    }
+
+    Some(report)
+}
+
+/// send the crew of `us` over the side onto `them`. returns what happened, and whether `them`
+/// was taken
+fn board(
+    us: &mut Combatant,
+    them: &mut Combatant,
+    rng: &mut RandomNumberGenerator,
+) -> (String, bool) {
+    // pirates live for this sort of thing
+    let fierceness = |c: &Combatant| {
+        let pirate = if c.role == Some(Role::Pirate) {
+            1.3
+        } else {
+            1.
+        };
+        pirate * c.drill(Skill::Melee)
+    };
+    let ours = f64::from(us.ship.crew) * rng.range(0.7, 1.3) * fierceness(us);
+    let theirs = f64::from(them.ship.crew) * rng.range(0.7, 1.3) * fierceness(them);
+
+    #[allow(clippy::cast_sign_loss)]
+    let (winners, losers) = ((ours / 6.) as u32, (ours / 3.).ceil() as u32);
+    #[allow(clippy::cast_sign_loss)]
+    let (defenders, attackers) = ((theirs / 6.) as u32, (theirs / 3.).ceil() as u32);
+
+    if ours > theirs {
+        them.ship.crew = them.ship.crew.saturating_sub(losers);
+        us.ship.crew = us.ship.crew.saturating_sub(defenders);
+        let taken = them.ship.crew == 0 || them.ship.crew * 3 < us.ship.crew;
+
+        let report = format!(
+            "{} boards {} and drives her crew back, losing {defenders} to their {losers}",
+            us.ship.name, them.ship.name
+        );
+        (report, taken)
+    } else {
+        us.ship.crew = us.ship.crew.saturating_sub(attackers);
+        them.ship.crew = them.ship.crew.saturating_sub(winners);
+
+        let report = format!(
+            "{} tries to board {} and is thrown back, losing {attackers} to their {winners}",
+            us.ship.name, them.ship.name
+        );
+        (report, false)
+    }
+}
+
+/// everything about a fight in progress
+struct Battle {
+    player: Combatant,
+    enemy: Combatant,
+    wind: Vector,
+    /// what's happened so far, latest last
+    log: Vec<String>,
+    turns: u32,
+    rng: RandomNumberGenerator,
+    outcome: Option<Outcome>,
+}
+
+impl Battle {
+    /// set the player and `enemy` up side by side, far enough apart to have a bit of sea to cross
+    fn new(game: &mut Game, enemy: &Ship, role: Role) -> Self {
+        let (x, y) = game.player.position;
+        let (cx, cy) = (ARENA_WIDTH / 2, ARENA_HEIGHT / 2);
+
+        // heading north and south, with their starboard sides to each other
+        let mut player =
+            Combatant::new(game.player.clone(), None, (cx - START_DISTANCE / 2, cy), 6);
+        player.drill = Skill::ALL.map(|skill| game.crew.modifier(skill));
+
+        Battle {
+            player,
+            enemy: Combatant::new(enemy.clone(), Some(role), (cx + START_DISTANCE / 2, cy), 2),
+            wind: game.world.wind_at(x, y),
+            log: Vec::new(),
+            turns: 0,
+            rng: RandomNumberGenerator::seeded(game.rng.next_u64()),
+            outcome: None,
+        }
+    }
+
+    /// (us, them, rng), from the point of view of the player or the enemy
+    fn sides(
+        &mut self,
+        player: bool,
+    ) -> (&mut Combatant, &mut Combatant, &mut RandomNumberGenerator) {
+        if player {
+            (&mut self.player, &mut self.enemy, &mut self.rng)
+        } else {
+            (&mut self.enemy, &mut self.player, &mut self.rng)
+        }
+    }
+
+    fn end(&mut self, player_won: bool, fate: Fate) {
+        if self.outcome.is_none() {
+            self.outcome = Some(Outcome { player_won, fate });
+        }
+    }
+
+    /// fire a broadside for one side, ending the fight if it sinks the other
+    fn fire(&mut self, player: bool, side: Side) -> bool {
+        let (us, them, rng) = self.sides(player);
+        let Some(report) = broadside(us, them, side, rng) else {
+            return false;
+        };
+
+        self.log.push(report);
+        if self.enemy.ship.hull == 0 {
+            self.log.push(format!("{} goes down", self.enemy.ship.name));
+            self.end(true, Fate::Sunk);
+        }
+        if self.player.ship.hull == 0 {
+            self.log
+                .push(format!("{} goes down", self.player.ship.name));
+            self.end(false, Fate::Sunk);
+        }
+
+        true
+    }
+
+    /// the player tries to fire a broadside, with a word about it if they can't
+    fn player_fire(&mut self, side: Side) {
+        if self.fire(true, side) {
+            return;
+        }
+
+        let us = &self.player;
+        let why = if us.ship.guns == 0 {
+            "you have no guns left".to_string()
+        } else if us.reload[side.index()] > 0 {
+            format!("the {side} guns are still loading")
+        } else if distance(us.position, self.enemy.position) > RANGE {
+            format!("{} is out of range", self.enemy.ship.name)
+        } else {
+            format!("the {side} guns don't bear on {}", self.enemy.ship.name)
+        };
+        self.log.push(why);
+    }
+
+    /// carry out a [`Command`] for one side
+    fn command(&mut self, player: bool, command: Command) {
+        match command {
+            Command::Helm(_) => {}
+            Command::Board => {
+                let (us, them, rng) = self.sides(player);
+                let (report, taken) = board(us, them, rng);
+                self.log.push(report);
+                if taken {
+                    self.end(player, Fate::Boarded);
+                }
+            }
+            Command::Strike => {
+                let name = if player {
+                    &self.player.ship.name
+                } else {
+                    &self.enemy.ship.name
+                };
+                self.log.push(format!("{name} strikes her colours"));
+                self.end(!player, Fate::Struck);
+            }
+        }
+    }
+
+    /// sail one side as far as she goes this turn on her new heading
+    fn manoeuvre(&mut self, player: bool, helm: Option<Side>) {
+        let wind = self.wind;
+        let (us, them, _) = self.sides(player);
+        us.heading = turn(us.heading, helm);
+        let (dx, dy) = HEADINGS[us.heading];
+
+        for _ in 0..us.speed(us.heading, wind) {
+            let next = (us.position.0 + dx, us.position.1 + dy);
+            if next == them.position {
+                break;
+            }
+            if !in_arena(next) {
+                let name = us.ship.name.clone();
+                self.log.push(format!("{name} gets clear away"));
+                self.end(player, Fate::Escaped);
+                return;
+            }
+
+            us.position = next;
+        }
+    }
+
+    /// the enemy fires whatever she can, and decides what to do with the rest of her turn
+    fn enemy_turn(&mut self) -> Command {
+        let role = self.enemy.role.unwrap_or(Role::Merchant);
+        let (me, them) = (&self.enemy, &self.player);
+        let d = distance(me.position, them.position);
+
+        let beaten = match role {
+            Role::Merchant => me.ship.hull < MAX_HULL * 3 / 5 || me.ship.crew * 2 < me.mustered,
+            Role::Navy | Role::Pirate => {
+                (me.ship.hull < MAX_HULL / 4 || me.ship.crew * 4 < me.mustered)
+                    && me.strength() < them.strength() / 2.
+            }
+        };
+        if beaten {
+            return Command::Strike;
+        }
+        let fleeing = role == Role::Merchant || me.strength() < them.strength() * 0.4;
+
+        let rigged = them.ship.sails > MAX_SAILS / 4;
+        self.enemy.shot = match role {
+            Role::Pirate if d <= GRAPE_RANGE => Shot::Grape,
+            // a prize is no good to a pirate at the bottom of the sea, and a merchant wants
+            // anything that slows down whoever is giving chase
+            Role::Pirate | Role::Merchant if rigged => Shot::Chain,
+            Role::Pirate | Role::Merchant | Role::Navy => Shot::Round,
+        };
+        for side in Side::BOTH {
+            self.fire(false, side);
+            if self.outcome.is_some() {
+                return Command::Helm(None);
+            }
+        }
+
+        let (me, them) = (&self.enemy, &self.player);
+        let adjacent = (me.position.0 - them.position.0).abs() <= 1
+            && (me.position.1 - them.position.1).abs() <= 1;
+        let boarding = match role {
+            Role::Pirate => me.ship.crew > them.ship.crew,
+            Role::Navy => me.ship.crew * 2 > them.ship.crew * 3,
+            Role::Merchant => false,
+        };
+        if adjacent && boarding && !fleeing {
+            return Command::Board;
+        }
+
+        // try every way of turning and go with whichever leaves her best placed
+        let score = |helm: Option<Side>| {
+            let heading = turn(me.heading, helm);
+            let speed = me.speed(heading, self.wind);
+            let (dx, dy) = HEADINGS[heading];
+            let next = (me.position.0 + dx * speed, me.position.1 + dy * speed);
+            let d = distance(next, them.position);
+
+            if fleeing {
+                if !in_arena(next) {
+                    return f64::INFINITY;
+                }
+                let to_edge = next
+                    .0
+                    .min(next.1)
+                    .min(ARENA_WIDTH - 1 - next.0)
+                    .min(ARENA_HEIGHT - 1 - next.1);
+                return d - to_edge as f64 * 0.5;
+            }
+            if !in_arena(next) {
+                return f64::NEG_INFINITY;
+            }
+
+            let wanted = if boarding { 1. } else { RANGE * 0.6 };
+            let ready = Side::BOTH
+                .into_iter()
+                .filter(|&side| {
+                    me.reload[side.index()] <= 1
+                        && bears(next, heading, side, them.position).is_some()
+                })
+                .count();
+
+            3. * ready as f64 - (d - wanted).abs()
+        };
+
+        let helm = [None, Some(Side::Port), Some(Side::Starboard)]
+            .into_iter()
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+            .flatten();
+
+        Command::Helm(helm)
+    }
+
+    /// the player has made up their mind, so the enemy gets her say and both ships move
+    fn end_turn(&mut self, command: Command) {
+        self.command(true, command);
+        if self.outcome.is_some() {
+            return;
+        }
+
+        let reply = self.enemy_turn();
+        self.command(false, reply);
+        if self.outcome.is_some() {
+            return;
+        }
+
+        if let Command::Helm(helm) = command {
+            self.manoeuvre(true, helm);
+        }
+        if let (Command::Helm(helm), None) = (reply, self.outcome) {
+            self.manoeuvre(false, helm);
+        }
+
+        for combatant in [&mut self.player, &mut self.enemy] {
+            for reload in &mut combatant.reload {
+                *reload = reload.saturating_sub(1);
+            }
+        }
+        self.turns += 1;
+    }
+}
+
+/// the arena with both ships on it, the state of both and what's happened lately
+// nothing gets drawn outside the arena
+#[allow(clippy::cast_sign_loss)]
+fn frame(game: &Game, battle: &Battle, title: &str) -> Frame {
+    let mut frame = Frame::blank(game.screen);
+    let (player, enemy) = (&battle.player, &battle.enemy);
+
+    // show where the player's loaded guns would hit
+    for y in 0..ARENA_HEIGHT {
+        for x in 0..ARENA_WIDTH {
+            let in_arc = Side::BOTH.into_iter().any(|side| {
+                player.reload[side.index()] == 0
+                    && bears(player.position, player.heading, side, (x, y)).is_some()
+            });
+            let bg = if in_arc { MIDNIGHT_BLUE } else { BLACK };
+
+            frame.set(x as usize, y as usize, Tile::new('~', NAVY, bg));
+        }
+    }
+
+    let Some(role) = enemy.role else {
+        return frame;
+    };
+    for (combatant, tile) in [
+        (enemy, role_tile(role)),
+        (player, Tile::new(PLAYER_CHAR, YELLOW, BLACK)),
+    ] {
+        let (x, y) = combatant.position;
+        let (dx, dy) = HEADINGS[combatant.heading];
+        if in_arena((x + dx, y + dy)) {
+            let bow = Tile::new(BOWS[combatant.heading], GRAY, BLACK);
+            frame.set((x + dx) as usize, (y + dy) as usize, bow);
+        }
+        frame.set(x as usize, y as usize, tile);
+    }
+
+    let panel = ARENA_WIDTH as usize + 2;
+    let mut lines = vec![(title.to_string(), YELLOW), (String::new(), WHITE)];
+    for (combatant, who) in [(player, "you"), (enemy, "enemy")] {
+        let ship = &combatant.ship;
+        lines.extend([
+            (format!("{} ({who})", ship.name), WHITE),
+            (
+                format!(
+                    "  hull {}/{MAX_HULL}  sails {}/{MAX_SAILS}",
+                    ship.hull, ship.sails
+                ),
+                WHITE,
+            ),
+            (
+                format!(
+                    "  crew {}/{}  guns {}",
+                    ship.crew, combatant.mustered, ship.guns
+                ),
+                WHITE,
+            ),
+            (
+                format!(
+                    "  {}, {}",
+                    combatant.guns_text(Side::Port),
+                    combatant.guns_text(Side::Starboard)
+                ),
+                WHITE,
+            ),
+            (format!("  loaded with {}", combatant.shot), WHITE),
+            (String::new(), WHITE),
+        ]);
+    }
+    lines.extend([
+        (
+            format!(
+                "wind {} {:.1}",
+                compass(battle.wind),
+                battle.wind.0.hypot(battle.wind.1)
+            ),
+            WHITE,
+        ),
+        (
+            format!(
+                "{:.0} apart, guns reach {RANGE:.0}",
+                distance(player.position, enemy.position)
+            ),
+            WHITE,
+        ),
+        (format!("turn {}", battle.turns), WHITE),
+    ]);
+    for (row, (line, colour)) in lines.iter().enumerate() {
+        frame.print(panel, row, line, *colour, BLACK);
+    }
+
+    let below = ARENA_HEIGHT as usize + 1;
+    let recent = battle.log.len().saturating_sub(LOG_LINES);
+    for (row, line) in battle.log[recent..].iter().enumerate() {
+        frame.print(0, below + row, line, WHITE, BLACK);
+    }
+
+    let help: &[&str] = if battle.outcome.is_some() {
+        &["[enter/esc] back to the map"]
+    } else {
+        &[
+            "[left/right] turn to port/starboard  [up/.] hold course",
+            "[q/e] fire port/starboard  [tab] change shot  [b] board  [s] strike colours",
+        ]
+    };
+    for (row, line) in help.iter().enumerate() {
+        frame.print(0, below + LOG_LINES + 1 + row, line, GRAY, BLACK);
+    }
+
+    frame
+}
+
+/// move every good aboard `from` that fits into the hold of `to`. returns how many were moved
+fn plunder(from: &mut Ship, to: &mut Ship) -> u32 {
+    let mut moved = 0;
+    for good in Good::ALL {
+        let taken = from.cargo_of(good).min(to.free_hold());
+        from.cargo[good.index()] -= taken;
+        to.cargo[good.index()] += taken;
+        moved += taken;
+    }
+
+    moved
+}
+
+/// the player's ship went down at `location`. whoever made it ashore gets another go
+fn shipwreck(game: &mut Game, location: Point<usize>, rng: &mut RandomNumberGenerator) -> String {
+    // washed up in the nearest port, with whatever was in the player's pockets
+    let history = game.world.history();
+    let nearest = history.living_ports().min_by_key(|(_, port)| {
+        port.location.0.abs_diff(location.0) + port.location.1.abs_diff(location.1)
+    });
+    let (position, docked) = match nearest {
+        Some((id, port)) => (port.location, Some(id)),
+        None => (location, None),
+    };
+    let name = game.player.name.clone();
+    game.player = Ship::new(names::ship(rng), position, docked);
+    let lost = game.crew.fit().saturating_sub(SURVIVORS);
+    game.crew.casualties(lost, rng);
+    game.gold /= 2;
+    game.course.clear();
+    game.destination = None;
+
+    format!(
+        "{name} went down. you washed up with half your gold and {} of the crew, and found yourself a new ship, {}",
+        game.crew.sailors.len(),
+        game.player.name
+    )
+}
+
+/// the aftermath of a battle: who ends up with what, and what the world makes of it
+fn settle(game: &mut Game, id: NpcId, aggressor: Aggressor, battle: Battle, outcome: Outcome) {
+    let Some(npc) = game.fleet.get(id) else {
+        return;
+    };
+    let (role, civ) = (npc.role, npc.civ);
+    let Battle {
+        player,
+        enemy,
+        turns,
+        mut rng,
+        ..
+    } = battle;
+    let mut enemy = enemy.ship;
+    // the player's ship keeps her wounds, but not her place in the arena. everyone who fell
+    // was somebody
+    let (killed, wounded) = game
+        .crew
+        .casualties(player.mustered - player.ship.crew, &mut rng);
+    game.player = player.ship;
+
+    let (victor, vanquished) = if outcome.player_won {
+        (game.player.name.clone(), enemy.name.clone())
+    } else {
+        (enemy.name.clone(), game.player.name.clone())
+    };
+    let location = game.player.position;
+
+    let message = match (outcome.player_won, outcome.fate) {
+        (true, Fate::Sunk) => {
+            game.fleet.remove(id);
+            format!("{} went down with all hands", enemy.name)
+        }
+        (true, Fate::Boarded | Fate::Struck) => {
+            game.fleet.remove(id);
+            let goods = plunder(&mut enemy, &mut game.player);
+            let (low, high) = STRONGBOX
+                .iter()
+                .find(|(r, _)| *r == role)
+                .map_or((0, 1), |&(_, range)| range);
+            let gold = rng.range(low, high);
+            game.gold += gold;
+
+            let taken = format!(
+                "you took {} with {goods} goods aboard and {gold} gold in her strongbox",
+                enemy.name
+            );
+            // pirates keep their secrets about them
+            match role {
+                Role::Pirate => match treasure::take_map(game, &mut rng) {
+                    Some(owner) => format!("{taken}, and a map to the hoard of {owner}"),
+                    None => taken,
+                },
+                Role::Merchant | Role::Navy => taken,
+            }
+        }
+        (false, Fate::Sunk) => shipwreck(game, location, &mut rng),
+        (false, Fate::Boarded | Fate::Struck) => {
+            let goods = plunder(&mut game.player, &mut enemy);
+            let gold = game.gold / 2;
+            game.gold -= gold;
+
+            format!(
+                "{} took {goods} goods and {gold} gold from you, and let you go",
+                enemy.name
+            )
+        }
+        (true, Fate::Escaped) => format!("you got away from {}", enemy.name),
+        (false, Fate::Escaped) => format!("{} got away", enemy.name),
+    };
+
+    // whoever is still afloat keeps the damage, and leaves the player be for a while
+    if let Some(npc) = game.fleet.get_mut(id) {
+        npc.ship = Ship {
+            position: npc.ship.position,
+            docked: npc.ship.docked,
+            ..enemy
+        };
+        game.fleet.disengage(id);
+    }
+
+    game.crew.cheer(match (outcome.player_won, outcome.fate) {
+        (_, Fate::Escaped) => 0,
+        (true, _) => VICTORY_MORALE,
+        (false, _) => -VICTORY_MORALE,
+    });
+    game.muster();
+    let message = match (killed, wounded) {
+        (0, 0) => message,
+        _ => format!("{message}. {killed} of the crew were killed and {wounded} wounded"),
+    };
+
+    // fighting back is fair enough, as long as she's let go afterwards
+    let taken = outcome.player_won && outcome.fate != Fate::Escaped;
+    if aggressor == Aggressor::Player || taken {
+        game.reputation.attacked(game.world.history(), civ, taken);
+    }
+    let message = match taken.then(|| contract::claim_bounty(game, id)).flatten() {
+        Some(bounty) => format!("{message}. {bounty}"),
+        None => message,
+    };
+
+    let year = u32::try_from(game.clock.date().year).unwrap_or(u32::MAX);
+    game.world.history_mut().push_event(
+        year,
+        EventKind::SeaBattle {
+            location,
+            victor,
+            vanquished,
+            civ,
+            fate: outcome.fate,
+        },
+    );
+
+    game.pass_time(u64::from(turns) * MINUTES_PER_TURN);
+    game.message = message;
+}
+
+/// fight it out with `id` until one side wins or gets away, then sort out what happens after
+///
+/// returns false if the window was closed mid fight
+pub fn fight(
+    game: &mut Game,
+    id: NpcId,
+    aggressor: Aggressor,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send battle to render thread. Error: {e}");
+        }
+    };
+
+    let Some(npc) = game.fleet.get(id) else {
+        return true;
+    };
+    let title = format!(
+        "{} vs {}, {}",
+        game.player.name,
+        npc.ship.name,
+        npc.describe(game.world.history())
+    );
+    let opening = match aggressor {
+        Aggressor::Player => format!(
+            "you close with {}, captained by {}",
+            npc.ship.name, npc.captain
+        ),
+        Aggressor::Enemy => format!(
+            "{}, captained by {}, closes with you",
+            npc.ship.name, npc.captain
+        ),
+    };
+    let (ship, role) = (npc.ship.clone(), npc.role);
+    let mut battle = Battle::new(game, &ship, role);
+    battle.log.push(opening);
+
+    send(frame(game, &battle, &title));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        if let Some(outcome) = battle.outcome {
+            if matches!(key, VirtualKeyCode::Escape | VirtualKeyCode::Return) {
+                settle(game, id, aggressor, battle, outcome);
+                return true;
+            }
+            continue;
+        }
+
+        match key {
+            VirtualKeyCode::Q => battle.player_fire(Side::Port),
+            VirtualKeyCode::E => battle.player_fire(Side::Starboard),
+            VirtualKeyCode::Tab => {
+                battle.player.shot = battle.player.shot.next();
+                battle.log.push(format!(
+                    "the guns will be loaded with {}",
+                    battle.player.shot
+                ));
+            }
+            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 => {
+                battle.end_turn(Command::Helm(Some(Side::Port)));
+            }
+            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 => {
+                battle.end_turn(Command::Helm(Some(Side::Starboard)));
+            }
+            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::Period => {
+                battle.end_turn(Command::Helm(None));
+            }
+            VirtualKeyCode::B => {
+                let (us, them) = (battle.player.position, battle.enemy.position);
+                if (us.0 - them.0).abs() <= 1 && (us.1 - them.1).abs() <= 1 {
+                    battle.end_turn(Command::Board);
+                } else {
+                    battle.log.push(format!(
+                        "{} is too far off to board",
+                        battle.enemy.ship.name
+                    ));
+                }
+            }
+            VirtualKeyCode::S => battle.end_turn(Command::Strike),
+            _ => continue,
+        }
+
+        send(frame(game, &battle, &title));
+    }
+
+    // the render thread hung up on us
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combatant(position: (isize, isize), heading: usize) -> Combatant {
+        let ship = Ship::new("Test".to_string(), (0, 0), None);
+
+        Combatant::new(ship, Some(Role::Merchant), position, heading)
+    }
+
+    #[test]
+    fn broadsides_bear_out_to_the_side() {
+        // heading north, so starboard looks east
+        let from = (10, 10);
+        assert_eq!(bears(from, 6, Side::Starboard, (15, 10)), Some(5.));
+        assert_eq!(bears(from, 6, Side::Port, (15, 10)), None);
+        assert_eq!(bears(from, 6, Side::Port, (5, 10)), Some(5.));
+
+        // right on the edge of the arc, and just past it
+        assert!(bears(from, 6, Side::Starboard, (15, 5)).is_some());
+        assert!(bears(from, 6, Side::Starboard, (15, 4)).is_none());
+        // dead ahead and dead astern never bear
+        assert!(Side::BOTH
+            .iter()
+            .all(|&side| bears(from, 6, side, (10, 5)).is_none()
+                && bears(from, 6, side, (10, 15)).is_none()));
+
+        // out of range, and on top of each other
+        assert!(bears(from, 6, Side::Starboard, (10 + RANGE as isize, 10)).is_some());
+        assert!(bears(from, 6, Side::Starboard, (11 + RANGE as isize, 10)).is_none());
+        assert!(bears(from, 6, Side::Starboard, from).is_none());
+    }
+
+    #[test]
+    fn only_loaded_guns_fire() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let mut us = combatant((10, 10), 6);
+        let mut them = combatant((14, 10), 2);
+
+        us.reload[Side::Starboard.index()] = 1;
+        assert!(broadside(&mut us, &mut them, Side::Starboard, &mut rng).is_none());
+        assert_eq!(us.reload[Side::Starboard.index()], 1);
+
+        us.ship.guns = 0;
+        us.reload = [0; 2];
+        assert!(broadside(&mut us, &mut them, Side::Starboard, &mut rng).is_none());
+        assert_eq!(us.reload, [0; 2]);
+        assert_eq!(them.ship.hull, MAX_HULL);
+
+        us.ship.guns = 10;
+        assert!(broadside(&mut us, &mut them, Side::Starboard, &mut rng).is_some());
+        assert_eq!(us.reload, [0, RELOAD_TURNS]);
+        // and not again until she's loaded
+        assert!(broadside(&mut us, &mut them, Side::Starboard, &mut rng).is_none());
+    }
+
+    #[test]
+    fn empty_ships_are_taken() {
+        for seed in 0..20 {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let mut us = combatant((10, 10), 6);
+            let mut them = combatant((11, 10), 2);
+            them.ship.crew = 0;
+            let crew = us.ship.crew;
+
+            let (_, taken) = board(&mut us, &mut them, &mut rng);
+            assert!(taken);
+            assert_eq!(us.ship.crew, crew);
+        }
+    }
+
+    #[test]
+    fn plunder_only_takes_what_fits() {
+        let mut from = Ship::new("Prize".to_string(), (0, 0), None);
+        let mut to = Ship::new("Raider".to_string(), (0, 0), None);
+        from.cargo[Good::Rum.index()] = 40;
+        from.cargo[Good::Sugar.index()] = 40;
+        to.hold = 60;
+        to.cargo[Good::Iron.index()] = 10;
+
+        assert_eq!(plunder(&mut from, &mut to), 50);
+        assert_eq!(to.free_hold(), 0);
+        assert_eq!(from.cargo_total() + to.cargo_total(), 90);
+        assert_eq!(plunder(&mut from, &mut to), 0);
+
+        to.hold = 100;
+        assert_eq!(plunder(&mut from, &mut to), 30);
+        assert_eq!(from.cargo_total(), 0);
+    }
+}
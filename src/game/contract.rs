@@ -0,0 +1,1041 @@
+//! contracts, the work ports have going for anyone with a ship
+//!
+//! every port draws up a few jobs every so often: cargo to deliver, merchants wanting an escort,
+//! pirates with a price on their heads, goods nobody should ask about and people stranded somewhere
+//! who need bringing home. every one of them has a deadline. keeping a contract pays, and breaking
+//! one costs the player their standing with whoever they let down
+
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, NAVY, WHITE, YELLOW};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{points_around, Point};
+use crate::render::{Frame, RenderPacket, RenderTick};
+use crate::worldgen::history::{CivId, History, PortId};
+use crate::worldgen::{names, FullWorld};
+
+use super::economy::{Good, GOOD_COUNT};
+use super::navigation::SeaChart;
+use super::npc::{Fleet, NpcId};
+use super::{clock, compass, Game};
+
+/// how many days the work going in a port stays the same before new contracts get drawn up
+const OFFER_DAYS: u64 = 3;
+/// how many contracts a port has going at once, at most
+const MAX_OFFERS: usize = 5;
+/// the player can't take on more than this many contracts at once
+const MAX_CONTRACTS: usize = 6;
+/// contracts only send the player to one of this many of the closest ports
+const NEARBY_PORTS: usize = 8;
+/// how many times the sailing time a contract allows
+const SLACK: f64 = 2.;
+/// time a contract allows on top of the sailing time, for getting ready and things going wrong
+const SPARE_HOURS: f64 = 48.;
+/// how long the player gets to hunt down a pirate
+const BOUNTY_DAYS: u64 = 20;
+/// what every hour of sailing a contract takes is worth
+const GOLD_PER_HOUR: f64 = 4.;
+/// what a bounty on a pirate pays
+const BOUNTY: u32 = 250;
+/// how much more smuggling pays than honest work
+const SMUGGLING_PREMIUM: f64 = 2.5;
+/// how likely customs are to find smuggled goods
+const CUSTOMS_CHANCE: f64 = 0.25;
+/// how close the player has to be to a ship they're escorting when she makes port
+const ESCORT_RANGE: usize = 10;
+/// how far from port the people waiting on a rescue are stranded, in tiles
+const RESCUE_DISTANCE: (isize, isize) = (8, 30);
+/// how many tries at finding somewhere for someone to be stranded
+const RESCUE_TRIES: usize = 50;
+/// how much of the waters around a place a client marks on the player's charts
+const CHART_RADIUS: usize = 3;
+/// how many units of goods a delivery is, from and up to
+const CARGO: (u32, u32) = (10, 41);
+/// what keeping a contract does for the player's standing with whoever it was for
+const KEPT_STANDING: i32 = 5;
+/// what breaking one does
+const BROKEN_STANDING: i32 = -10;
+/// what getting caught smuggling does for the player's standing with whoever caught them
+const CAUGHT_STANDING: i32 = -20;
+/// how many of the latest journal entries fit on the screen
+const JOURNAL_LINES: usize = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Kind {
+    /// carry goods to another port
+    Delivery { good: Good, amount: u32, to: PortId },
+    /// see a merchant safely to another port. she only puts to sea once the contract is taken on,
+    /// with `cargo` aboard
+    Escort {
+        ship: String,
+        captain: String,
+        to: PortId,
+        npc: Option<NpcId>,
+        cargo: u32,
+    },
+    /// take or sink a pirate, last seen near `seen`
+    Bounty {
+        npc: NpcId,
+        ship: String,
+        captain: String,
+        seen: PortId,
+    },
+    /// like a delivery, but the customs at the other end can't know about it
+    Smuggling { good: Good, amount: u32, to: PortId },
+    /// pick someone up from where they're stranded and bring them back
+    Rescue {
+        who: String,
+        location: Point<usize>,
+        aboard: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contract {
+    pub kind: Kind,
+    /// the port the contract was drawn up in
+    pub from: PortId,
+    /// who is paying, like "the merchants of somewhere"
+    pub client: String,
+    /// who holds it against the player if the contract is broken. smugglers keep their grudges
+    /// to themselves
+    pub civ: Option<CivId>,
+    pub reward: u32,
+    /// how many minutes the contract allows from when it's taken on
+    allowed: u64,
+    /// the minute on the clock it has to be done by, once it's been taken on
+    deadline: u64,
+}
+
+/// how a contract turned out
+enum Outcome {
+    Kept(String),
+    Broken(String),
+    /// nobody's fault, nobody pays
+    Void(String),
+}
+
+/// the contracts the player has taken on, and how the old ones went
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// the work going in each port, and the day it was drawn up
+    #[serde(skip)]
+    offers: HashMap<PortId, (u64, Vec<Contract>)>,
+    active: Vec<Contract>,
+    /// how every contract the player took on turned out, the latest last
+    entries: Vec<String>,
+}
+
+impl Journal {
+    /// where people are waiting to be rescued, to show on the map
+    pub fn strandings(&self) -> impl Iterator<Item = Point<usize>> + '_ {
+        self.active
+            .iter()
+            .filter_map(|contract| match contract.kind {
+                Kind::Rescue {
+                    location,
+                    aboard: false,
+                    ..
+                } => Some(location),
+                _ => None,
+            })
+    }
+}
+
+/// how many steps it takes to get from `a` to `b`, diagonals included
+fn distance(a: Point<usize>, b: Point<usize>) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// what `hours` of sailing is worth, and how many minutes a contract allows for it
+#[allow(clippy::cast_sign_loss)]
+fn terms(hours: f64) -> (u32, u64) {
+    let reward = (hours * GOLD_PER_HOUR).round() as u32;
+    let allowed = ((hours * SLACK + SPARE_HOURS) * clock::MINUTES_PER_HOUR as f64) as u64;
+
+    (reward, allowed)
+}
+
+impl Contract {
+    /// what the contract asks of the player
+    fn describe(&self, world: &FullWorld) -> String {
+        let history = world.history();
+        let port = |id: PortId| history.ports[id].name.as_str();
+
+        match &self.kind {
+            Kind::Delivery { good, amount, to } => {
+                format!(
+                    "deliver {amount} {good} to {} for {}",
+                    port(*to),
+                    self.client
+                )
+            }
+            Kind::Escort { ship, to, .. } => {
+                format!("see {ship} of {} safely to {}", self.client, port(*to))
+            }
+            Kind::Bounty {
+                ship,
+                captain,
+                seen,
+                ..
+            } => format!(
+                "take or sink the pirate {ship} under {captain}, last seen near {}, for {}",
+                port(*seen),
+                self.client
+            ),
+            Kind::Smuggling { good, amount, to } => format!(
+                "slip {amount} {good} past the customs of {} for {}",
+                port(*to),
+                self.client
+            ),
+            Kind::Rescue {
+                who,
+                location,
+                aboard: false,
+            } => {
+                let from = history.ports[self.from].location;
+                let offset = (
+                    location.0 as f64 - from.0 as f64,
+                    location.1 as f64 - from.1 as f64,
+                );
+                format!(
+                    "bring {who} home to {}, stranded {} leagues {} of there",
+                    port(self.from),
+                    distance(*location, from),
+                    compass(offset)
+                )
+            }
+            Kind::Rescue {
+                who, aboard: true, ..
+            } => format!("bring {who} home to {}, already aboard", port(self.from)),
+        }
+    }
+
+    /// one line of a list of contracts, with how long is left on it if it's been taken on
+    fn line(&self, world: &FullWorld, now: Option<u64>) -> String {
+        let time = match now {
+            Some(now) => self.deadline.saturating_sub(now),
+            None => self.allowed,
+        };
+
+        format!(
+            "  {}  {} gold, {} days{}",
+            self.describe(world),
+            self.reward,
+            time / clock::MINUTES_PER_DAY,
+            if now.is_some() { " left" } else { "" }
+        )
+    }
+}
+
+/// the living ports closest to `port`, not counting it
+fn nearby_ports(history: &History, port: PortId) -> Vec<PortId> {
+    let here = history.ports[port].location;
+    let mut ports: Vec<_> = history
+        .living_ports()
+        .filter(|&(id, _)| id != port)
+        .map(|(id, other)| (distance(here, other.location), id))
+        .collect();
+    ports.sort_unstable();
+
+    ports
+        .into_iter()
+        .take(NEARBY_PORTS)
+        .map(|(_, id)| id)
+        .collect()
+}
+
+/// a contract to carry goods from `port` to somewhere close by, above board or not
+fn delivery(
+    chart: &SeaChart,
+    rng: &mut RandomNumberGenerator,
+    port: PortId,
+    smuggling: bool,
+) -> Option<Contract> {
+    let history = chart.world().history();
+    let owner = history.ports[port].owner;
+    // smugglers make their money getting things into somewhere else's ports
+    let ports: Vec<_> = nearby_ports(history, port)
+        .into_iter()
+        .filter(|&to| !smuggling || history.ports[to].owner != owner)
+        .collect();
+    let &to = rng.random_slice_entry(&ports)?;
+    let route = chart.route_between(port, to)?;
+
+    let good = Good::ALL[rng.range(0, GOOD_COUNT)];
+    let amount = rng.range(CARGO.0, CARGO.1);
+    let (reward, allowed) = terms(route.hours);
+    let reward = reward + amount;
+
+    let here = &history.ports[port].name;
+    let (kind, client, civ, reward) = if smuggling {
+        #[allow(clippy::cast_sign_loss)]
+        let reward = (f64::from(reward) * SMUGGLING_PREMIUM) as u32;
+        (
+            Kind::Smuggling { good, amount, to },
+            format!("a shifty sort in the taverns of {here}"),
+            None,
+            reward,
+        )
+    } else {
+        (
+            Kind::Delivery { good, amount, to },
+            format!("the merchants of {here}"),
+            Some(owner),
+            reward,
+        )
+    };
+
+    Some(Contract {
+        kind,
+        from: port,
+        client,
+        civ,
+        reward,
+        allowed,
+        deadline: 0,
+    })
+}
+
+/// a merchant of `port` wanting a guard on the way somewhere close by
+fn escort(chart: &SeaChart, rng: &mut RandomNumberGenerator, port: PortId) -> Option<Contract> {
+    let history = chart.world().history();
+    let ports = nearby_ports(history, port);
+    let &to = rng.random_slice_entry(&ports)?;
+    let route = chart.route_between(port, to)?;
+
+    let ship = names::ship(rng);
+    let captain = names::captain(rng);
+    let (reward, allowed) = terms(route.hours);
+    let owner = history.ports[port].owner;
+
+    Some(Contract {
+        client: format!("captain {captain}"),
+        kind: Kind::Escort {
+            ship,
+            captain,
+            to,
+            npc: None,
+            cargo: 0,
+        },
+        from: port,
+        civ: Some(owner),
+        reward: reward * 3 / 2,
+        allowed,
+        deadline: 0,
+    })
+}
+
+/// a price on the head of one of the pirates out there
+fn bounty(
+    world: &FullWorld,
+    fleet: &Fleet,
+    rng: &mut RandomNumberGenerator,
+    port: PortId,
+) -> Option<Contract> {
+    let pirates: Vec<_> = fleet.pirates().collect();
+    let pirate = rng.random_slice_entry(&pirates)?;
+
+    let history = world.history();
+    let (seen, _) = history
+        .living_ports()
+        .min_by_key(|(_, port)| distance(port.location, pirate.ship.position))?;
+    let owner = history.ports[port].owner;
+
+    Some(Contract {
+        kind: Kind::Bounty {
+            npc: pirate.id,
+            ship: pirate.ship.name.clone(),
+            captain: pirate.captain.clone(),
+            seen,
+        },
+        from: port,
+        client: format!("the governor of {}", history.ports[port].name),
+        civ: Some(owner),
+        reward: BOUNTY,
+        allowed: BOUNTY_DAYS * clock::MINUTES_PER_DAY,
+        deadline: 0,
+    })
+}
+
+/// somebody of `port`'s civilization stranded on a stretch of coast not too far away. famous
+/// captains get stranded too
+fn rescue(chart: &SeaChart, rng: &mut RandomNumberGenerator, port: PortId) -> Option<Contract> {
+    let world = chart.world();
+    let history = world.history();
+    let here = history.ports[port].location;
+    let owner = history.ports[port].owner;
+
+    let (location, hours) = (0..RESCUE_TRIES).find_map(|_| {
+        let mut offset = || {
+            let by = rng.range(RESCUE_DISTANCE.0, RESCUE_DISTANCE.1);
+            if rng.range(0, 2) == 0 {
+                -by
+            } else {
+                by
+            }
+        };
+        let (dx, dy) = (offset(), offset());
+        let (x, y) = (
+            here.0.checked_add_signed(dx)?,
+            here.1.checked_add_signed(dy)?,
+        );
+        if !world.in_bounds(x, y)
+            || world.is_navigable(x, y)
+            || world.is_frozen(x, y)
+            || world.port_at(x, y).is_some()
+        {
+            return None;
+        }
+
+        // somewhere on the shore a ship can get to
+        let shore = points_around(x as isize, y as isize)
+            .into_iter()
+            .filter_map(|(x, y)| Some((usize::try_from(x).ok()?, usize::try_from(y).ok()?)))
+            .find(|&(x, y)| world.is_navigable(x, y))?;
+        let route = chart.route(here, shore)?;
+
+        Some(((x, y), route.hours))
+    })?;
+
+    let captains: Vec<_> = history
+        .captains
+        .iter()
+        .filter(|captain| captain.civ == owner && captain.died.is_none())
+        .map(|captain| captain.name.clone())
+        .collect();
+    let who = match rng.random_slice_entry(&captains) {
+        Some(captain) => format!("captain {captain}"),
+        None => names::person(rng),
+    };
+
+    // there and back again
+    let (reward, allowed) = terms(hours * 2.);
+
+    Some(Contract {
+        client: format!("the family of {who}"),
+        kind: Kind::Rescue {
+            who,
+            location,
+            aboard: false,
+        },
+        from: port,
+        civ: Some(owner),
+        reward,
+        allowed,
+        deadline: 0,
+    })
+}
+
+/// the work going in `port` right now, drawn up afresh every few days
+fn offers(game: &mut Game, port: PortId) -> Vec<Contract> {
+    let today = game.clock.now() / clock::MINUTES_PER_DAY;
+    if let Some((day, offers)) = game.journal.offers.get(&port) {
+        if today < day + OFFER_DAYS {
+            return offers.clone();
+        }
+    }
+
+    let chart = SeaChart::new(&game.world, &game.waters);
+    let rng = &mut game.rng;
+    let count = rng.range(1, MAX_OFFERS + 1);
+    let offers: Vec<_> = (0..count)
+        .filter_map(|_| match rng.range(0, 5) {
+            0 => delivery(&chart, rng, port, false),
+            1 => delivery(&chart, rng, port, true),
+            2 => escort(&chart, rng, port),
+            3 => bounty(&game.world, &game.fleet, rng, port),
+            _ => rescue(&chart, rng, port),
+        })
+        .collect();
+
+    game.journal.offers.insert(port, (today, offers.clone()));
+
+    offers
+}
+
+/// take on the `i`th of the `offers` going in `port`. returns what came of it
+fn take_on(game: &mut Game, port: PortId, offers: &mut Vec<Contract>, i: usize) -> String {
+    let Some(contract) = offers.get(i) else {
+        return "there's no work going here".into();
+    };
+    if game.journal.active.len() >= MAX_CONTRACTS {
+        return format!("you can't take on more than {MAX_CONTRACTS} contracts at once");
+    }
+
+    match contract.kind {
+        Kind::Delivery { good, amount, .. } | Kind::Smuggling { good, amount, .. }
+            if game.player.free_hold() < amount =>
+        {
+            return format!("there's no room in the hold for {amount} {good}");
+        }
+        Kind::Bounty { npc, ref ship, .. } if game.fleet.get(npc).is_none() => {
+            let message = format!("somebody already got to {ship}");
+            offers.remove(i);
+            if let Some((_, left)) = game.journal.offers.get_mut(&port) {
+                left.clone_from(offers);
+            }
+            return message;
+        }
+        _ => {}
+    }
+
+    let mut contract = offers.remove(i);
+    let history = game.world.history();
+    let message = format!("you agreed to {}", contract.describe(&game.world));
+
+    // the client marks wherever the player needs to go on their charts
+    let marked = match &mut contract.kind {
+        Kind::Delivery { good, amount, to } | Kind::Smuggling { good, amount, to } => {
+            game.player.cargo[good.index()] += *amount;
+            Some(history.ports[*to].location)
+        }
+        Kind::Escort {
+            ship,
+            captain,
+            to,
+            npc,
+            cargo,
+        } => {
+            let id = game.fleet.charter(
+                &game.world,
+                contract.civ,
+                (port, *to),
+                (ship.clone(), captain.clone()),
+            );
+            *npc = Some(id);
+            *cargo = game.fleet.get(id).map_or(0, |npc| npc.ship.cargo_total());
+            Some(history.ports[*to].location)
+        }
+        Kind::Rescue { location, .. } => Some(*location),
+        Kind::Bounty { .. } => None,
+    };
+    if let Some(location) = marked {
+        game.fog.chart(location, CHART_RADIUS);
+    }
+
+    contract.deadline = game.clock.now() + contract.allowed;
+    game.journal.active.push(contract);
+    if let Some((_, left)) = game.journal.offers.get_mut(&port) {
+        left.clone_from(offers);
+    }
+
+    message
+}
+
+/// whether the `i`th contract the player has taken on is over and done with, one way or another
+fn check(game: &mut Game, i: usize) -> Option<Outcome> {
+    let docked = game.player.docked;
+    let contract = &mut game.journal.active[i];
+    let history = game.world.history();
+
+    let outcome = match &mut contract.kind {
+        Kind::Delivery { good, amount, to } if docked == Some(*to) => {
+            if game.player.cargo_of(*good) < *amount {
+                return None;
+            }
+
+            game.player.cargo[good.index()] -= *amount;
+            Outcome::Kept(format!(
+                "delivered {amount} {good} to {}",
+                history.ports[*to].name
+            ))
+        }
+        Kind::Smuggling { good, amount, to } if docked == Some(*to) => {
+            if game.player.cargo_of(*good) < *amount {
+                return None;
+            }
+
+            let port = &history.ports[*to];
+            if game.rng.range(0., 1.) < CUSTOMS_CHANCE {
+                // the goods are taken off the player when the contract is closed
+                game.reputation.change(port.owner, CAUGHT_STANDING);
+                Outcome::Broken(format!("the customs of {} found the {good}", port.name))
+            } else {
+                game.player.cargo[good.index()] -= *amount;
+                Outcome::Kept(format!("slipped {amount} {good} into {}", port.name))
+            }
+        }
+        Kind::Escort {
+            ship,
+            to,
+            npc: Some(id),
+            cargo,
+            ..
+        } => match game.fleet.get(*id) {
+            None => Outcome::Broken(format!("{ship} was lost at sea")),
+            Some(npc) if npc.ship.docked == Some(*to) => {
+                if distance(npc.ship.position, game.player.position) > ESCORT_RANGE {
+                    return None;
+                }
+                Outcome::Kept(format!("saw {ship} safely to {}", history.ports[*to].name))
+            }
+            Some(npc) if npc.ship.docked.is_none() && npc.ship.cargo_total() < *cargo => {
+                Outcome::Broken(format!("{ship} was plundered"))
+            }
+            Some(_) => return None,
+        },
+        Kind::Bounty { npc, ship, .. } if game.fleet.get(*npc).is_none() => {
+            Outcome::Void(format!("somebody else got to {ship} first"))
+        }
+        Kind::Rescue {
+            who,
+            location,
+            aboard,
+        } => {
+            if !*aboard && docked.is_none() && distance(*location, game.player.position) <= 1 {
+                *aboard = true;
+                game.message = format!("you took {who} aboard");
+            }
+            if *aboard && docked == Some(contract.from) {
+                Outcome::Kept(format!(
+                    "brought {who} home to {}",
+                    history.ports[contract.from].name
+                ))
+            } else {
+                return None;
+            }
+        }
+        _ => return None,
+    };
+
+    Some(outcome)
+}
+
+/// take back the goods a delivery or smuggling run put in the hold when it falls through, and
+/// charge for whatever isn't there any more at what it costs in the port it came from. returns
+/// what was taken, to go on the end of what happened
+fn take_back(game: &mut Game, contract: &Contract) -> Option<String> {
+    let (Kind::Delivery { good, amount, .. } | Kind::Smuggling { good, amount, .. }) =
+        contract.kind
+    else {
+        return None;
+    };
+
+    let returned = game.player.cargo_of(good).min(amount);
+    game.player.cargo[good.index()] -= returned;
+    let missing = amount - returned;
+    if missing == 0 {
+        return Some(format!(", and {amount} {good} were taken off you"));
+    }
+
+    let price = game
+        .economy
+        .market(contract.from)
+        .map_or(1, |market| market.buy_price(good));
+    let paid = missing.saturating_mul(price).min(game.gold);
+    game.gold -= paid;
+
+    Some(format!(
+        ", and {returned} {good} were taken off you and {paid} gold for the other {missing}"
+    ))
+}
+
+/// pay up or count the cost of a contract that's over. returns what happened
+fn close(game: &mut Game, contract: &Contract, outcome: Outcome) -> String {
+    if let Kind::Escort { npc: Some(id), .. } = contract.kind {
+        game.fleet.release(id);
+    }
+
+    let client = &contract.client;
+    let text = match outcome {
+        Outcome::Kept(what) => {
+            game.gold += contract.reward;
+            if let Some(civ) = contract.civ {
+                game.reputation.change(civ, KEPT_STANDING);
+            }
+            format!("{what}, and {client} paid {} gold", contract.reward)
+        }
+        Outcome::Broken(what) => {
+            if let Some(civ) = contract.civ {
+                game.reputation.change(civ, BROKEN_STANDING);
+            }
+            let taken = take_back(game, contract).unwrap_or_default();
+            format!("{what}. {client} won't forget it{taken}")
+        }
+        Outcome::Void(what) => format!("{what}, and {client} called it off"),
+    };
+
+    game.journal
+        .entries
+        .push(format!("{}: {text}", game.clock.date()));
+
+    text
+}
+
+/// settle every contract that's over, kept or broken. run whenever time passes
+pub fn review(game: &mut Game) {
+    let now = game.clock.now();
+    let mut i = 0;
+
+    while i < game.journal.active.len() {
+        let outcome = check(game, i).or_else(|| {
+            let contract = &game.journal.active[i];
+            (now > contract.deadline).then(|| {
+                Outcome::Broken(format!(
+                    "you ran out of time to {}",
+                    contract.describe(&game.world)
+                ))
+            })
+        });
+
+        match outcome {
+            Some(outcome) => {
+                let contract = game.journal.active.remove(i);
+                game.message = close(game, &contract, outcome);
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// the player took or sank `id`. returns what came of any bounty on her
+pub fn claim_bounty(game: &mut Game, id: NpcId) -> Option<String> {
+    let i = game
+        .journal
+        .active
+        .iter()
+        .position(|contract| matches!(contract.kind, Kind::Bounty { npc, .. } if npc == id))?;
+
+    let contract = game.journal.active.remove(i);
+    let Kind::Bounty { ref ship, .. } = contract.kind else {
+        return None;
+    };
+    let what = format!("brought the pirate {ship} to justice");
+
+    Some(close(game, &contract, Outcome::Kept(what)))
+}
+
+/// give up on the `i`th contract the player has taken on. returns what came of it
+fn give_up(game: &mut Game, i: usize) -> String {
+    if i >= game.journal.active.len() {
+        return "you haven't taken on any contracts".into();
+    }
+
+    let contract = game.journal.active.remove(i);
+    let what = format!(
+        "you gave up on having to {}",
+        contract.describe(&game.world)
+    );
+
+    close(game, &contract, Outcome::Broken(what))
+}
+
+/// the work going in port, the player's contracts and the journal, with the selected contract
+/// picked out in whichever list is being looked at
+fn frame(
+    game: &Game,
+    port: Option<PortId>,
+    offers: &[Contract],
+    (active_list, selected): (bool, usize),
+    message: &str,
+) -> Frame {
+    let world = &game.world;
+    let mut frame = Frame::blank(game.screen);
+
+    let title = match port {
+        Some(port) => format!(
+            "Contracts at {} - {} gold",
+            world.history().ports[port].name,
+            game.gold
+        ),
+        None => format!("Contracts - {} gold", game.gold),
+    };
+    frame.print(0, 0, &title, YELLOW, BLACK);
+    frame.print(
+        0,
+        1,
+        "[up/down] pick  [tab] switch list  [enter] take on  [d] give up  [esc] back",
+        GRAY,
+        BLACK,
+    );
+    frame.print(0, 2, message, WHITE, BLACK);
+
+    let now = game.clock.now();
+    let offered: Vec<_> = offers.iter().map(|c| c.line(world, None)).collect();
+    let taken: Vec<_> = game
+        .journal
+        .active
+        .iter()
+        .map(|c| c.line(world, Some(now)))
+        .collect();
+    let lists = [
+        ("work going here", offered, false),
+        ("your contracts", taken, true),
+    ];
+
+    let mut row = 4;
+    for (title, lines, is_active) in lists {
+        frame.print(0, row, title, YELLOW, BLACK);
+        row += 1;
+
+        for (i, line) in lines.iter().enumerate() {
+            let bg = if is_active == active_list && i == selected {
+                NAVY
+            } else {
+                BLACK
+            };
+            frame.print(0, row, line, WHITE, bg);
+            row += 1;
+        }
+        row += 1;
+    }
+
+    frame.print(0, row, "journal", YELLOW, BLACK);
+    let entries = &game.journal.entries;
+    for (i, entry) in entries[entries.len().saturating_sub(JOURNAL_LINES)..]
+        .iter()
+        .rev()
+        .enumerate()
+    {
+        frame.print(0, row + 1 + i, &format!("  {entry}"), GRAY, BLACK);
+    }
+
+    frame
+}
+
+/// show the contracts screen until the player backs out of it. work can only be taken on in port
+///
+/// returns false if the window was closed while looking
+pub fn contracts(
+    game: &mut Game,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send contracts to render thread. Error: {e}");
+        }
+    };
+
+    let port = game.player.docked;
+    let mut offers = match port {
+        Some(port) => offers(game, port),
+        None => Vec::new(),
+    };
+    // whether the player's own contracts are being looked at, rather than the work going
+    let mut active_list = port.is_none();
+    let mut selected = 0;
+    let mut message = String::new();
+
+    send(frame(
+        game,
+        port,
+        &offers,
+        (active_list, selected),
+        &message,
+    ));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        let length = if active_list {
+            game.journal.active.len()
+        } else {
+            offers.len()
+        };
+        match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::J => return true,
+            VirtualKeyCode::Tab => {
+                active_list = !active_list;
+                selected = 0;
+            }
+            VirtualKeyCode::Up => selected = selected.saturating_sub(1),
+            VirtualKeyCode::Down => selected = (selected + 1).min(length.saturating_sub(1)),
+            VirtualKeyCode::Return if !active_list => {
+                message = match port {
+                    Some(port) => take_on(game, port, &mut offers, selected),
+                    None => "you need to be in port to find work".into(),
+                };
+                selected = selected.min(offers.len().saturating_sub(1));
+            }
+            VirtualKeyCode::D if active_list => {
+                message = give_up(game, selected);
+                selected = selected.min(game.journal.active.len().saturating_sub(1));
+            }
+            _ => continue,
+        }
+
+        send(frame(
+            game,
+            port,
+            &offers,
+            (active_list, selected),
+            &message,
+        ));
+    }
+
+    // the render thread hung up on us
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a game docked at one port, and another port to go to
+    fn game() -> (Game, PortId, PortId) {
+        let mut game = Game::for_tests(4);
+        let ports: Vec<_> = game
+            .world
+            .history()
+            .living_ports()
+            .map(|(id, _)| id)
+            .collect();
+        let (from, to) = (ports[0], ports[1]);
+        game.player.docked = Some(from);
+        game.player.position = game.world.history().ports[from].location;
+
+        (game, from, to)
+    }
+
+    fn contract(game: &Game, from: PortId, kind: Kind) -> Contract {
+        Contract {
+            kind,
+            from,
+            client: "the merchants of somewhere".into(),
+            civ: Some(game.world.history().ports[from].owner),
+            reward: 100,
+            allowed: clock::MINUTES_PER_DAY,
+            deadline: 0,
+        }
+    }
+
+    /// take on a contract for `kind` drawn up in `from`
+    fn take(game: &mut Game, from: PortId, kind: Kind) {
+        let contract = contract(game, from, kind);
+        take_on(game, from, &mut vec![contract], 0);
+        assert_eq!(game.journal.active.len(), 1);
+    }
+
+    #[test]
+    fn deliveries_get_paid_for() {
+        let (mut game, from, to) = game();
+        let kind = Kind::Delivery {
+            good: Good::Rum,
+            amount: 10,
+            to,
+        };
+        take(&mut game, from, kind);
+        assert_eq!(game.player.cargo_of(Good::Rum), 10);
+
+        // nothing happens until the player gets there
+        review(&mut game);
+        assert_eq!(game.journal.active.len(), 1);
+
+        let gold = game.gold;
+        game.player.docked = Some(to);
+        review(&mut game);
+        assert!(game.journal.active.is_empty());
+        assert_eq!(game.player.cargo_of(Good::Rum), 0);
+        assert_eq!(game.gold, gold + 100);
+        assert!(
+            game.reputation
+                .standing(game.world.history().ports[from].owner)
+                > 0
+        );
+    }
+
+    #[test]
+    fn given_up_deliveries_are_taken_back() {
+        let (mut game, from, to) = game();
+        let kind = Kind::Smuggling {
+            good: Good::Rum,
+            amount: 10,
+            to,
+        };
+        take(&mut game, from, kind);
+        game.player.cargo[Good::Rum.index()] += 5;
+
+        let gold = game.gold;
+        give_up(&mut game, 0);
+        assert!(game.journal.active.is_empty());
+        // only what the contract put there
+        assert_eq!(game.player.cargo_of(Good::Rum), 5);
+        assert_eq!(game.gold, gold);
+    }
+
+    #[test]
+    fn running_out_of_time_costs_whatever_was_sold() {
+        let (mut game, from, to) = game();
+        let owner = game.world.history().ports[from].owner;
+        let kind = Kind::Delivery {
+            good: Good::Iron,
+            amount: 10,
+            to,
+        };
+        take(&mut game, from, kind);
+        // sold off on the quiet
+        game.player.cargo[Good::Iron.index()] -= 4;
+        let price = game.economy.market(from).unwrap().buy_price(Good::Iron);
+        let gold = game.gold;
+
+        game.clock.advance(clock::MINUTES_PER_DAY);
+        review(&mut game);
+        assert_eq!(game.journal.active.len(), 1);
+
+        game.clock.advance(1);
+        review(&mut game);
+        assert!(game.journal.active.is_empty());
+        assert!(game.message.contains("ran out of time"), "{}", game.message);
+        assert_eq!(game.player.cargo_of(Good::Iron), 0);
+        assert_eq!(game.gold, gold - (4 * price).min(gold));
+        assert_eq!(game.reputation.standing(owner), BROKEN_STANDING);
+    }
+
+    #[test]
+    fn bounties_pay_out_once() {
+        let (mut game, from, _) = game();
+        let pirate = game.fleet.pirates().next().unwrap();
+        let kind = Kind::Bounty {
+            npc: pirate.id,
+            ship: pirate.ship.name.clone(),
+            captain: pirate.captain.clone(),
+            seen: from,
+        };
+        let id = pirate.id;
+        take(&mut game, from, kind);
+
+        let gold = game.gold;
+        assert!(claim_bounty(&mut game, id + 1000).is_none());
+        assert!(claim_bounty(&mut game, id).is_some());
+        assert_eq!(game.gold, gold + 100);
+        assert!(game.journal.active.is_empty());
+        assert!(claim_bounty(&mut game, id).is_none());
+    }
+
+    #[test]
+    fn plundered_escorts_break_the_contract() {
+        let (mut game, from, to) = game();
+        let kind = Kind::Escort {
+            ship: "Escorted".into(),
+            captain: "Captain".into(),
+            to,
+            npc: None,
+            cargo: 0,
+        };
+        take(&mut game, from, kind);
+        let Kind::Escort { npc: Some(id), .. } = game.journal.active[0].kind else {
+            panic!("the escort never set sail");
+        };
+
+        // still in port with everything aboard
+        review(&mut game);
+        assert_eq!(game.journal.active.len(), 1);
+
+        let escorted = &mut game.fleet.get_mut(id).unwrap().ship;
+        escorted.docked = None;
+        escorted.cargo = Default::default();
+        review(&mut game);
+        assert!(game.journal.active.is_empty());
+        assert!(game.message.contains("was plundered"), "{}", game.message);
+    }
+}
@@ -0,0 +1,409 @@
+//! the player's crew, every one of them somebody with a name
+//!
+//! sailors are better at some jobs than others, and how good the crew is at a job as a whole
+//! changes how well the ship sails and fights. they want paying every week, and when they aren't
+//! paid or the going gets rough they lose heart. unhappy sailors with no loyalty to the ship jump
+//! ship the next time she's in port
+
+use std::fmt::Display;
+
+use bracket_lib::random::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::worldgen::names;
+
+pub const SKILL_COUNT: usize = 4;
+/// the best anyone gets at anything. the worst is 1
+pub const MAX_SKILL: u8 = 5;
+/// how many hands the player's ship has room for
+pub const BERTHS: usize = 40;
+/// how many days apart wages are paid
+pub const PAYDAY_DAYS: u64 = 7;
+/// sailors want this many weeks of wages up front to sign on
+pub const SIGNING_WEEKS: u32 = 2;
+const MAX_MORALE: u8 = 100;
+const MAX_LOYALTY: u8 = 100;
+/// below this morale, sailors start thinking about leaving
+const UNHAPPY: u8 = 25;
+/// how many days a wound takes to heal, at most
+const WORST_WOUND: u32 = 20;
+/// wounds heal this many times faster with a surgeon ashore
+const SHORE_HEALING: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skill {
+    /// laying and working the guns
+    Gunnery,
+    /// finding the quickest way across the sea
+    Navigation,
+    /// handling sails, and patching them up
+    Rigging,
+    /// fighting hand to hand
+    Melee,
+}
+
+impl Skill {
+    pub const ALL: [Skill; SKILL_COUNT] = [
+        Skill::Gunnery,
+        Skill::Navigation,
+        Skill::Rigging,
+        Skill::Melee,
+    ];
+
+    /// where this skill goes in a sailor's skills
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for Skill {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Skill::Gunnery => "gunnery",
+            Skill::Navigation => "navigation",
+            Skill::Rigging => "rigging",
+            Skill::Melee => "melee",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sailor {
+    pub name: String,
+    /// 1 to [`MAX_SKILL`] for every skill, indexed by [`Skill::index`]
+    skills: [u8; SKILL_COUNT],
+    /// how happy they are aboard, up to [`MAX_MORALE`]
+    pub morale: u8,
+    /// how much they'll put up with before leaving, up to [`MAX_LOYALTY`]
+    pub loyalty: u8,
+    /// gold a week
+    pub wage: u32,
+    /// days until their wounds heal. 0 for anyone fit for duty
+    pub injured: u32,
+}
+
+impl Sailor {
+    /// somebody looking for a berth
+    pub fn random(rng: &mut RandomNumberGenerator) -> Self {
+        // the worse of two rolls, so old hands are rarer than green ones
+        let mut skills = [1; SKILL_COUNT];
+        for skill in &mut skills {
+            *skill = rng.range(1, MAX_SKILL + 1).min(rng.range(1, MAX_SKILL + 1));
+        }
+        let total: u32 = skills.iter().map(|&s| u32::from(s)).sum();
+
+        Sailor {
+            name: names::person(rng),
+            skills,
+            morale: rng.range(50, 80),
+            loyalty: rng.range(20, 50),
+            wage: 2 + total / 2,
+            injured: 0,
+        }
+    }
+
+    pub fn skill(&self, skill: Skill) -> u8 {
+        self.skills[skill.index()]
+    }
+
+    pub fn is_fit(&self) -> bool {
+        self.injured == 0
+    }
+
+    /// what it costs to get them to sign on
+    pub fn signing_fee(&self) -> u32 {
+        self.wage * SIGNING_WEEKS
+    }
+
+    fn cheer(&mut self, by: i32) {
+        self.morale = change(self.morale, by, MAX_MORALE);
+    }
+}
+
+/// `value` changed by `by`, kept between 0 and `max`
+#[allow(clippy::cast_sign_loss)]
+fn change(value: u8, by: i32, max: u8) -> u8 {
+    (i32::from(value) + by).clamp(0, i32::from(max)) as u8
+}
+
+/// everyone aboard the player's ship
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Crew {
+    pub sailors: Vec<Sailor>,
+}
+
+impl Crew {
+    pub fn new(rng: &mut RandomNumberGenerator, hands: u32) -> Self {
+        Crew {
+            sailors: (0..hands).map(|_| Sailor::random(rng)).collect(),
+        }
+    }
+
+    /// how many hands are fit for duty
+    pub fn fit(&self) -> u32 {
+        self.sailors.iter().filter(|s| s.is_fit()).count() as u32
+    }
+
+    pub fn injured(&self) -> u32 {
+        self.sailors.len() as u32 - self.fit()
+    }
+
+    /// what the whole crew wants paying every week
+    pub fn wages(&self) -> u32 {
+        self.sailors.iter().map(|s| s.wage).sum()
+    }
+
+    /// how happy the crew is on the whole
+    pub fn morale(&self) -> u8 {
+        let total: u32 = self.sailors.iter().map(|s| u32::from(s.morale)).sum();
+
+        total
+            .checked_div(self.sailors.len() as u32)
+            .map_or(0, |m| m as u8)
+    }
+
+    /// how much better or worse than an ordinary crew this one is at `skill`, as a multiplier.
+    /// navigation comes down to whoever is best at it, everything else takes everyone
+    pub fn modifier(&self, skill: Skill) -> f64 {
+        let fit = self.sailors.iter().filter(|s| s.is_fit());
+        let level = if skill == Skill::Navigation {
+            fit.map(|s| f64::from(s.skill(skill))).fold(1., f64::max) - 2.
+        } else {
+            let (total, count) = fit.fold((0., 0.), |(total, count), s| {
+                (total + f64::from(s.skill(skill)), count + 1.)
+            });
+            if count == 0. {
+                0.
+            } else {
+                total / count
+            }
+        };
+
+        // a crew of ordinary sailors is somewhere around 2
+        (0.6 + 0.2 * level).max(0.5)
+    }
+
+    pub fn hire(&mut self, sailor: Sailor) {
+        self.sailors.push(sailor);
+    }
+
+    pub fn dismiss(&mut self, i: usize) -> Option<Sailor> {
+        (i < self.sailors.len()).then(|| self.sailors.remove(i))
+    }
+
+    /// change everyone's morale by `by`
+    pub fn cheer(&mut self, by: i32) {
+        for sailor in &mut self.sailors {
+            sailor.cheer(by);
+        }
+    }
+
+    /// `count` of the fit hands are hit, some of them killed and the rest wounded. returns how
+    /// many of each
+    pub fn casualties(&mut self, count: u32, rng: &mut RandomNumberGenerator) -> (u32, u32) {
+        let (mut killed, mut wounded) = (0, 0);
+
+        for _ in 0..count {
+            let fit: Vec<_> = (0..self.sailors.len())
+                .filter(|&i| self.sailors[i].is_fit())
+                .collect();
+            let Some(&i) = rng.random_slice_entry(&fit) else {
+                break;
+            };
+
+            if rng.range(0, 2) == 0 {
+                self.sailors.remove(i);
+                killed += 1;
+            } else {
+                self.sailors[i].injured = rng.range(1, WORST_WOUND + 1);
+                wounded += 1;
+            }
+        }
+
+        // losing shipmates is hard on everyone
+        self.cheer(-(killed as i32) * 2);
+
+        (killed, wounded)
+    }
+
+    /// a day goes by. wounds heal, and the crew gets fed up with the sea or enjoys shore leave
+    pub fn day(&mut self, docked: bool) {
+        for sailor in &mut self.sailors {
+            let healing = if docked { SHORE_HEALING } else { 1 };
+            sailor.injured = sailor.injured.saturating_sub(healing);
+            sailor.cheer(if docked { 3 } else { -1 });
+        }
+    }
+
+    /// pay everyone out of `gold`, as far as it goes. returns a word about how it went
+    pub fn payday(&mut self, gold: &mut u32) -> String {
+        let mut unpaid = 0;
+
+        for sailor in &mut self.sailors {
+            if *gold >= sailor.wage {
+                *gold -= sailor.wage;
+                sailor.cheer(5);
+                // another week served under the same flag
+                if sailor.morale > MAX_MORALE / 2 {
+                    sailor.loyalty = change(sailor.loyalty, 2, MAX_LOYALTY);
+                }
+            } else {
+                sailor.cheer(-20);
+                sailor.loyalty = change(sailor.loyalty, -10, MAX_LOYALTY);
+                unpaid += 1;
+            }
+        }
+
+        match unpaid {
+            0 => format!("payday, {} gold in wages", self.wages()),
+            _ => format!("payday, and {unpaid} of the crew went without. they aren't happy"),
+        }
+    }
+
+    /// unhappy sailors with no reason to stay walk off the ship. returns who left
+    pub fn desert(&mut self, rng: &mut RandomNumberGenerator) -> Vec<String> {
+        let mut deserters = Vec::new();
+
+        self.sailors.retain(|sailor| {
+            let leaving = sailor.morale < UNHAPPY && rng.range(0, MAX_LOYALTY) >= sailor.loyalty;
+            if leaving {
+                deserters.push(sailor.name.clone());
+            }
+            !leaving
+        });
+
+        deserters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sailor(skill: u8, morale: u8, loyalty: u8) -> Sailor {
+        Sailor {
+            name: format!("sailor {skill} {morale} {loyalty}"),
+            skills: [skill; SKILL_COUNT],
+            morale,
+            loyalty,
+            wage: 6,
+            injured: 0,
+        }
+    }
+
+    fn crew(sailors: Vec<Sailor>) -> Crew {
+        Crew { sailors }
+    }
+
+    #[test]
+    fn skills_change_how_well_the_crew_does() {
+        let ordinary = crew(vec![sailor(2, 50, 50); 4]);
+        for skill in Skill::ALL {
+            if skill != Skill::Navigation {
+                assert!((ordinary.modifier(skill) - 1.).abs() < 1e-9);
+            }
+        }
+
+        // one good navigator is all it takes
+        let mut navigated = ordinary.clone();
+        navigated.hire(sailor(5, 50, 50));
+        assert!((navigated.modifier(Skill::Navigation) - 1.2).abs() < 1e-9);
+        assert!(navigated.modifier(Skill::Gunnery) > ordinary.modifier(Skill::Gunnery));
+
+        // but the wounded don't help
+        navigated.sailors[4].injured = 3;
+        assert!(
+            (navigated.modifier(Skill::Navigation) - ordinary.modifier(Skill::Navigation)).abs()
+                < 1e-9
+        );
+
+        let empty = Crew::default();
+        for skill in Skill::ALL {
+            assert!(empty.modifier(skill) >= 0.5);
+        }
+    }
+
+    #[test]
+    fn morale_and_loyalty_stay_in_bounds() {
+        let mut crew = crew(vec![sailor(2, 98, 99), sailor(2, 1, 0)]);
+
+        crew.cheer(10);
+        assert_eq!(crew.sailors[0].morale, MAX_MORALE);
+        crew.cheer(-200);
+        assert!(crew.sailors.iter().all(|s| s.morale == 0));
+
+        // a long voyage wears everyone down, shore leave cheers them back up
+        crew.cheer(50);
+        crew.day(false);
+        assert!(crew.sailors.iter().all(|s| s.morale == 49));
+        crew.day(true);
+        assert!(crew.sailors.iter().all(|s| s.morale == 52));
+
+        let mut gold = 100;
+        crew.payday(&mut gold);
+        assert_eq!(crew.sailors[0].loyalty, MAX_LOYALTY);
+        assert_eq!(crew.sailors[1].loyalty, 2);
+    }
+
+    #[test]
+    fn short_paydays_pay_whoever_they_can() {
+        let mut crew = crew(vec![sailor(2, 50, 50), sailor(2, 50, 50)]);
+        let mut gold = 10;
+
+        let message = crew.payday(&mut gold);
+        assert_eq!(gold, 4);
+        assert!(message.contains("1 of the crew went without"));
+
+        assert_eq!(crew.sailors[0].morale, 55);
+        assert_eq!(crew.sailors[0].loyalty, 52);
+        assert_eq!(crew.sailors[1].morale, 30);
+        assert_eq!(crew.sailors[1].loyalty, 40);
+
+        let mut gold = 12;
+        assert_eq!(crew.payday(&mut gold), "payday, 12 gold in wages");
+        assert_eq!(gold, 0);
+    }
+
+    #[test]
+    fn only_unhappy_disloyal_sailors_desert() {
+        let mut rng = RandomNumberGenerator::seeded(3);
+        let mut crew = crew(vec![
+            sailor(2, 80, 0),
+            sailor(2, 0, MAX_LOYALTY),
+            sailor(2, 0, 0),
+        ]);
+        let leaving = crew.sailors[2].name.clone();
+
+        for _ in 0..50 {
+            let deserters = crew.desert(&mut rng);
+            assert!(deserters.iter().all(|name| *name == leaving));
+        }
+        assert_eq!(crew.sailors.len(), 2);
+        assert!(crew.sailors.iter().all(|s| s.name != leaving));
+    }
+
+    #[test]
+    fn casualties_are_killed_or_wounded_and_heal() {
+        let mut rng = RandomNumberGenerator::seeded(8);
+        let mut crew = crew(vec![sailor(2, 50, 50); 10]);
+
+        let (killed, wounded) = crew.casualties(6, &mut rng);
+        assert_eq!(killed + wounded, 6);
+        assert_eq!(crew.sailors.len() as u32, 10 - killed);
+        assert_eq!(crew.injured(), wounded);
+        assert_eq!(crew.fit(), 4);
+        assert!(crew.sailors.iter().all(|s| s.injured <= WORST_WOUND));
+
+        // nobody is hit twice, and there's only so many to hit
+        let (killed, wounded) = crew.casualties(10, &mut rng);
+        assert_eq!(killed + wounded, 4);
+        assert_eq!(crew.fit(), 0);
+
+        for _ in 0..WORST_WOUND / SHORE_HEALING {
+            crew.day(true);
+        }
+        assert_eq!(crew.injured(), 0);
+    }
+}
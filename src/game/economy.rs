@@ -0,0 +1,429 @@
+//! goods, and the markets in every port that buy and sell them
+//!
+//! every port makes goods from the land around it and uses them up feeding its people. whatever is
+//! left over piles up in the market, and prices follow how much of something is piled up compared
+//! to how much the port wants to keep around
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::Point;
+use crate::worldgen::climate::Biome;
+use crate::worldgen::history::{History, PortId};
+use crate::worldgen::FullWorld;
+
+pub const GOOD_COUNT: usize = 8;
+
+/// an amount of every good, indexed by [`Good::index`]
+pub type Goods = [f64; GOOD_COUNT];
+
+/// how far from a port its people go to farm, fish, log and mine
+const HINTERLAND: isize = 5;
+/// the number of days of use a port likes to have stocked up
+const DAYS_OF_SUPPLY: f64 = 30.;
+/// a market never wants less than this much of anything, so even unused goods have a price
+const MIN_WANTED: f64 = 10.;
+/// once a market has this many times what it wants, the rest spoils or gets shipped away
+const MAX_STOCK: f64 = 4.;
+/// prices never go further than this many times above or below the base price
+const PRICE_SPREAD: f64 = 5.;
+/// how much more a market charges than it pays, as a fraction of the price
+const MARGIN: f64 = 0.1;
+/// how many days markets are run for before the game starts, to settle prices down
+const WARM_UP_DAYS: u32 = 30;
+/// how much more timber and iron a civilization at war goes through
+const WAR_USE: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Good {
+    Provisions,
+    Timber,
+    Sugar,
+    Spices,
+    Furs,
+    Iron,
+    Cloth,
+    Rum,
+}
+
+impl Good {
+    pub const ALL: [Good; GOOD_COUNT] = [
+        Good::Provisions,
+        Good::Timber,
+        Good::Sugar,
+        Good::Spices,
+        Good::Furs,
+        Good::Iron,
+        Good::Cloth,
+        Good::Rum,
+    ];
+
+    /// where this good goes in a [`Goods`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// what a unit sells for in a market with exactly as much as it wants
+    fn base_price(self) -> f64 {
+        match self {
+            Good::Provisions => 4.,
+            Good::Timber => 6.,
+            Good::Sugar => 10.,
+            Good::Iron => 14.,
+            Good::Cloth => 16.,
+            Good::Rum => 20.,
+            Good::Furs => 25.,
+            Good::Spices => 40.,
+        }
+    }
+
+    /// how much a thousand people go through in a day
+    fn consumption(self) -> f64 {
+        match self {
+            Good::Provisions => 8.,
+            Good::Timber => 2.,
+            Good::Sugar | Good::Iron | Good::Cloth => 1.,
+            Good::Rum => 0.8,
+            Good::Spices | Good::Furs => 0.3,
+        }
+    }
+}
+
+impl Display for Good {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Good::Provisions => "provisions",
+            Good::Timber => "timber",
+            Good::Sugar => "sugar",
+            Good::Spices => "spices",
+            Good::Furs => "furs",
+            Good::Iron => "iron",
+            Good::Cloth => "cloth",
+            Good::Rum => "rum",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// what a single tile of a biome produces in a day, if there are people to work it
+fn yields(biome: Biome) -> Goods {
+    let mut goods = [0.; GOOD_COUNT];
+    let mut add = |good: Good, amount: f64| goods[good.index()] += amount;
+
+    match biome {
+        Biome::Ocean => add(Good::Provisions, 0.3),
+        Biome::Beach => add(Good::Provisions, 0.5),
+        Biome::Grassland => {
+            add(Good::Provisions, 1.5);
+            add(Good::Cloth, 0.2);
+        }
+        Biome::Forest => {
+            add(Good::Timber, 1.5);
+            add(Good::Furs, 0.1);
+            add(Good::Provisions, 0.3);
+        }
+        Biome::Jungle => {
+            add(Good::Sugar, 0.8);
+            add(Good::Spices, 0.2);
+            add(Good::Timber, 0.5);
+        }
+        Biome::Desert => {
+            add(Good::Spices, 0.1);
+            add(Good::Iron, 0.2);
+        }
+        Biome::Tundra => {
+            add(Good::Furs, 0.4);
+            add(Good::Provisions, 0.1);
+        }
+        Biome::Mountain => add(Good::Iron, 0.6),
+    }
+
+    goods
+}
+
+/// the market of a single port
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Market {
+    /// how much of everything is for sale
+    stock: Goods,
+    /// how much of everything the port makes in a day
+    production: Goods,
+    /// how much of everything the port uses up in a day, in peacetime
+    consumption: Goods,
+    /// whether the port's civilization is fighting anyone, which it could start or stop doing at
+    /// any time
+    #[serde(default)]
+    at_war: bool,
+}
+
+impl Market {
+    /// set up the market of `port`, from the land around it and the civilization that owns it
+    fn new(world: &FullWorld, port: PortId) -> Self {
+        let history = world.history();
+        let location = history.ports[port].location;
+        let population = f64::from(history.ports[port].population);
+        let civ = history.ports[port].owner;
+
+        let mut production = [0.; GOOD_COUNT];
+        for (x, y) in hinterland(world, location) {
+            for (total, amount) in production.iter_mut().zip(yields(world.biome_at(x, y))) {
+                *total += amount;
+            }
+        }
+
+        // more hands work more land, but there is only so much land to go round
+        let workforce = population / (population + 1000.);
+        for amount in &mut production {
+            *amount *= workforce;
+        }
+
+        // bigger civilizations have the workshops and distilleries to turn raw goods into finer ones
+        let civ_ports = history.ports_of(civ).count() as f64;
+        let industry = (civ_ports / 5.).min(1.) * population / 1000.;
+        production[Good::Cloth.index()] += industry;
+        production[Good::Rum.index()] += production[Good::Sugar.index()] * 0.3 * (1. + industry);
+
+        let mut consumption = [0.; GOOD_COUNT];
+        for good in Good::ALL {
+            consumption[good.index()] = good.consumption() * population / 1000.;
+        }
+
+        let mut market = Market {
+            stock: [0.; GOOD_COUNT],
+            production,
+            consumption,
+            at_war: history.at_war(civ),
+        };
+        for good in Good::ALL {
+            market.stock[good.index()] = market.wanted(good);
+        }
+        for _ in 0..WARM_UP_DAYS {
+            market.tick();
+        }
+
+        market
+    }
+
+    /// how much of `good` the port uses up in a day right now
+    fn used(&self, good: Good) -> f64 {
+        let used = self.consumption[good.index()];
+
+        // a civilization at war burns through timber and iron
+        if self.at_war && matches!(good, Good::Timber | Good::Iron) {
+            used * WAR_USE
+        } else {
+            used
+        }
+    }
+
+    /// how much of `good` the market would like to have
+    fn wanted(&self, good: Good) -> f64 {
+        (self.used(good) * DAYS_OF_SUPPLY).max(MIN_WANTED)
+    }
+
+    /// a day of making and using up goods
+    fn tick(&mut self) {
+        for good in Good::ALL {
+            let i = good.index();
+            let stock = self.stock[i] + self.production[i] - self.used(good);
+
+            self.stock[i] = stock.clamp(0., self.wanted(good) * MAX_STOCK);
+        }
+    }
+
+    /// how much of `good` is for sale, in whole units
+    #[allow(clippy::cast_sign_loss)]
+    pub fn stock(&self, good: Good) -> u32 {
+        self.stock[good.index()].floor() as u32
+    }
+
+    /// the going rate for `good`, before the market takes its cut
+    fn price(&self, good: Good) -> f64 {
+        let scarcity = (self.wanted(good) + 1.) / (self.stock[good.index()] + 1.);
+
+        good.base_price() * scarcity.clamp(1. / PRICE_SPREAD, PRICE_SPREAD)
+    }
+
+    /// what it costs to buy a unit of `good` here
+    #[allow(clippy::cast_sign_loss)]
+    pub fn buy_price(&self, good: Good) -> u32 {
+        (self.price(good) * (1. + MARGIN)).ceil().max(1.) as u32
+    }
+
+    /// what the market pays for a unit of `good`
+    #[allow(clippy::cast_sign_loss)]
+    pub fn sell_price(&self, good: Good) -> u32 {
+        (self.price(good) * (1. - MARGIN)).floor().max(1.) as u32
+    }
+
+    /// take `amount` of `good` out of the market. returns false, and takes nothing, if there
+    /// isn't that much
+    pub fn remove(&mut self, good: Good, amount: u32) -> bool {
+        if self.stock(good) < amount {
+            return false;
+        }
+
+        self.stock[good.index()] -= f64::from(amount);
+        true
+    }
+
+    /// put `amount` of `good` into the market
+    pub fn add(&mut self, good: Good, amount: u32) {
+        self.stock[good.index()] += f64::from(amount);
+    }
+}
+
+/// every tile close enough to `location` to be worked by its port
+fn hinterland(
+    world: &FullWorld,
+    location: Point<usize>,
+) -> impl Iterator<Item = Point<usize>> + '_ {
+    (-HINTERLAND..=HINTERLAND)
+        .flat_map(|dy| (-HINTERLAND..=HINTERLAND).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| dx * dx + dy * dy <= HINTERLAND * HINTERLAND)
+        .filter_map(move |(dx, dy)| {
+            Some((
+                location.0.checked_add_signed(dx)?,
+                location.1.checked_add_signed(dy)?,
+            ))
+        })
+        .filter(|&(x, y)| world.in_bounds(x, y) && !world.is_frozen(x, y))
+}
+
+/// the markets of every port in the world
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Economy {
+    /// indexed by [`PortId`]. None for ports that were gone before the game started
+    markets: Vec<Option<Market>>,
+}
+
+impl Economy {
+    pub fn new(world: &FullWorld) -> Self {
+        let history = world.history();
+
+        Economy {
+            markets: (0..history.ports.len())
+                .map(|port| {
+                    history.ports[port]
+                        .is_alive()
+                        .then(|| Market::new(world, port))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn market(&self, port: PortId) -> Option<&Market> {
+        self.markets.get(port)?.as_ref()
+    }
+
+    pub fn market_mut(&mut self, port: PortId) -> Option<&mut Market> {
+        self.markets.get_mut(port)?.as_mut()
+    }
+
+    /// a day passes in every market, with whatever wars are going on in `history` today
+    pub fn tick(&mut self, history: &History) {
+        for (port, market) in self.markets.iter_mut().enumerate() {
+            let Some(market) = market else {
+                continue;
+            };
+
+            market.at_war = history
+                .ports
+                .get(port)
+                .is_some_and(|port| history.at_war(port.owner));
+            market.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{Cancel, RectDimension};
+    use crate::worldgen::history::Relation;
+    use crate::worldgen::{gen_full_world, GenParam};
+
+    use super::*;
+
+    /// a market making and using 10 of everything a day, with a month's worth in stock
+    fn market() -> Market {
+        Market {
+            stock: [300.; GOOD_COUNT],
+            production: [10.; GOOD_COUNT],
+            consumption: [10.; GOOD_COUNT],
+            at_war: false,
+        }
+    }
+
+    #[test]
+    fn scarce_goods_cost_more() {
+        let mut market = market();
+        let settled = market.price(Good::Rum);
+        assert!((settled - Good::Rum.base_price()).abs() < 1e-9);
+
+        market.stock[Good::Rum.index()] = 100.;
+        let scarce = market.price(Good::Rum);
+        market.stock[Good::Rum.index()] = 900.;
+        let glut = market.price(Good::Rum);
+
+        assert!(scarce > settled && settled > glut);
+        assert!(market.buy_price(Good::Rum) > market.sell_price(Good::Rum));
+    }
+
+    #[test]
+    fn prices_stay_within_the_spread() {
+        let mut market = market();
+
+        for good in Good::ALL {
+            market.stock[good.index()] = 0.;
+            let top = good.base_price() * PRICE_SPREAD;
+            assert!((market.price(good) - top).abs() < 1e-9, "{good}");
+
+            market.stock[good.index()] = 1e12;
+            let bottom = good.base_price() / PRICE_SPREAD;
+            assert!((market.price(good) - bottom).abs() < 1e-9, "{good}");
+            assert!(market.sell_price(good) >= 1);
+        }
+    }
+
+    #[test]
+    fn wars_started_during_play_reach_prices() {
+        let params = GenParam {
+            seed: 2,
+            world_size: RectDimension::new(80, 60),
+            max_civilizations: 6,
+            history_years: 0,
+            ..GenParam::default()
+        };
+        let world = gen_full_world(&params, None, &Cancel::new()).unwrap();
+        let mut history = world.history().clone();
+        let mut economy = Economy::new(&world);
+
+        let (port, owner) = history
+            .living_ports()
+            .map(|(id, port)| (id, port.owner))
+            .next()
+            .unwrap();
+        let enemy = (owner + 1) % history.civilizations.len();
+        assert_ne!(owner, enemy);
+        for civ in 0..history.civilizations.len() {
+            history.set_relation(owner, civ, Relation::Peace);
+        }
+        for _ in 0..WARM_UP_DAYS {
+            economy.tick(&history);
+        }
+        let peace = economy.market(port).unwrap().price(Good::Timber);
+
+        history.set_relation(owner, enemy, Relation::War);
+        economy.tick(&history);
+        assert!(economy.market(port).unwrap().price(Good::Timber) > peace);
+
+        history.set_relation(owner, enemy, Relation::Peace);
+        for _ in 0..WARM_UP_DAYS {
+            economy.tick(&history);
+        }
+        let after = economy.market(port).unwrap().price(Good::Timber);
+        assert!((after - peace).abs() < 1e-9, "{after} against {peace}");
+    }
+}
@@ -0,0 +1,141 @@
+//! what the player knows of the world, and what they can see of it right now
+//!
+//! everything starts out unexplored and hidden. anything that comes into sight stays explored from
+//! then on, but only what's in sight right now shows who is out there
+
+use bracket_lib::terminal::{BLACK, WHITE};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Grid, Point, RectDimension};
+use crate::render::{Camera, Frame, Tile};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fog {
+    /// tiles the player has seen or had charted
+    explored: Grid<bool>,
+    /// tiles in sight right now
+    visible: Grid<bool>,
+}
+
+impl Fog {
+    pub fn new(dimensions: RectDimension) -> Self {
+        Fog {
+            explored: Grid::filled(dimensions, false),
+            visible: Grid::filled(dimensions, false),
+        }
+    }
+
+    pub fn is_explored(&self, x: usize, y: usize) -> bool {
+        self.explored.get(x, y).is_some_and(|&explored| explored)
+    }
+
+    pub fn is_visible(&self, x: usize, y: usize) -> bool {
+        self.visible.get(x, y).is_some_and(|&visible| visible)
+    }
+
+    /// every tile within `radius` of `centre` that's on the map
+    fn circle(&self, (cx, cy): Point<usize>, radius: usize) -> Vec<Point<usize>> {
+        let (width, height) = (self.explored.width(), self.explored.height());
+        let xs = cx.saturating_sub(radius)..=(cx + radius).min(width.saturating_sub(1));
+        let ys = cy.saturating_sub(radius)..=(cy + radius).min(height.saturating_sub(1));
+
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .filter(|&(x, y)| x.abs_diff(cx).pow(2) + y.abs_diff(cy).pow(2) <= radius.pow(2))
+            .collect()
+    }
+
+    /// look out to `radius` from `from`. anything that was in sight before and isn't any more is
+    /// only remembered
+    pub fn look(&mut self, from: Point<usize>, radius: usize) {
+        self.visible.fill(false);
+
+        for point in self.circle(from, radius) {
+            self.visible[point] = true;
+            self.explored[point] = true;
+        }
+    }
+
+    /// mark everything within `radius` of `centre` as explored, like a chart of the waters would.
+    /// returns how many tiles weren't known before
+    pub fn chart(&mut self, centre: Point<usize>, radius: usize) -> usize {
+        let mut new = 0;
+
+        for point in self.circle(centre, radius) {
+            if !self.explored[point] {
+                self.explored[point] = true;
+                new += 1;
+            }
+        }
+
+        new
+    }
+
+    /// hide whatever on a frame of the map `camera` can see hasn't been explored, and dim whatever
+    /// is out of sight
+    pub fn shroud(&self, map: &mut Frame, camera: Camera) {
+        for (point, tile) in map.to_render.points().zip(map.to_render.iter_mut()) {
+            let (x, y) = camera.to_map(point);
+            if !self.is_explored(x, y) {
+                *tile = Tile::new(' ', WHITE, BLACK);
+            } else if !self.is_visible(x, y) {
+                *tile = tile.dimmed();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fog() -> Fog {
+        Fog::new(RectDimension::new(10, 10))
+    }
+
+    #[test]
+    fn circles_stop_at_the_edges() {
+        let fog = fog();
+
+        assert_eq!(fog.circle((5, 5), 2).len(), 13);
+
+        let mut corner = fog.circle((0, 0), 2);
+        corner.sort_unstable();
+        assert_eq!(corner, [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (2, 0)]);
+        assert_eq!(fog.circle((9, 9), 2).len(), 6);
+        assert_eq!(fog.circle((9, 0), 0), [(9, 0)]);
+
+        // a circle bigger than the map is just the whole map
+        assert_eq!(fog.circle((4, 4), 100).len(), 100);
+    }
+
+    #[test]
+    fn looking_away_only_leaves_a_memory() {
+        let mut fog = fog();
+
+        fog.look((2, 2), 1);
+        assert!(fog.is_visible(2, 2) && fog.is_visible(3, 2));
+        assert!(!fog.is_visible(4, 2) && !fog.is_explored(4, 2));
+
+        fog.look((7, 7), 1);
+        assert!(fog.is_visible(7, 7) && fog.is_explored(7, 7));
+        for (x, y) in fog.circle((2, 2), 1) {
+            assert!(!fog.is_visible(x, y));
+            assert!(fog.is_explored(x, y));
+        }
+        assert!(!fog.is_visible(20, 20) && !fog.is_explored(20, 20));
+    }
+
+    #[test]
+    fn charts_count_what_was_new() {
+        let mut fog = fog();
+
+        assert_eq!(fog.chart((5, 5), 2), 13);
+        assert_eq!(fog.chart((5, 5), 2), 0);
+        assert_eq!(fog.chart((6, 5), 2), 5);
+        assert!(!fog.is_visible(5, 5));
+
+        fog.look((0, 0), 2);
+        assert_eq!(fog.chart((0, 0), 2), 0);
+        assert_eq!(fog.chart((0, 0), 3), 5);
+    }
+}
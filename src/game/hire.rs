@@ -0,0 +1,291 @@
+//! the hiring screen, for signing on sailors in port and letting them go
+
+use std::fmt::Write;
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, NAVY, RED, WHITE, YELLOW};
+use log::error;
+
+use crate::render::{Frame, RenderPacket, RenderTick};
+use crate::worldgen::history::PortId;
+
+use super::clock;
+use super::crew::{Sailor, Skill, BERTHS};
+use super::Game;
+
+/// rows taken up by the header above each list
+const HEADER_ROWS: usize = 4;
+/// people in port looking for a berth, one for every this many living there
+const PEOPLE_PER_RECRUIT: u32 = 400;
+/// there's never more than this many looking for a berth in one port
+const MAX_RECRUITS: u32 = 12;
+
+/// one line of a list of sailors
+fn sailor_line(sailor: &Sailor) -> String {
+    let mut skills = String::new();
+    for skill in Skill::ALL {
+        let _ = write!(skills, "{:>4}", sailor.skill(skill));
+    }
+    let state = match sailor.injured {
+        0 => String::new(),
+        days => format!("  hurt, {days} days"),
+    };
+
+    format!(
+        "  {:<24}{skills}{:>8}{:>8}{:>6}{state}",
+        sailor.name, sailor.morale, sailor.loyalty, sailor.wage
+    )
+}
+
+fn header() -> String {
+    format!(
+        "  {:<24}{:>4}{:>4}{:>4}{:>4}{:>8}{:>8}{:>6}",
+        "name", "gun", "nav", "rig", "mel", "morale", "loyal", "wage"
+    )
+}
+
+/// both lists, with the selected sailor picked out in whichever list is being looked at
+fn frame(
+    game: &Game,
+    port: PortId,
+    recruits: &[Sailor],
+    (crew_list, selected): (bool, usize),
+    message: &str,
+) -> Frame {
+    let history = game.world.history();
+    let mut frame = Frame::blank(game.screen);
+
+    frame.print(
+        0,
+        0,
+        &format!(
+            "Hiring at {} - {} gold, {}/{BERTHS} hands, {} gold a week in wages",
+            history.ports[port].name,
+            game.gold,
+            game.crew.sailors.len(),
+            game.crew.wages()
+        ),
+        YELLOW,
+        BLACK,
+    );
+    frame.print(
+        0,
+        1,
+        "[up/down] pick  [tab] switch list  [h/enter] hire  [d] dismiss  [esc] back",
+        GRAY,
+        BLACK,
+    );
+    frame.print(0, 2, message, WHITE, BLACK);
+
+    let lists = [
+        (
+            "looking for a berth (signing on costs two weeks' wages)".to_string(),
+            recruits,
+            false,
+        ),
+        ("your crew".to_string(), game.crew.sailors.as_slice(), true),
+    ];
+    let mut row = HEADER_ROWS - 1;
+    for (title, sailors, is_crew) in lists {
+        frame.print(0, row, &title, YELLOW, BLACK);
+        frame.print(0, row + 1, &header(), GRAY, BLACK);
+        row += 2;
+
+        for (i, sailor) in sailors.iter().enumerate() {
+            let bg = if is_crew == crew_list && i == selected {
+                NAVY
+            } else {
+                BLACK
+            };
+            let fg = if sailor.is_fit() { WHITE } else { RED };
+
+            frame.print(0, row, &sailor_line(sailor), fg, bg);
+            row += 1;
+        }
+        row += 1;
+    }
+
+    frame
+}
+
+/// the sailors looking for work in `port` today, made up the first time anyone asks
+fn recruits(game: &mut Game, port: PortId) -> Vec<Sailor> {
+    let today = game.clock.now() / clock::MINUTES_PER_DAY;
+    if let Some((day, recruits)) = game.recruits.get(&port) {
+        if *day == today {
+            return recruits.clone();
+        }
+    }
+
+    let population = game.world.history().ports[port].population;
+    let count = (population / PEOPLE_PER_RECRUIT).min(MAX_RECRUITS) + 1;
+    let recruits: Vec<_> = (0..count).map(|_| Sailor::random(&mut game.rng)).collect();
+    game.recruits.insert(port, (today, recruits.clone()));
+
+    recruits
+}
+
+fn hire(game: &mut Game, port: PortId, recruits: &mut Vec<Sailor>, i: usize) -> String {
+    let Some(sailor) = recruits.get(i) else {
+        return "there's nobody to hire".into();
+    };
+    if game.crew.sailors.len() >= BERTHS {
+        return "there's no room aboard for anyone else".into();
+    }
+    let fee = sailor.signing_fee();
+    if game.gold < fee {
+        return format!("{} wants {fee} gold to sign on", sailor.name);
+    }
+
+    game.gold -= fee;
+    let sailor = recruits.remove(i);
+    let message = format!("{} signed on for {fee} gold", sailor.name);
+    game.crew.hire(sailor);
+    game.muster();
+    if let Some((_, left)) = game.recruits.get_mut(&port) {
+        left.clone_from(recruits);
+    }
+
+    message
+}
+
+/// show the hiring screen for `port` until the player backs out of it
+///
+/// returns false if the window was closed while hiring
+pub fn hiring(
+    game: &mut Game,
+    port: PortId,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send hiring screen to render thread. Error: {e}");
+        }
+    };
+
+    let mut recruits = recruits(game, port);
+    // whether the crew list is being looked at, rather than the recruits
+    let mut crew_list = false;
+    let mut selected = 0;
+    let mut message = String::new();
+
+    send(frame(
+        game,
+        port,
+        &recruits,
+        (crew_list, selected),
+        &message,
+    ));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        let length = if crew_list {
+            game.crew.sailors.len()
+        } else {
+            recruits.len()
+        };
+        match key {
+            VirtualKeyCode::Escape => return true,
+            VirtualKeyCode::Tab => {
+                crew_list = !crew_list;
+                selected = 0;
+            }
+            VirtualKeyCode::Up => selected = selected.saturating_sub(1),
+            VirtualKeyCode::Down => selected = (selected + 1).min(length.saturating_sub(1)),
+            VirtualKeyCode::H | VirtualKeyCode::Return if !crew_list => {
+                message = hire(game, port, &mut recruits, selected);
+                selected = selected.min(recruits.len().saturating_sub(1));
+            }
+            VirtualKeyCode::D if crew_list => {
+                if let Some(sailor) = game.crew.dismiss(selected) {
+                    message = format!("{} was paid off and left the ship", sailor.name);
+                    game.muster();
+                }
+                selected = selected.min(game.crew.sailors.len().saturating_sub(1));
+            }
+            _ => continue,
+        }
+
+        send(frame(
+            game,
+            port,
+            &recruits,
+            (crew_list, selected),
+            &message,
+        ));
+    }
+
+    // the render thread hung up on us
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use bracket_lib::random::RandomNumberGenerator;
+
+    use super::*;
+
+    #[test]
+    fn ports_only_have_so_many_looking_for_work() {
+        let mut game = Game::for_tests(4);
+
+        for port in 0..game.world.history().ports.len() {
+            let population = game.world.history().ports[port].population;
+            let found = recruits(&mut game, port);
+
+            assert!(!found.is_empty());
+            assert!(found.len() as u32 <= MAX_RECRUITS + 1);
+            assert_eq!(
+                found.len() as u32,
+                (population / PEOPLE_PER_RECRUIT).min(MAX_RECRUITS) + 1
+            );
+
+            // the same faces all day
+            let again = recruits(&mut game, port);
+            assert_eq!(
+                again.iter().map(|s| &s.name).collect::<Vec<_>>(),
+                found.iter().map(|s| &s.name).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn hiring_takes_gold_and_room() {
+        let mut game = Game::for_tests(4);
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let mut found: Vec<_> = (0..2).map(|_| Sailor::random(&mut rng)).collect();
+        let fee = found[0].signing_fee();
+        let hands = game.crew.sailors.len();
+
+        game.gold = fee - 1;
+        assert!(hire(&mut game, 0, &mut found, 0).contains("wants"));
+        assert_eq!(found.len(), 2);
+        assert_eq!(game.crew.sailors.len(), hands);
+
+        game.gold = fee;
+        assert!(hire(&mut game, 0, &mut found, 0).contains("signed on"));
+        assert_eq!(game.gold, 0);
+        assert_eq!(found.len(), 1);
+        assert_eq!(game.crew.sailors.len(), hands + 1);
+        assert_eq!(game.player.crew, game.crew.fit());
+
+        assert_eq!(hire(&mut game, 0, &mut found, 5), "there's nobody to hire");
+
+        game.gold = u32::MAX;
+        while game.crew.sailors.len() < BERTHS {
+            game.crew.hire(Sailor::random(&mut rng));
+        }
+        assert_eq!(
+            hire(&mut game, 0, &mut found, 0),
+            "there's no room aboard for anyone else"
+        );
+        assert_eq!(game.crew.sailors.len(), BERTHS);
+        assert_eq!(game.gold, u32::MAX);
+    }
+}
@@ -0,0 +1,155 @@
+//! the tile inspector, for looking around the map without sailing anywhere
+
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, WHITE, YELLOW};
+use log::error;
+
+use crate::helpers::Point;
+use crate::render::{Frame, RenderPacket, RenderTick, Tile};
+
+use super::economy::Good;
+use super::{compass, direction, Game};
+
+const CURSOR_CHAR: char = 'X';
+/// how many goods fit on a single line of prices
+const GOODS_PER_LINE: usize = 4;
+
+/// the map with the cursor on it, and everything known about the tile under it
+fn frame(game: &Game, (x, y): Point<usize>) -> Frame {
+    let world = &game.world;
+    let history = world.history();
+    // the map follows the cursor around, so there's somewhere to look past the edge of the screen
+    let camera = game.camera((x, y));
+    let mut frame = game.map_frame(camera);
+    frame.set_on_map(camera, (x, y), Tile::new(CURSOR_CHAR, YELLOW, BLACK));
+
+    let terrain = world.terrain();
+    let ground = if world.is_frozen(x, y) {
+        "pack ice".to_string()
+    } else if terrain.is_sea(x, y) {
+        format!("open water, depth {:.0}", terrain.depth(x, y))
+    } else {
+        format!(
+            "{}, height {:.0}",
+            world.biome_at(x, y),
+            -terrain.depth(x, y)
+        )
+    };
+    let wind = world.wind_at(x, y);
+    let current = world.current_at(x, y);
+
+    let mut lines = vec![if game.fog.is_explored(x, y) {
+        format!(
+            "({x}, {y}) {ground}  wind {} {:.1}  current {} {:.1}",
+            compass(wind),
+            wind.0.hypot(wind.1),
+            compass(current),
+            current.0.hypot(current.1)
+        )
+    } else {
+        format!("({x}, {y}) uncharted")
+    }];
+
+    if let Some(npc) = game
+        .fleet
+        .ship_at(x, y)
+        .filter(|_| game.fog.is_visible(x, y))
+    {
+        lines.push(format!(
+            "{}, {}, captained by {}",
+            npc.ship.name,
+            npc.describe(history),
+            npc.captain
+        ));
+    }
+
+    if let Some(port) = world.port_at(x, y).filter(|_| game.fog.is_explored(x, y)) {
+        let owner = &history.civilizations[history.ports[port].owner].name;
+        lines.push(format!(
+            "{} of {owner}, population {}",
+            history.ports[port].name, history.ports[port].population
+        ));
+
+        if let Some(market) = game.economy.market(port) {
+            for goods in Good::ALL.chunks(GOODS_PER_LINE) {
+                let prices: Vec<_> = goods
+                    .iter()
+                    .map(|&good| {
+                        format!(
+                            "{good} {}/{}",
+                            market.buy_price(good),
+                            market.sell_price(good)
+                        )
+                    })
+                    .collect();
+
+                lines.push(format!("  {}", prices.join("  ")));
+            }
+        }
+    }
+
+    let below_map = camera.view.height as usize;
+    for (row, line) in lines.iter().enumerate() {
+        frame.print(0, below_map + row, line, WHITE, BLACK);
+    }
+    frame.print(
+        0,
+        below_map + lines.len(),
+        "[arrows/numpad] move  [esc] back  prices are buy/sell",
+        GRAY,
+        BLACK,
+    );
+
+    frame
+}
+
+/// look around the map until the player backs out
+///
+/// returns false if the window was closed while looking around
+pub fn inspect(
+    game: &Game,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send inspector to render thread. Error: {e}");
+        }
+    };
+
+    let mut cursor = game.player.position;
+    send(frame(game, cursor));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::I => return true,
+            key => {
+                let Some((dx, dy)) = direction(key) else {
+                    continue;
+                };
+                let (Some(x), Some(y)) = (
+                    cursor.0.checked_add_signed(dx),
+                    cursor.1.checked_add_signed(dy),
+                ) else {
+                    continue;
+                };
+
+                if game.world.in_bounds(x, y) {
+                    cursor = (x, y);
+                }
+            }
+        }
+
+        send(frame(game, cursor));
+    }
+
+    // the render thread hung up on us
+    false
+}
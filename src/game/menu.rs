@@ -0,0 +1,219 @@
+//! the pause menu, for saving to and loading from the save slots, or starting over in a whole new
+//! world
+//!
+//! the menu is a gui the renderer draws over the top of the game, so the map stays in view
+//! underneath. it stops the clock while it's open
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, NAVY, WHITE, YELLOW};
+use log::error;
+
+use crate::helpers::RectDimension;
+use crate::render::{self, Frame, OffsetX, OffsetY, RenderPacket, RenderTick, GUI};
+use crate::worldgen::{config, GenParam};
+
+use super::save::{self, slot_path, AUTOSAVE_PATH, SAVE_PATH, SLOTS};
+use super::Game;
+
+const MENU_WIDTH: u16 = 80;
+/// how far in from the top left corner of the screen the menu is drawn
+const MENU_OFFSET: (i8, i8) = (12, 20);
+/// drawn over the clock and anything else
+const MENU_PRIORITY: u8 = 2;
+/// rows above the list of things to pick from
+const HEADER_ROWS: usize = 4;
+
+/// what picking a line of the menu does
+enum Action {
+    Resume,
+    Save(PathBuf),
+    Load(PathBuf),
+    NewWorld(Box<GenParam>),
+}
+
+/// what the player left the menu to do
+pub enum Choice {
+    Resume,
+    /// carry on with a game loaded from a save instead
+    Load(Box<Game>),
+    /// give up on this game and generate a new world from these params
+    NewWorld(Box<GenParam>),
+    /// the window was closed with the menu open
+    Closed,
+}
+
+/// register the menu with the renderer, hidden until it's needed. returns its gui id
+pub fn register(sender: &Sender<RenderPacket>, receiver: &Receiver<RenderTick>) -> Option<usize> {
+    let gui = GUI {
+        offset: (OffsetX::Left(MENU_OFFSET.0), OffsetY::Above(MENU_OFFSET.1)),
+        to_render: None,
+    };
+
+    render::register_gui(sender, receiver, MENU_PRIORITY, gui)
+}
+
+/// everything in the menu, with what's in each save slot right now
+fn entries(game: &Game) -> Vec<(String, Action)> {
+    let mut entries = vec![("resume".to_string(), Action::Resume)];
+
+    for slot in 1..=SLOTS {
+        let path = slot_path(slot);
+        let saved = save::describe(&path).unwrap_or_else(|| "empty".into());
+        entries.push((format!("save to slot {slot}   {saved}"), Action::Save(path)));
+    }
+
+    let loads = (1..=SLOTS)
+        .map(|slot| (format!("slot {slot}"), slot_path(slot)))
+        .chain([
+            ("quick save".to_string(), PathBuf::from(SAVE_PATH)),
+            ("autosave".to_string(), PathBuf::from(AUTOSAVE_PATH)),
+        ]);
+    for (name, path) in loads {
+        if let Some(saved) = save::describe(&path) {
+            entries.push((format!("load {name}   {saved}"), Action::Load(path)));
+        }
+    }
+
+    // new worlds are the same size as this one, with a new seed
+    let current = game.world().params();
+    let fresh = |params: GenParam| GenParam {
+        seed: config::clock_seed(),
+        world_size: current.world_size,
+        ..params
+    };
+    entries.push((
+        "new world   same settings, new seed".to_string(),
+        Action::NewWorld(Box::new(fresh(current.clone()))),
+    ));
+    for name in config::preset_names() {
+        if let Some(preset) = config::preset(&name) {
+            entries.push((
+                format!("new {name} world"),
+                Action::NewWorld(Box::new(fresh(preset))),
+            ));
+        }
+    }
+
+    entries
+}
+
+fn frame(entries: &[(String, Action)], selected: usize, message: &str) -> Frame {
+    let height = (HEADER_ROWS + entries.len() + 1) as u16;
+    let mut frame = Frame::blank(RectDimension::new(MENU_WIDTH, height));
+
+    frame.print(1, 1, "Paused", YELLOW, BLACK);
+    frame.print(
+        1,
+        2,
+        "[up/down] pick  [enter] choose  [esc] resume",
+        GRAY,
+        BLACK,
+    );
+    frame.print(1, 3, message, WHITE, BLACK);
+
+    for (i, (line, _)) in entries.iter().enumerate() {
+        let bg = if i == selected { NAVY } else { BLACK };
+        frame.print(1, HEADER_ROWS + i, &format!(" {line:<77}"), WHITE, bg);
+    }
+
+    frame
+}
+
+/// pause the game and show the menu until the player is done with it
+///
+/// `gui` is the menu's id from [`register`]. if the renderer never gave it one, the menu is drawn
+/// straight onto the game instead
+pub fn pause(
+    game: &mut Game,
+    gui: Option<usize>,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> Choice {
+    let show = |game: &Game, menu: Option<Frame>| {
+        let sent = match (gui, menu) {
+            (Some(id), update) => sender.send(RenderPacket::UpdateGUI { id, update }),
+            (None, Some(menu)) => {
+                let mut frame = game.frame();
+                frame.draw(
+                    &menu,
+                    usize::from(MENU_OFFSET.0.unsigned_abs()),
+                    usize::from(MENU_OFFSET.1.unsigned_abs()),
+                );
+                sender.send(RenderPacket::NewFrame(frame))
+            }
+            (None, None) => sender.send(RenderPacket::NewFrame(game.frame())),
+        };
+        if let Err(e) = sent {
+            error!("Unable to send pause menu to render thread. Error: {e}");
+        }
+    };
+
+    game.clock.paused = true;
+    let mut entries = entries(game);
+    let mut selected = 0;
+    let mut message = String::new();
+    show(game, Some(frame(&entries, selected, &message)));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return Choice::Closed,
+            _ => continue,
+        };
+
+        match key {
+            VirtualKeyCode::Up => selected = selected.saturating_sub(1),
+            VirtualKeyCode::Down => selected = (selected + 1).min(entries.len() - 1),
+            VirtualKeyCode::Escape => break,
+            VirtualKeyCode::Return => match &entries[selected].1 {
+                Action::Resume => break,
+                Action::Save(path) => {
+                    message = match save::save(game, path) {
+                        Ok(()) => format!("saved to {}", path.display()),
+                        Err(e) => {
+                            error!("Unable to save game to {}. Error: {e}", path.display());
+                            format!("couldn't save the game: {e}")
+                        }
+                    };
+                    entries = self::entries(game);
+                }
+                Action::Load(path) => match load(game, path) {
+                    Ok(loaded) => {
+                        show(game, None);
+                        return Choice::Load(Box::new(loaded));
+                    }
+                    Err(e) => message = e,
+                },
+                Action::NewWorld(params) => {
+                    show(game, None);
+                    return Choice::NewWorld(params.clone());
+                }
+            },
+            _ => continue,
+        }
+
+        show(game, Some(frame(&entries, selected, &message)));
+    }
+
+    show(game, None);
+    Choice::Resume
+}
+
+/// load the game saved at `path` to replace `game`, or say why it couldn't be
+fn load(game: &Game, path: &Path) -> Result<Game, String> {
+    match save::load(path, game.screen) {
+        Ok(mut loaded) => {
+            loaded.message = format!("loaded {}", path.display());
+            Ok(loaded)
+        }
+        Err(e) => {
+            error!(
+                "Unable to load saved game from {}. Error: {e}",
+                path.display()
+            );
+            Err(format!("couldn't load the game: {e}"))
+        }
+    }
+}
@@ -0,0 +1,961 @@
+//! everything that happens once a world is generated and the player is let loose on it
+//!
+//! like the rest of the work thread, the game waits on [`RenderTick`]s from the renderer and
+//! answers them with new frames. while the clock isn't paused it also lets time pass on its own
+
+mod clock;
+mod combat;
+mod contract;
+mod crew;
+mod economy;
+mod fog;
+mod hire;
+mod inspect;
+mod menu;
+mod navigation;
+mod npc;
+mod reputation;
+mod save;
+mod ship;
+mod trade;
+mod treasure;
+mod weather;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, CYAN, GRAY, GREEN, RED, WHITE, YELLOW};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{points_around, Point, RectDimension};
+use crate::legends;
+use crate::render::{self, Camera, Frame, OffsetX, OffsetY, RenderPacket, RenderTick, Tile, GUI};
+use crate::worldgen::history::{self, PortId};
+use crate::worldgen::treasure::TreasureId;
+use crate::worldgen::{self, names, FullWorld};
+
+use clock::{Clock, Scheduler};
+use combat::Aggressor;
+use contract::Journal;
+use crew::{Crew, Sailor, Skill};
+use economy::Economy;
+use fog::Fog;
+use menu::Choice;
+pub use navigation::{SeaChart, Waters};
+use npc::{Fleet, NpcId, Role};
+use reputation::Reputation;
+pub use save::{load, AUTOSAVE_PATH, SAVE_PATH};
+pub use ship::{compass, sailing_hours, Ship};
+use weather::{Conditions, Weather};
+
+const PLAYER_CHAR: char = '@';
+const COURSE_CHAR: char = '.';
+const STRANDED_CHAR: char = '!';
+/// lines kept clear under the map for the status and the keys
+const STATUS_ROWS: u16 = 5;
+/// how much gold the player starts out with
+const STARTING_GOLD: u32 = 500;
+/// how long to wait between game hours while the clock is running
+const REAL_TIME_HOUR: Duration = Duration::from_millis(500);
+/// how much of the hull and rigging gets patched up for every day spent in port, by an ordinary
+/// crew
+const REPAIRS_PER_DAY: f64 = 20.;
+/// how much of the rigging gets patched up for every day at sea, by an ordinary crew
+const REPAIRS_AT_SEA: f64 = 5.;
+/// the hours of daylight, from dawn up to dusk
+const DAYLIGHT: std::ops::Range<u64> = 6..20;
+/// how much of what can be seen by day can still be seen at night
+const NIGHT_SIGHT: f64 = 0.4;
+/// how many days go by between autosaves
+const AUTOSAVE_DAYS: u64 = 7;
+/// what charts of a civilization's waters cost
+const CHART_PRICE: u32 = 50;
+/// how far around each of a civilization's ports its charts cover
+const CHART_RADIUS: usize = 15;
+
+/// things that happen on the simulation clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameEvent {
+    DayBreak,
+    PayDay,
+    SeasonChange,
+    NewYear,
+    AutoSave,
+}
+
+/// the state of a game being played
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    world: FullWorld,
+    player: Ship,
+    /// everyone aboard the player's ship. [`Ship::crew`] is kept to the number of them fit for
+    /// duty
+    crew: Crew,
+    /// sailors looking for a berth in each port, and the day they were found
+    recruits: HashMap<PortId, (u64, Vec<Sailor>)>,
+    /// the port the player has plotted a course to, if any
+    destination: Option<PortId>,
+    /// what's left of the plotted course, backwards so the next step is at the end
+    course: Vec<Point<usize>>,
+    /// the player's purse
+    gold: u32,
+    /// the treasure maps the player has, by the treasure they lead to
+    maps: Vec<TreasureId>,
+    /// the contracts the player has taken on, and how the old ones went
+    journal: Journal,
+    economy: Economy,
+    /// everyone else at sea
+    fleet: Fleet,
+    /// the ship the player is about to fight, if any, and who's starting it
+    engaged: Option<(NpcId, Aggressor)>,
+    reputation: Reputation,
+    /// what the player has explored, and what they can see right now
+    fog: Fog,
+    weather: Weather,
+    /// worked out from the world again on loading
+    #[serde(skip)]
+    waters: Waters,
+    clock: Clock,
+    scheduler: Scheduler<GameEvent>,
+    #[serde(skip)]
+    screen: RectDimension,
+    /// shown under the map, telling the player what just happened
+    message: String,
+    rng: RandomNumberGenerator,
+}
+
+impl Game {
+    pub fn world(&self) -> &FullWorld {
+        &self.world
+    }
+
+    /// start a new game on `world`, with the player docked at a random port
+    pub fn new(world: FullWorld, screen: RectDimension) -> Self {
+        Self::with_rng(world, screen, RandomNumberGenerator::new())
+    }
+
+    /// start a new game with everything random drawn from `rng`
+    fn with_rng(world: FullWorld, screen: RectDimension, mut rng: RandomNumberGenerator) -> Self {
+        let ports: Vec<_> = world.history().living_ports().map(|(id, _)| id).collect();
+        let (position, docked) = match rng.random_slice_entry(&ports) {
+            Some(&port) => (world.history().ports[port].location, Some(port)),
+            // no ports at all, just drop the player in the first bit of open water
+            None => (first_open_water(&world), None),
+        };
+
+        let player = Ship::new(names::ship(&mut rng), position, docked);
+        let crew = Crew::new(&mut rng, player.crew);
+
+        Self::resume(
+            world,
+            player,
+            crew,
+            (STARTING_GOLD, Journal::default()),
+            screen,
+            rng,
+        )
+    }
+
+    /// a new game on a small world generated from `seed`, with everything random in it seeded from
+    /// `seed` too, to try things out on
+    #[cfg(test)]
+    pub(crate) fn for_tests(seed: u64) -> Self {
+        let params = worldgen::GenParam {
+            seed,
+            world_size: RectDimension::new(80, 60),
+            history_years: 20,
+            ..worldgen::GenParam::default()
+        };
+        let world =
+            worldgen::gen_full_world(&params, None, &crate::helpers::Cancel::new()).unwrap();
+
+        Game::with_rng(
+            world,
+            RectDimension::new(105, 105),
+            RandomNumberGenerator::seeded(seed),
+        )
+    }
+
+    /// carry on a game with the player's ship, crew, purse and contracts as they were
+    fn resume(
+        world: FullWorld,
+        player: Ship,
+        crew: Crew,
+        (gold, journal): (u32, Journal),
+        screen: RectDimension,
+        mut rng: RandomNumberGenerator,
+    ) -> Self {
+        // the game picks up the year after history left off
+        let clock = Clock::new(u64::from(world.history().years) + 1);
+        let mut scheduler = Scheduler::new();
+        scheduler.every(
+            clock::MINUTES_PER_DAY,
+            clock::MINUTES_PER_DAY,
+            GameEvent::DayBreak,
+        );
+        scheduler.every(
+            clock::MINUTES_PER_DAY * crew::PAYDAY_DAYS,
+            clock::MINUTES_PER_DAY * crew::PAYDAY_DAYS,
+            GameEvent::PayDay,
+        );
+        scheduler.every(
+            clock::MINUTES_PER_SEASON,
+            clock::MINUTES_PER_SEASON,
+            GameEvent::SeasonChange,
+        );
+        scheduler.every(
+            clock::MINUTES_PER_YEAR,
+            clock::MINUTES_PER_YEAR,
+            GameEvent::NewYear,
+        );
+        scheduler.every(
+            clock::MINUTES_PER_DAY * AUTOSAVE_DAYS,
+            clock::MINUTES_PER_DAY * AUTOSAVE_DAYS,
+            GameEvent::AutoSave,
+        );
+
+        let economy = Economy::new(&world);
+        let fleet = Fleet::new(&world, RandomNumberGenerator::seeded(rng.next_u64()));
+        let reputation = Reputation::new(&world);
+        let fog = Fog::new(world.dimensions());
+        let weather = Weather::new(
+            &world,
+            clock.date().season,
+            RandomNumberGenerator::seeded(rng.next_u64()),
+        );
+        let waters = Waters::new(&world);
+
+        let mut game = Game {
+            world,
+            player,
+            crew,
+            recruits: HashMap::new(),
+            destination: None,
+            course: Vec::new(),
+            gold,
+            maps: Vec::new(),
+            journal,
+            economy,
+            fleet,
+            engaged: None,
+            reputation,
+            fog,
+            weather,
+            waters,
+            clock,
+            scheduler,
+            screen,
+            message: String::new(),
+            rng,
+        };
+        game.muster();
+        game.look();
+
+        game
+    }
+
+    /// count up the hands fit for duty
+    fn muster(&mut self) {
+        self.player.crew = self.crew.fit();
+    }
+
+    /// how far the player can see right now, as a part of what they could on a clear day
+    fn visibility(&self) -> f64 {
+        let light = if DAYLIGHT.contains(&self.clock.date().hour) {
+            1.
+        } else {
+            NIGHT_SIGHT
+        };
+        let (x, y) = self.player.position;
+
+        light * self.weather.at(x, y).visibility()
+    }
+
+    /// have the lookouts see whatever they can from where the ship is now
+    fn look(&mut self) {
+        #[allow(clippy::cast_sign_loss)]
+        let radius = (self.player.sight() * self.visibility()).round().max(1.) as usize;
+        self.fog.look(self.player.position, radius);
+    }
+
+    /// move the clock forward and run everything scheduled in the meantime
+    fn pass_time(&mut self, minutes: u64) {
+        self.clock.advance(minutes);
+
+        let hours = minutes as f64 / clock::MINUTES_PER_HOUR as f64;
+        self.weather
+            .advance(&self.world, hours, self.clock.date().season);
+        self.weather_damage(hours);
+
+        let chart = SeaChart::new(&self.world, &self.waters);
+        let news = self.fleet.advance(
+            &chart,
+            &mut self.economy,
+            hours,
+            (&self.player, &self.reputation),
+        );
+        if let Some(news) = news.last() {
+            self.message.clone_from(news);
+        }
+        for (a, b, by) in self.fleet.take_dealings() {
+            self.world.history_mut().change_opinion(a, b, by);
+        }
+
+        if self.engaged.is_none() {
+            self.engaged = self
+                .fleet
+                .attacker(&self.player)
+                .map(|id| (id, Aggressor::Enemy));
+            if let Some(npc) = self.engaged.and_then(|(id, _)| self.fleet.get(id)) {
+                self.message = format!("{} bears down on you!", npc.ship.name);
+            }
+        }
+
+        for event in self.scheduler.advance(self.clock.now()) {
+            self.handle(event);
+        }
+        contract::review(self);
+        self.look();
+    }
+
+    fn handle(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::DayBreak => {
+                self.economy.tick(self.world.history());
+                self.fleet.replenish(&self.world);
+
+                let docked = self.player.docked.is_some();
+                let rigging = self.crew.modifier(Skill::Rigging);
+                #[allow(clippy::cast_sign_loss)]
+                if docked {
+                    self.player.repair((REPAIRS_PER_DAY * rigging) as u32);
+                } else {
+                    let sails = self.player.sails + (REPAIRS_AT_SEA * rigging) as u32;
+                    self.player.sails = sails.min(ship::MAX_SAILS);
+                }
+
+                self.crew.day(docked);
+                if docked {
+                    let deserters = self.crew.desert(&mut self.rng);
+                    match deserters.len() {
+                        0 => {}
+                        1 => self.message = format!("{} jumped ship", deserters[0]),
+                        n => self.message = format!("{n} of the crew jumped ship"),
+                    }
+                }
+                self.muster();
+            }
+            GameEvent::PayDay => self.message = self.crew.payday(&mut self.gold),
+            GameEvent::SeasonChange => {
+                self.message = format!("{} has come", self.clock.date().season);
+                self.diplomacy();
+            }
+            GameEvent::NewYear => {
+                self.message = format!("the year {} begins", self.clock.date().year);
+            }
+            GameEvent::AutoSave => {
+                if let Err(e) = save::save(self, Path::new(AUTOSAVE_PATH)) {
+                    error!("Unable to autosave to {AUTOSAVE_PATH}. Error: {e}");
+                }
+            }
+        }
+    }
+
+    /// whatever a storm does to the player's ship over `hours`. a good crew keeps more of the
+    /// rigging up
+    fn weather_damage(&mut self, hours: f64) {
+        let (x, y) = self.player.position;
+        if self.player.docked.is_some() || self.weather.at(x, y) != Conditions::Storm {
+            return;
+        }
+
+        let rigging = self.crew.modifier(Skill::Rigging);
+        #[allow(clippy::cast_sign_loss)]
+        let (sails, hull) = (
+            (weather::STORM_SAILS * hours / rigging).round() as u32,
+            (weather::STORM_HULL * hours / rigging).round() as u32,
+        );
+        self.player.sails = self.player.sails.saturating_sub(sails);
+        // she'll be in a bad way, but a storm alone never quite sends her down
+        self.player.hull = self.player.hull.saturating_sub(hull).max(1);
+
+        if sails > 0 || hull > 0 {
+            self.message = "the storm batters the ship".into();
+        }
+    }
+
+    /// a season's worth of the civilizations' dealings with each other
+    fn diplomacy(&mut self) {
+        let year = u32::try_from(self.clock.date().year).unwrap_or(u32::MAX);
+        // as eventful as the history the world was generated with
+        let eventfulness = history::eventfulness(self.world.params());
+        let history = self.world.history_mut();
+        let before = history.events.len();
+        history::diplomacy(
+            history,
+            year,
+            &mut self.rng,
+            eventfulness,
+            1. / clock::SEASONS_PER_YEAR as f64,
+        );
+
+        let history = self.world.history();
+        if let Some(event) = history.events[before..].last() {
+            self.message = history.describe(event);
+        }
+        for civ in self.reputation.review_marques(history) {
+            self.message = format!(
+                "{} has no more need of privateers, and withdrew your letters of marque",
+                history.civilizations[civ].name
+            );
+        }
+    }
+
+    /// the port the player is in or right outside of, if there is one
+    fn nearby_port(&self) -> Option<PortId> {
+        let (x, y) = self.player.position;
+
+        self.player.docked.or_else(|| {
+            points_around(x as isize, y as isize)
+                .into_iter()
+                .filter_map(|(x, y)| Some((usize::try_from(x).ok()?, usize::try_from(y).ok()?)))
+                .find_map(|(x, y)| self.world.port_at(x, y))
+        })
+    }
+
+    /// pay off the harbourmaster of the port the player is at, or shut out of
+    fn bribe(&mut self) {
+        let Some(port) = self.nearby_port() else {
+            self.message = "there's nobody around to bribe".into();
+            return;
+        };
+
+        let owner = self.world.history().ports[port].owner;
+        self.message = self
+            .reputation
+            .bribe(self.world.history(), owner, &mut self.gold);
+    }
+
+    /// buy charts of the waters around every port of whoever owns the port the player is docked at
+    fn buy_charts(&mut self) {
+        let Some(port) = self.player.docked else {
+            self.message = "you need to be in port to buy charts".into();
+            return;
+        };
+        if self.gold < CHART_PRICE {
+            self.message = format!("charts cost {CHART_PRICE} gold");
+            return;
+        }
+
+        let history = self.world.history();
+        let owner = history.ports[port].owner;
+        let charted: usize = history
+            .ports_of(owner)
+            .map(|(_, port)| self.fog.chart(port.location, CHART_RADIUS))
+            .sum();
+
+        let name = &history.civilizations[owner].name;
+        self.message = if charted == 0 {
+            format!("the charts of {name} show nothing you don't already know")
+        } else {
+            self.gold -= CHART_PRICE;
+            format!("you bought charts of the waters of {name} for {CHART_PRICE} gold")
+        };
+    }
+
+    /// ask the port the player is docked at for letters of marque
+    fn marque(&mut self) {
+        let Some(port) = self.player.docked else {
+            self.message = "you need to be in port to ask for letters of marque".into();
+            return;
+        };
+
+        let owner = self.world.history().ports[port].owner;
+        self.message = self
+            .reputation
+            .buy_marque(self.world.history(), owner, &mut self.gold);
+    }
+
+    /// save the game to [`SAVE_PATH`], telling the player how it went
+    fn quick_save(&mut self) {
+        self.message = match save::save(self, Path::new(SAVE_PATH)) {
+            Ok(()) => format!("saved to {SAVE_PATH}"),
+            Err(e) => {
+                error!("Unable to save game to {SAVE_PATH}. Error: {e}");
+                format!("couldn't save the game: {e}")
+            }
+        };
+    }
+
+    /// let an hour go by without doing anything
+    fn wait(&mut self) {
+        self.clock.next_turn();
+        self.message = "you wait for an hour".into();
+        self.pass_time(clock::MINUTES_PER_HOUR);
+    }
+
+    /// an hour on the running clock, spent following the plotted course if there is one
+    fn tick(&mut self) {
+        if self.course.is_empty() {
+            self.pass_time(clock::MINUTES_PER_HOUR);
+        } else {
+            self.follow_course();
+        }
+    }
+
+    /// try to sail the player one tile in the direction (dx, dy)
+    fn sail(&mut self, dx: isize, dy: isize) {
+        let (x, y) = self.player.position;
+        let (Some(tx), Some(ty)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+            self.message = "there's nothing past the edge of the world".into();
+            return;
+        };
+
+        if let Some(npc) = self.fleet.ship_at(tx, ty) {
+            self.message = format!("you bear down on {}", npc.ship.name);
+            self.engaged = Some((npc.id, Aggressor::Player));
+            return;
+        }
+
+        let Some(hours) = sailing_hours(&self.world, (x, y), (tx, ty)) else {
+            self.message = if self.world.in_bounds(tx, ty) && self.world.is_frozen(tx, ty) {
+                "the way is frozen solid".into()
+            } else {
+                "you can't sail onto land".into()
+            };
+            return;
+        };
+
+        if let Some(port) = self.world.port_at(tx, ty) {
+            let port = &self.world.history().ports[port];
+            if !self.reputation.welcome(port.owner) {
+                self.message = format!(
+                    "{} has closed its harbour to you. [b] to bribe your way in",
+                    port.name
+                );
+                return;
+            }
+        }
+
+        // torn rigging and foul weather slow the ship down, and a good navigator speeds her up
+        let hours = hours
+            / (self.player.trim()
+                * self.crew.modifier(Skill::Navigation)
+                * self.weather.at(x, y).speed());
+        self.player.position = (tx, ty);
+        self.player.docked = self.world.port_at(tx, ty);
+
+        self.message = match self.player.docked {
+            Some(port) => format!("docked at {}", self.world.history().ports[port].name),
+            None => format!("sailed for {hours:.1} hours"),
+        };
+
+        self.clock.next_turn();
+        #[allow(clippy::cast_sign_loss)]
+        self.pass_time((hours * clock::MINUTES_PER_HOUR as f64).round() as u64);
+    }
+
+    /// plot a course to the next port along from the current destination
+    fn plot_course(&mut self) {
+        let history = self.world.history();
+        // nobody can plot a course to somewhere they've never heard of
+        let ports: Vec<_> = history
+            .living_ports()
+            .filter(|(_, port)| self.fog.is_explored(port.location.0, port.location.1))
+            .map(|(id, _)| id)
+            .filter(|&id| Some(id) != self.player.docked)
+            .collect();
+
+        let next = match self.destination {
+            Some(current) => ports.iter().find(|&&id| id > current).or(ports.first()),
+            None => ports.first(),
+        };
+        let Some(&port) = next else {
+            self.message = "there's nowhere you know of to sail to".into();
+            return;
+        };
+
+        self.destination = Some(port);
+        let name = &history.ports[port].name;
+
+        let chart = SeaChart::new(&self.world, &self.waters);
+        if let Some(route) = chart.route(self.player.position, history.ports[port].location) {
+            self.message = format!(
+                "course plotted to {name}, about {:.0} hours away",
+                route.hours
+            );
+            self.course = route.steps.into_iter().rev().collect();
+        } else {
+            self.message = format!("there's no way to sail to {name} from here");
+            self.course.clear();
+        }
+    }
+
+    /// take the next step of the plotted course. gives up on the course if the way is blocked
+    fn follow_course(&mut self) {
+        let Some((tx, ty)) = self.course.pop() else {
+            return;
+        };
+        // steer clear of running into anyone by accident, they'll likely be gone in an hour
+        if let Some(npc) = self.fleet.ship_at(tx, ty) {
+            self.message = format!("{} is in the way", npc.ship.name);
+            self.course.push((tx, ty));
+            self.pass_time(clock::MINUTES_PER_HOUR);
+            return;
+        }
+
+        let (x, y) = self.player.position;
+        self.sail(tx as isize - x as isize, ty as isize - y as isize);
+
+        if self.player.position != (tx, ty) {
+            self.course.clear();
+        }
+        if self.course.is_empty() {
+            self.destination = None;
+        }
+    }
+
+    /// the line of text shown in the clock overlay
+    fn clock_text(&self) -> String {
+        format!(
+            "{} | turn {}{}",
+            self.clock.date(),
+            self.clock.turns(),
+            if self.clock.paused { " | paused" } else { "" }
+        )
+    }
+
+    /// the view of the map around `focus`, leaving room underneath for the status
+    fn camera(&self, focus: Point<usize>) -> Camera {
+        let space = RectDimension::new(
+            self.screen.width,
+            self.screen.height.saturating_sub(STATUS_ROWS),
+        );
+
+        Camera::centred(focus, self.world.dimensions(), space)
+    }
+
+    /// the part of the world map `camera` can see, with the player and their course drawn on it,
+    /// and nothing underneath
+    fn map_frame(&self, camera: Camera) -> Frame {
+        let mut frame = Frame::blank(self.screen);
+        let mut map = worldgen::world_frame(&self.world, camera);
+        self.fog.shroud(&mut map, camera);
+        frame.draw(&map, 0, 0);
+
+        for &point in &self.course {
+            frame.set_on_map(camera, point, Tile::new(COURSE_CHAR, GRAY, BLACK));
+        }
+        for point in self.journal.strandings() {
+            frame.set_on_map(camera, point, Tile::new(STRANDED_CHAR, YELLOW, BLACK));
+        }
+
+        // the weather is only known where it can be seen
+        for (vx, vy) in map.to_render.points() {
+            let (x, y) = camera.to_map((vx, vy));
+            if self.fog.is_visible(x, y) {
+                let conditions = self.weather.at(x, y);
+                let tile = frame.to_render[(vx, vy)];
+                frame.set(vx, vy, conditions.overlay(tile));
+            }
+        }
+
+        for npc in self.fleet.at_sea() {
+            let (nx, ny) = npc.ship.position;
+            if self.fog.is_visible(nx, ny) {
+                frame.set_on_map(camera, (nx, ny), role_tile(npc.role));
+            }
+        }
+
+        frame.set_on_map(
+            camera,
+            self.player.position,
+            Tile::new(PLAYER_CHAR, YELLOW, BLACK),
+        );
+
+        frame
+    }
+
+    fn frame(&self) -> Frame {
+        let camera = self.camera(self.player.position);
+        let mut frame = self.map_frame(camera);
+        let (x, y) = self.player.position;
+
+        let history = self.world.history();
+        let location = match self.player.docked {
+            Some(port) => {
+                let owner = history.ports[port].owner;
+                format!(
+                    "docked at {} of {} ({}{})",
+                    history.ports[port].name,
+                    history.civilizations[owner].name,
+                    self.reputation.describe(owner),
+                    if self.reputation.has_marque(owner) {
+                        ", privateer"
+                    } else {
+                        ""
+                    }
+                )
+            }
+            None => format!(
+                "at sea, depth {:.0}",
+                self.world.terrain().depth(x, y).max(0.)
+            ),
+        };
+        let wind = self.world.wind_at(x, y);
+        let current = self.world.current_at(x, y);
+
+        let below_map = camera.view.height as usize;
+        frame.print(
+            0,
+            below_map,
+            &format!(
+                "{} ({x}, {y}) {location}  {} gold, hold {}/{}, hull {}",
+                self.player.name,
+                self.gold,
+                self.player.cargo_total(),
+                self.player.hold,
+                self.player.hull
+            ),
+            WHITE,
+            BLACK,
+        );
+        frame.print(
+            0,
+            below_map + 1,
+            &format!(
+                "{}, wind {} {:.1}  current {} {:.1}  crew {} fit, {} hurt, morale {}",
+                self.weather.at(x, y),
+                compass(wind),
+                wind.0.hypot(wind.1),
+                compass(current),
+                current.0.hypot(current.1),
+                self.crew.fit(),
+                self.crew.injured(),
+                self.crew.morale()
+            ),
+            WHITE,
+            BLACK,
+        );
+        frame.print(0, below_map + 2, &self.message, YELLOW, BLACK);
+        frame.print(
+            0,
+            below_map + 3,
+            "[arrows] sail [p] plot [enter] follow [.] wait [space] pause [d] dig [i] look [f5] save [esc] menu",
+            GRAY,
+            BLACK,
+        );
+        frame.print(
+            0,
+            below_map + 4,
+            "[x] maps [j] contracts [l] legends  in port: [t] trade [h] hire [c] charts [g] map [m] marque [b] bribe",
+            GRAY,
+            BLACK,
+        );
+
+        frame
+    }
+}
+
+/// how an NPC ship is drawn on the map
+fn role_tile(role: Role) -> Tile {
+    match role {
+        Role::Merchant => Tile::new('m', GREEN, BLACK),
+        Role::Navy => Tile::new('n', CYAN, BLACK),
+        Role::Pirate => Tile::new('p', RED, BLACK),
+    }
+}
+
+/// the first tile ships can sail on, or the top left corner if the whole world is land
+fn first_open_water(world: &FullWorld) -> (usize, usize) {
+    let dimensions = world.dimensions();
+
+    (0..dimensions.height as usize)
+        .flat_map(|y| (0..dimensions.width as usize).map(move |x| (x, y)))
+        .find(|&(x, y)| world.is_navigable(x, y))
+        .unwrap_or((0, 0))
+}
+
+/// which way a key moves a ship, if it moves it at all
+fn direction(key: VirtualKeyCode) -> Option<(isize, isize)> {
+    match key {
+        VirtualKeyCode::Up | VirtualKeyCode::Numpad8 => Some((0, -1)),
+        VirtualKeyCode::Down | VirtualKeyCode::Numpad2 => Some((0, 1)),
+        VirtualKeyCode::Left | VirtualKeyCode::Numpad4 => Some((-1, 0)),
+        VirtualKeyCode::Right | VirtualKeyCode::Numpad6 => Some((1, 0)),
+        VirtualKeyCode::Numpad7 => Some((-1, -1)),
+        VirtualKeyCode::Numpad9 => Some((1, -1)),
+        VirtualKeyCode::Numpad1 => Some((-1, 1)),
+        VirtualKeyCode::Numpad3 => Some((1, 1)),
+        _ => None,
+    }
+}
+
+/// fight whoever the player is engaged with, if anyone. returns false if the window was closed
+fn engage(game: &mut Game, sender: &Sender<RenderPacket>, receiver: &Receiver<RenderTick>) -> bool {
+    while let Some((id, aggressor)) = game.engaged.take() {
+        // a fight stops the clock, nobody wants to come back to find the ship sailing off
+        game.clock.paused = true;
+        game.course.clear();
+        game.destination = None;
+
+        if !combat::fight(game, id, aggressor, sender, receiver) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// open the trading screen for [t] or the hiring screen for [h], as long as the player is in port.
+/// returns false if the window was closed
+fn in_port(
+    game: &mut Game,
+    key: VirtualKeyCode,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let trading = key == VirtualKeyCode::T;
+    let Some(port) = game.player.docked else {
+        game.message = if trading {
+            "you need to be in port to trade".into()
+        } else {
+            "you need to be in port to hire anyone".into()
+        };
+        return true;
+    };
+
+    if trading {
+        trade::trade(game, port, sender, receiver)
+    } else {
+        hire::hiring(game, port, sender, receiver)
+    }
+}
+
+/// play `game` until the window is closed, or until the player asks for a new world from the menu,
+/// in which case the params for it are handed back
+#[allow(clippy::too_many_lines)]
+pub fn play(
+    mut game: Game,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> Option<worldgen::GenParam> {
+    let screen = game.screen;
+
+    let clock_gui = GUI {
+        offset: (OffsetX::Right(0), OffsetY::Above(0)),
+        to_render: Some(render::string_to_frame(&game.clock_text())),
+    };
+    let clock_id = render::register_gui(sender, receiver, 1, clock_gui);
+    let menu_id = menu::register(sender, receiver);
+
+    let send = |game: &Game| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(game.frame())) {
+            error!("Unable to send game to render thread. Error: {e}");
+        }
+
+        if let Some(id) = clock_id {
+            let update = Some(render::string_to_frame(&game.clock_text()));
+            sender.send(RenderPacket::UpdateGUI { id, update }).ok();
+        }
+    };
+
+    send(&game);
+
+    loop {
+        // while the clock runs, an hour passes every time we go too long without any input
+        let tick = if game.clock.paused {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            receiver.recv_timeout(REAL_TIME_HOUR)
+        };
+
+        let tick = match tick {
+            Ok(tick) => tick,
+            Err(RecvTimeoutError::Timeout) => {
+                game.tick();
+                if !engage(&mut game, sender, receiver) {
+                    return None;
+                }
+                send(&game);
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => return None,
+        };
+
+        match tick {
+            RenderTick::Key(VirtualKeyCode::Escape) => {
+                match menu::pause(&mut game, menu_id, sender, receiver) {
+                    Choice::Resume => {}
+                    Choice::Load(loaded) => game = *loaded,
+                    Choice::Closed => return None,
+                    Choice::NewWorld(params) => {
+                        // the next game registers a clock of its own
+                        if let Some(id) = clock_id {
+                            sender
+                                .send(RenderPacket::UpdateGUI { id, update: None })
+                                .ok();
+                        }
+                        return Some(*params);
+                    }
+                }
+            }
+            RenderTick::Key(VirtualKeyCode::Space) => game.clock.paused = !game.clock.paused,
+            RenderTick::Key(VirtualKeyCode::Period) => game.wait(),
+            RenderTick::Key(VirtualKeyCode::P) => game.plot_course(),
+            // following a course happens as time passes, so just get the clock going
+            RenderTick::Key(VirtualKeyCode::Return) => game.clock.paused = false,
+            RenderTick::Key(key @ (VirtualKeyCode::T | VirtualKeyCode::H)) => {
+                if !in_port(&mut game, key, sender, receiver) {
+                    return None;
+                }
+            }
+            RenderTick::Key(VirtualKeyCode::B) => game.bribe(),
+            RenderTick::Key(VirtualKeyCode::M) => game.marque(),
+            RenderTick::Key(VirtualKeyCode::C) => game.buy_charts(),
+            RenderTick::Key(VirtualKeyCode::G) => treasure::buy_map(&mut game),
+            RenderTick::Key(VirtualKeyCode::D) => treasure::dig(&mut game),
+            RenderTick::Key(VirtualKeyCode::X) => {
+                if !treasure::maps(&mut game, sender, receiver) {
+                    return None;
+                }
+            }
+            RenderTick::Key(VirtualKeyCode::J) => {
+                if !contract::contracts(&mut game, sender, receiver) {
+                    return None;
+                }
+            }
+            RenderTick::Key(VirtualKeyCode::F5) => game.quick_save(),
+            RenderTick::Key(VirtualKeyCode::I) => {
+                if !inspect::inspect(&game, sender, receiver) {
+                    return None;
+                }
+            }
+            RenderTick::Key(VirtualKeyCode::L) => {
+                if !legends::browse(&game.world, sender, receiver, screen) {
+                    return None;
+                }
+            }
+            RenderTick::Key(key) => match direction(key) {
+                Some((dx, dy)) => {
+                    // taking the helm gives up on any plotted course
+                    game.course.clear();
+                    game.destination = None;
+                    game.sail(dx, dy);
+                }
+                None => continue,
+            },
+            RenderTick::LoopClosed => return None,
+            _ => continue,
+        }
+
+        if !engage(&mut game, sender, receiver) {
+            return None;
+        }
+        send(&game);
+    }
+}
@@ -0,0 +1,344 @@
+//! finding the way across the sea
+//!
+//! [`SeaChart`] is a view of a world that bracket-lib's pathfinding understands. moving from one
+//! tile to the next costs as many hours as it takes to sail there, so routes ride the wind and
+//! currents and keep clear of shallows and ice when they can
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use bracket_lib::pathfinding::{Algorithm2D, BaseMap, DistanceAlg, SmallVec};
+
+use crate::helpers::{points_around, Grid, Neighbours, Point, Wrap};
+use crate::worldgen::history::PortId;
+use crate::worldgen::FullWorld;
+
+use super::ship::{sailing_hours, MAX_SPEED};
+
+/// a way from one place to another
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    /// every tile along the way, not counting where the route starts
+    pub steps: Vec<Point<usize>>,
+    /// how long the whole thing takes to sail, in hours
+    pub hours: f64,
+}
+
+/// which body of water every tile belongs to, so we know straight away when there is no way
+/// across. A* has to search the whole sea before giving up otherwise
+///
+/// slow to work out, so it's done once and kept around
+#[derive(Debug, Clone, Default)]
+pub struct Waters {
+    /// None for tiles ships can't go
+    tiles: Grid<Option<usize>>,
+}
+
+impl Waters {
+    /// flood fill every body of water in `world`
+    pub fn new(world: &FullWorld) -> Self {
+        let mut waters = Grid::filled(world.dimensions(), None);
+        let mut next_water = 0;
+
+        for start in waters.points().collect::<Vec<_>>() {
+            if waters[start].is_some() || !world.is_navigable(start.0, start.1) {
+                continue;
+            }
+
+            waters[start] = Some(next_water);
+            let mut open = vec![start];
+            while let Some(point) = open.pop() {
+                let around: Vec<_> = waters
+                    .neighbours(point, Neighbours::Eight, Wrap::Never)
+                    .collect();
+                for next in around {
+                    if world.is_navigable(next.0, next.1) && waters[next].is_none() {
+                        waters[next] = Some(next_water);
+                        open.push(next);
+                    }
+                }
+            }
+
+            next_water += 1;
+        }
+
+        Waters { tiles: waters }
+    }
+
+    /// the body of water (x, y) is part of, if ships can go there at all
+    pub fn at(&self, (x, y): Point<usize>) -> Option<usize> {
+        *self.tiles.get(x, y)?
+    }
+}
+
+/// a tile waiting to be looked at by [`SeaChart::a_star`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+    idx: usize,
+    /// hours to get here
+    hours: f32,
+    /// hours to get here, plus a guess at the hours left to go
+    estimate: f32,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    /// backwards, so the heap hands out the lowest estimate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// the sea of a world, as far as sailing it goes
+pub struct SeaChart<'a> {
+    world: &'a FullWorld,
+    waters: &'a Waters,
+}
+
+impl<'a> SeaChart<'a> {
+    pub fn new(world: &'a FullWorld, waters: &'a Waters) -> Self {
+        SeaChart { world, waters }
+    }
+
+    pub fn world(&self) -> &'a FullWorld {
+        self.world
+    }
+
+    fn index(&self, (x, y): Point<usize>) -> Option<usize> {
+        self.waters.tiles.index(x, y)
+    }
+
+    fn point(&self, idx: usize) -> Option<Point<usize>> {
+        self.waters.tiles.point(idx)
+    }
+
+    /// A* over our own [`BaseMap`] implementation. returns every tile from `start` to `end`, not
+    /// counting `start`
+    ///
+    /// bracket-lib's `a_star_search` can search a [`SeaChart`] too, but it can't be used for this.
+    /// it keeps only the cost of the last step into a tile as the cost of getting there, not the
+    /// whole way, so with steps costing different amounts it settles for slow routes. it also gives
+    /// up after 65536 steps, fewer than there are tiles in a 2048x1024 world, so long routes are
+    /// never found at all
+    fn a_star(&self, start: usize, end: usize) -> Option<Vec<usize>> {
+        let mut hours = vec![f32::INFINITY; self.world.num_tiles()];
+        let mut parents = vec![usize::MAX; self.world.num_tiles()];
+        let mut open = BinaryHeap::new();
+
+        hours[start] = 0.;
+        open.push(Open {
+            idx: start,
+            hours: 0.,
+            estimate: self.get_pathing_distance(start, end),
+        });
+
+        while let Some(current) = open.pop() {
+            if current.idx == end {
+                let mut path = vec![end];
+                while let Some(&last) = path.last() {
+                    match parents[last] {
+                        parent if parent == start || parent == usize::MAX => break,
+                        parent => path.push(parent),
+                    }
+                }
+                path.reverse();
+
+                return Some(path);
+            }
+            // already been here a quicker way
+            if current.hours > hours[current.idx] {
+                continue;
+            }
+
+            for (next, cost) in self.get_available_exits(current.idx) {
+                let next_hours = current.hours + cost;
+                if next_hours < hours[next] {
+                    hours[next] = next_hours;
+                    parents[next] = current.idx;
+                    open.push(Open {
+                        idx: next,
+                        hours: next_hours,
+                        estimate: next_hours + self.get_pathing_distance(next, end),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// the quickest way to sail from `from` to `to`, or None if there is no way there at all.
+    /// both ends have to be somewhere a ship can be
+    pub fn route(&self, from: Point<usize>, to: Point<usize>) -> Option<Route> {
+        if !self.world.in_bounds(from.0, from.1) || !self.world.in_bounds(to.0, to.1) {
+            return None;
+        }
+        if from == to {
+            return Some(Route::default());
+        }
+
+        let water = self.waters.at(from);
+        if water.is_none() || water != self.waters.at(to) {
+            return None;
+        }
+
+        let steps = self
+            .a_star(self.index(from)?, self.index(to)?)?
+            .into_iter()
+            .map(|i| self.point(i))
+            .collect::<Option<Vec<_>>>()?;
+        let mut hours = 0.;
+        let mut at = from;
+        for &step in &steps {
+            hours += sailing_hours(self.world, at, step)?;
+            at = step;
+        }
+
+        Some(Route { steps, hours })
+    }
+
+    /// the quickest way from one port to another
+    pub fn route_between(&self, from: PortId, to: PortId) -> Option<Route> {
+        let ports = &self.world.history().ports;
+
+        self.route(ports.get(from)?.location, ports.get(to)?.location)
+    }
+}
+
+impl BaseMap for SeaChart<'_> {
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
+        let Some(from) = self.point(idx) else {
+            return exits;
+        };
+
+        for (x, y) in points_around(from.0 as isize, from.1 as isize) {
+            let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+                continue;
+            };
+
+            if let (Some(hours), Some(to)) =
+                (sailing_hours(self.world, from, (x, y)), self.index((x, y)))
+            {
+                exits.push((to, hours as f32));
+            }
+        }
+
+        exits
+    }
+
+    /// never more than the real cost, or A* stops finding the quickest routes
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        let distance = DistanceAlg::Pythagoras
+            .distance2d(self.index_to_point2d(idx1), self.index_to_point2d(idx2));
+
+        distance / MAX_SPEED as f32
+    }
+}
+
+impl Algorithm2D for SeaChart<'_> {
+    fn dimensions(&self) -> bracket_lib::pathfinding::Point {
+        let dimensions = self.world.dimensions();
+
+        bracket_lib::pathfinding::Point::new(dimensions.width, dimensions.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bracket_lib::pathfinding::a_star_search;
+
+    use crate::helpers::RectDimension;
+    use crate::worldgen::GenParam;
+
+    use super::*;
+
+    const LAND: f64 = 10.;
+    const SEA: f64 = -10.;
+
+    /// open sea with an island in the middle, and a lake to the north east with a ring of land all
+    /// the way around it
+    fn world() -> FullWorld {
+        let heights = Grid::from_fn(RectDimension::new(40, 30), |x, y| {
+            let island = (15..25).contains(&x) && (8..22).contains(&y);
+            let ring = (30..38).contains(&x) && (2..10).contains(&y);
+            let lake = (32..36).contains(&x) && (4..8).contains(&y);
+
+            if (island || ring) && !lake {
+                LAND
+            } else {
+                SEA
+            }
+        });
+        let params = GenParam {
+            seed: 1,
+            world_size: heights.dimensions(),
+            // less than there is sea, so all of it ends up under
+            target_water: 50.,
+            ..GenParam::default()
+        };
+
+        FullWorld::from_height_map(heights, &params)
+    }
+
+    #[test]
+    fn routes_go_around_islands() {
+        let world = world();
+        let waters = Waters::new(&world);
+        let chart = SeaChart::new(&world, &waters);
+
+        let route = chart.route((5, 15), (35, 15)).unwrap();
+        assert_eq!(route.steps.last(), Some(&(35, 15)));
+        assert!(route.steps.iter().all(|&(x, y)| world.is_navigable(x, y)));
+        // it had to go above or below the island to get past it
+        assert!(route
+            .steps
+            .iter()
+            .any(|&(x, y)| (15..25).contains(&x) && !(8..22).contains(&y)));
+    }
+
+    #[test]
+    fn lakes_cant_be_reached_from_the_sea() {
+        let world = world();
+        let waters = Waters::new(&world);
+        let chart = SeaChart::new(&world, &waters);
+
+        assert!(world.is_navigable(33, 5));
+        assert_ne!(waters.at((33, 5)), waters.at((5, 15)));
+        assert!(chart.route((5, 15), (33, 5)).is_none());
+        assert!(chart.route((33, 5), (34, 6)).is_some());
+    }
+
+    #[test]
+    fn never_slower_than_bracket_lib() {
+        let world = world();
+        let waters = Waters::new(&world);
+        let chart = SeaChart::new(&world, &waters);
+
+        for (from, to) in [((5, 15), (35, 15)), ((2, 2), (38, 28)), ((20, 2), (20, 27))] {
+            let route = chart.route(from, to).unwrap();
+
+            // bracket-lib's search works on the same chart, it just doesn't find as good a way
+            let path = a_star_search(chart.index(from).unwrap(), chart.index(to).unwrap(), &chart);
+            assert!(path.success);
+            let steps: Vec<_> = path.steps.iter().filter_map(|&i| chart.point(i)).collect();
+            let hours: f64 = steps
+                .windows(2)
+                .map(|step| sailing_hours(&world, step[0], step[1]).unwrap())
+                .sum();
+
+            assert!(
+                route.hours <= hours + 1e-6,
+                "{} against {hours}",
+                route.hours
+            );
+        }
+    }
+}
@@ -0,0 +1,1185 @@
+//! the other ships at sea
+//!
+//! every ship decides what to do next by scoring everything it could be doing and going with the
+//! best of them. merchants chase profit, navies chase pirates and their enemies' shipping, pirates
+//! chase anything worth robbing, and everyone runs from whatever would sink them. how a ship treats
+//! the player comes down to what the ship's civilization makes of them
+
+use std::collections::HashMap;
+
+use bracket_lib::random::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{points_around, Point};
+use crate::worldgen::history::{CivId, History, PortId, Relation};
+use crate::worldgen::{names, FullWorld};
+
+use super::economy::{Economy, Good, GOOD_COUNT};
+use super::navigation::{Route, SeaChart};
+use super::reputation::Reputation;
+use super::ship::{sailing_hours, Ship, MAX_HULL, MAX_SAILS};
+
+pub type NpcId = usize;
+
+/// how many tiles away ships can spot each other
+const SIGHT: usize = 8;
+/// how close to the player something has to happen for them to hear about it
+const NEWS_RANGE: usize = 12;
+/// pirates leave alone anyone sailing this close to the player's guns
+const GUARD_RANGE: usize = 2;
+/// merchants only look for trade with this many of the closest ports
+const TRADE_PARTNERS: usize = 6;
+/// how long ships stay put once they get where they were going
+const REST_HOURS: f64 = 12.;
+/// how far from a port pirates like to lie in wait
+const AMBUSH_RANGE: (isize, isize) = (2, 6);
+/// one pirate for every this many ports, plus one
+const PORTS_PER_PIRATE: usize = 4;
+/// one navy ship for every this many ports of a civilization, plus one
+const PORTS_PER_NAVY: usize = 3;
+/// how much a plan already under way is worth, so ships don't keep changing their minds
+const CARRY_ON: f64 = 1.;
+/// how many times to try for a ship name nobody else has
+const UNIQUE_NAME_TRIES: usize = 10;
+/// a ship never takes more steps than this in one go, just in case
+const MAX_STEPS: usize = 64;
+/// what a merchant coming to trade does for how two civilizations feel about each other
+const TRADE_OPINION: i32 = 1;
+/// what losing a ship to an enemy navy does for how two civilizations feel about each other
+const PRIZE_OPINION: i32 = -5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    Merchant,
+    Navy,
+    Pirate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Goal {
+    /// staying put for this many more hours
+    Rest(f64),
+    /// following the course wherever it goes
+    Voyage,
+    /// going after another ship
+    Chase(NpcId),
+    /// getting away from another ship
+    Flee(NpcId),
+    /// going after the player
+    ChasePlayer,
+    /// getting away from the player
+    FleePlayer,
+}
+
+/// a ship that isn't the player's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Npc {
+    pub id: NpcId,
+    pub ship: Ship,
+    pub role: Role,
+    /// who the ship sails for. pirates sail for nobody
+    pub civ: Option<CivId>,
+    pub captain: String,
+    /// the port a ship hired for a voyage is bound for, wherever trade would take her
+    charter: Option<PortId>,
+    goal: Goal,
+    /// what's left of the current course, backwards so the next step is at the end
+    course: Vec<Point<usize>>,
+    /// where the ship was bound before she went off chasing or running from someone, to make
+    /// for again once that's over
+    #[serde(default)]
+    bound_for: Option<Point<usize>>,
+    /// hours the ship has to spend, carried over between turns so slow steps still get taken
+    time: f64,
+}
+
+/// something a ship could do, and how much it wants to do it
+struct Plan {
+    goal: Goal,
+    course: Vec<Point<usize>>,
+    /// goods to load before setting off
+    load: Option<(Good, u32)>,
+    utility: f64,
+}
+
+impl Plan {
+    fn new(goal: Goal, utility: f64) -> Self {
+        Plan {
+            goal,
+            course: Vec::new(),
+            load: None,
+            utility,
+        }
+    }
+
+    fn voyage(route: &Route, utility: f64) -> Self {
+        Plan {
+            goal: Goal::Voyage,
+            course: route.steps.iter().rev().copied().collect(),
+            load: None,
+            utility,
+        }
+    }
+}
+
+impl Npc {
+    /// what sort of ship this is and who she sails for, like "a merchant of somewhere"
+    pub fn describe(&self, history: &History) -> String {
+        let flag = self
+            .civ
+            .map_or("", |civ| history.civilizations[civ].name.as_str());
+
+        match self.role {
+            Role::Merchant => format!("a merchant of {flag}"),
+            Role::Navy => format!("a warship of {flag}"),
+            Role::Pirate => "a pirate".to_string(),
+        }
+    }
+}
+
+/// how many steps it takes to get from `a` to `b`, diagonals included
+fn distance(a: Point<usize>, b: Point<usize>) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// the tiles next to `p` that a ship could sail into
+fn neighbours(world: &FullWorld, p: Point<usize>) -> impl Iterator<Item = Point<usize>> + '_ {
+    points_around(p.0 as isize, p.1 as isize)
+        .into_iter()
+        .filter_map(|(x, y)| Some((usize::try_from(x).ok()?, usize::try_from(y).ok()?)))
+        .filter(|&(x, y)| world.is_navigable(x, y))
+}
+
+/// how much fight a ship has in her, to settle who wins when two ships meet
+fn strength(ship: &Ship) -> u32 {
+    ship.guns * ship.crew
+}
+
+/// the neighbouring tile of `from` a ship can sail into with the highest `score`
+fn best_step(
+    world: &FullWorld,
+    from: Point<usize>,
+    score: impl Fn(Point<usize>) -> f64,
+) -> Option<Point<usize>> {
+    neighbours(world, from).max_by(|&a, &b| score(a).total_cmp(&score(b)))
+}
+
+/// the way to whichever of the closest few ports can be reached first
+fn nearest_port(chart: &SeaChart, from: Point<usize>) -> Option<Route> {
+    let mut ports: Vec<_> = chart
+        .world()
+        .history()
+        .living_ports()
+        .map(|(_, port)| (distance(from, port.location), port.location))
+        .collect();
+    ports.sort_unstable();
+
+    ports
+        .into_iter()
+        .take(TRADE_PARTNERS)
+        .filter_map(|(_, to)| chart.route(from, to))
+        .min_by(|a, b| a.hours.total_cmp(&b.hours))
+}
+
+/// every NPC ship in the world
+#[derive(Serialize, Deserialize)]
+pub struct Fleet {
+    npcs: Vec<Npc>,
+    next_id: NpcId,
+    /// port to port routes, worked out the first time someone needs them
+    #[serde(skip)]
+    routes: HashMap<(PortId, PortId), Option<Route>>,
+    /// how civilizations' opinions of each other changed because of their ships, waiting to be
+    /// passed on to the history
+    dealings: Vec<(CivId, CivId, i32)>,
+    rng: RandomNumberGenerator,
+}
+
+impl Fleet {
+    /// fill the seas with merchants and navies for every civilization, and pirates to prey on them
+    pub fn new(world: &FullWorld, rng: RandomNumberGenerator) -> Self {
+        let mut fleet = Fleet {
+            npcs: Vec::new(),
+            next_id: 0,
+            routes: HashMap::new(),
+            dealings: Vec::new(),
+            rng,
+        };
+        let history = world.history();
+
+        for civ in 0..history.civilizations.len() {
+            let ports: Vec<_> = history.ports_of(civ).map(|(id, _)| id).collect();
+            if ports.is_empty() {
+                continue;
+            }
+
+            for &port in &ports {
+                fleet.spawn(world, Role::Merchant, Some(civ), port);
+            }
+        }
+
+        // navies and pirates can start out anywhere
+        fleet.replenish(world);
+
+        fleet
+    }
+
+    /// how many pirates the seas can support
+    fn wanted_pirates(world: &FullWorld) -> usize {
+        world.history().living_ports().count() / PORTS_PER_PIRATE + 1
+    }
+
+    /// how many ships of `role` a civilization with `ports` ports keeps at sea
+    fn wanted(role: Role, ports: usize) -> usize {
+        match role {
+            Role::Merchant => ports,
+            Role::Navy => ports / PORTS_PER_NAVY + 1,
+            Role::Pirate => 0,
+        }
+    }
+
+    /// put a new ship in port
+    fn spawn(&mut self, world: &FullWorld, role: Role, civ: Option<CivId>, port: PortId) -> NpcId {
+        let position = world.history().ports[port].location;
+        let mut name = names::ship(&mut self.rng);
+        // there aren't that many ship names to go round, but try not to have two at sea at once
+        for _ in 0..UNIQUE_NAME_TRIES {
+            if !self.npcs.iter().any(|npc| npc.ship.name == name) {
+                break;
+            }
+            name = names::ship(&mut self.rng);
+        }
+        let mut ship = Ship::new(name, position, Some(port));
+        match role {
+            // warships give up cargo space for guns, and the hands to work them
+            Role::Navy => {
+                ship.hold /= 4;
+                ship.guns *= 2;
+                ship.crew *= 2;
+            }
+            // merchants sail with as few hands and guns as they can get away with
+            Role::Merchant => {
+                ship.guns /= 2;
+                ship.crew = ship.crew * 3 / 5;
+            }
+            // pirates need plenty of hands for boarding
+            Role::Pirate => ship.crew = ship.crew * 7 / 4,
+        }
+
+        self.npcs.push(Npc {
+            id: self.next_id,
+            ship,
+            role,
+            civ,
+            captain: names::captain(&mut self.rng),
+            charter: None,
+            // so they don't all set off at once
+            goal: Goal::Rest(self.rng.range(0., REST_HOURS)),
+            course: Vec::new(),
+            bound_for: None,
+            time: 0.,
+        });
+        self.next_id += 1;
+
+        self.next_id - 1
+    }
+
+    /// put a merchant of `civ` called `name` in port at `from`, bound for `to` with a hold half
+    /// full, and keeping to that voyage until released
+    pub fn charter(
+        &mut self,
+        world: &FullWorld,
+        civ: Option<CivId>,
+        (from, to): (PortId, PortId),
+        (name, captain): (String, String),
+    ) -> NpcId {
+        let id = self.spawn(world, Role::Merchant, civ, from);
+        let good = Good::ALL[self.rng.range(0, GOOD_COUNT)];
+
+        if let Some(npc) = self.get_mut(id) {
+            npc.ship.name = name;
+            npc.captain = captain;
+            npc.charter = Some(to);
+            npc.goal = Goal::Rest(0.);
+            npc.ship.cargo[good.index()] = npc.ship.hold / 2;
+        }
+
+        id
+    }
+
+    /// let a chartered ship go back to trading wherever she likes
+    pub fn release(&mut self, id: NpcId) {
+        if let Some(npc) = self.get_mut(id) {
+            npc.charter = None;
+        }
+    }
+
+    /// bring every civilization's navy and merchants, and the pirates, back up to strength after
+    /// some were sunk or taken
+    pub fn replenish(&mut self, world: &FullWorld) {
+        let history = world.history();
+        for civ in 0..history.civilizations.len() {
+            let ports: Vec<_> = history.ports_of(civ).map(|(id, _)| id).collect();
+
+            for role in [Role::Merchant, Role::Navy] {
+                let have = self
+                    .npcs
+                    .iter()
+                    .filter(|n| n.role == role && n.civ == Some(civ))
+                    .count();
+
+                for _ in have..Self::wanted(role, ports.len()) {
+                    // civilizations with no ports left don't build any more ships
+                    let Some(&port) = self.rng.random_slice_entry(&ports) else {
+                        break;
+                    };
+                    self.spawn(world, role, Some(civ), port);
+                }
+            }
+        }
+
+        let ports: Vec<_> = history.living_ports().map(|(id, _)| id).collect();
+        let pirates = self.npcs.iter().filter(|n| n.role == Role::Pirate).count();
+
+        for _ in pirates..Self::wanted_pirates(world) {
+            // pirates turn up at the edge of some port or other, hiding amongst everyone else
+            let Some(&port) = self.rng.random_slice_entry(&ports) else {
+                return;
+            };
+            self.spawn(world, Role::Pirate, None, port);
+        }
+    }
+
+    /// every ship at sea. ships in port are hidden away behind the port itself
+    pub fn at_sea(&self) -> impl Iterator<Item = &Npc> {
+        self.npcs.iter().filter(|npc| npc.ship.docked.is_none())
+    }
+
+    /// every pirate, at sea or selling off their loot in port
+    pub fn pirates(&self) -> impl Iterator<Item = &Npc> {
+        self.npcs.iter().filter(|npc| npc.role == Role::Pirate)
+    }
+
+    /// the ship at (x, y), if there is one at sea there
+    pub fn ship_at(&self, x: usize, y: usize) -> Option<&Npc> {
+        self.at_sea().find(|npc| npc.ship.position == (x, y))
+    }
+
+    pub fn get(&self, id: NpcId) -> Option<&Npc> {
+        self.npcs.iter().find(|npc| npc.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: NpcId) -> Option<&mut Npc> {
+        self.npcs.iter_mut().find(|npc| npc.id == id)
+    }
+
+    /// take a ship out of the fleet for good, because it was sunk or taken
+    pub fn remove(&mut self, id: NpcId) -> Option<Npc> {
+        let i = self.npcs.iter().position(|npc| npc.id == id)?;
+
+        Some(self.npcs.remove(i))
+    }
+
+    /// leave a ship be for a while after a fight, so it doesn't pick another one straight away
+    pub fn disengage(&mut self, id: NpcId) {
+        if let Some(npc) = self.get_mut(id) {
+            npc.goal = Goal::Rest(REST_HOURS);
+            npc.course.clear();
+        }
+    }
+
+    /// everything ships did to sway how civilizations feel about each other since the last call,
+    /// as (civ, other civ, change in opinion)
+    pub fn take_dealings(&mut self) -> Vec<(CivId, CivId, i32)> {
+        std::mem::take(&mut self.dealings)
+    }
+
+    /// a ship that has caught up with the player and means to attack, if there is one
+    pub fn attacker(&self, player: &Ship) -> Option<NpcId> {
+        if player.docked.is_some() {
+            return None;
+        }
+
+        self.at_sea()
+            .find(|npc| {
+                npc.goal == Goal::ChasePlayer && distance(npc.ship.position, player.position) <= 1
+            })
+            .map(|npc| npc.id)
+    }
+
+    /// let `hours` pass for every ship. returns news of anything that happened near `player`
+    pub fn advance(
+        &mut self,
+        chart: &SeaChart,
+        economy: &mut Economy,
+        hours: f64,
+        (player, reputation): (&Ship, &Reputation),
+    ) -> Vec<String> {
+        for i in 0..self.npcs.len() {
+            self.npcs[i].time += hours;
+            self.act(i, chart, economy, (player, reputation));
+        }
+
+        self.encounters(chart.world(), player.position)
+    }
+
+    /// spend the time a ship has on doing whatever it wants to do
+    fn act(
+        &mut self,
+        i: usize,
+        chart: &SeaChart,
+        economy: &mut Economy,
+        player: (&Ship, &Reputation),
+    ) {
+        let world = chart.world();
+
+        for _ in 0..MAX_STEPS {
+            let npc = &mut self.npcs[i];
+
+            match npc.goal {
+                Goal::Rest(left) if npc.time < left => {
+                    npc.goal = Goal::Rest(left - npc.time);
+                    npc.time = 0.;
+                    return;
+                }
+                Goal::Rest(left) => {
+                    npc.time -= left;
+                    self.decide(i, chart, economy, player);
+                }
+                Goal::Voyage if npc.course.is_empty() => self.arrive(i, world, economy),
+                Goal::Voyage
+                | Goal::Chase(_)
+                | Goal::Flee(_)
+                | Goal::ChasePlayer
+                | Goal::FleePlayer => {
+                    // things might have changed since the last step
+                    self.decide(i, chart, economy, player);
+
+                    let npc = &mut self.npcs[i];
+                    let Some(&next) = npc.course.last() else {
+                        // cornered, or nowhere to go. hold position for a bit
+                        if npc.goal != Goal::Voyage {
+                            npc.goal = Goal::Rest(1.);
+                        }
+                        continue;
+                    };
+                    let Some(hours) = sailing_hours(world, npc.ship.position, next) else {
+                        // something is in the way, think again in a bit
+                        npc.course.clear();
+                        npc.goal = Goal::Rest(1.);
+                        continue;
+                    };
+                    if npc.time < hours {
+                        return;
+                    }
+
+                    npc.time -= hours;
+                    npc.course.pop();
+                    npc.ship.position = next;
+                    npc.ship.docked = world.port_at(next.0, next.1);
+                }
+            }
+        }
+
+        // whatever kept the ship this busy, don't let the time pile up
+        self.npcs[i].time = 0.;
+    }
+
+    /// the end of a voyage. in port that means selling everything, at sea it means lying in wait
+    fn arrive(&mut self, i: usize, world: &FullWorld, economy: &mut Economy) {
+        let npc = &mut self.npcs[i];
+        npc.goal = Goal::Rest(REST_HOURS);
+
+        let (x, y) = npc.ship.position;
+        let Some(port) = world.port_at(x, y) else {
+            return;
+        };
+        npc.ship.docked = Some(port);
+        // a spell in port is enough to put right whatever happened at sea
+        npc.ship.repair(MAX_HULL.max(MAX_SAILS));
+
+        // trade brings people together, as long as they aren't at war
+        let owner = world.history().ports[port].owner;
+        if let Some(civ) = npc.civ.filter(|&civ| civ != owner) {
+            if npc.role == Role::Merchant && world.history().relation(civ, owner) != Relation::War {
+                self.dealings.push((civ, owner, TRADE_OPINION));
+            }
+        }
+
+        if let Some(market) = economy.market_mut(port) {
+            for good in Good::ALL {
+                market.add(good, npc.ship.cargo_of(good));
+            }
+        }
+        npc.ship.cargo = Default::default();
+    }
+
+    /// the route between two ports, from the cache if it's been worked out before
+    fn port_route(&mut self, chart: &SeaChart, from: PortId, to: PortId) -> Option<Route> {
+        self.routes
+            .entry((from, to))
+            .or_insert_with(|| chart.route_between(from, to))
+            .clone()
+    }
+
+    /// score everything ship `i` could be doing and set it off doing the best of them
+    fn decide(
+        &mut self,
+        i: usize,
+        chart: &SeaChart,
+        economy: &mut Economy,
+        player: (&Ship, &Reputation),
+    ) {
+        let mut plans = self.sightings(i, chart.world(), player);
+
+        let npc = &self.npcs[i];
+        // nothing needs to be planned out while busy, but keeping on as we are is still an option
+        let carry_on = match npc.goal {
+            Goal::Voyage if !npc.course.is_empty() => Some(Plan {
+                course: npc.course.clone(),
+                ..Plan::new(Goal::Voyage, CARRY_ON)
+            }),
+            // as long as whoever it is is still in sight, with a fresh step towards or away
+            Goal::Chase(_) | Goal::Flee(_) | Goal::ChasePlayer | Goal::FleePlayer => plans
+                .iter()
+                .find(|plan| plan.goal == npc.goal)
+                .map(|plan| Plan {
+                    course: plan.course.clone(),
+                    ..Plan::new(npc.goal, CARRY_ON)
+                }),
+            _ => None,
+        }
+        // whatever took the ship off her course is over, so back to it
+        .or_else(|| {
+            let to = npc.bound_for?;
+            chart
+                .route(npc.ship.position, to)
+                .map(|route| Plan::voyage(&route, CARRY_ON))
+        });
+
+        if let Some(plan) = carry_on {
+            plans.push(plan);
+        } else {
+            plans.extend(match npc.role {
+                Role::Merchant => self.merchant_plans(i, chart, economy),
+                Role::Navy => self.navy_plans(i, chart),
+                Role::Pirate => self.pirate_plans(i, chart),
+            });
+        }
+
+        let best = plans
+            .into_iter()
+            .max_by(|a, b| a.utility.total_cmp(&b.utility));
+
+        let npc = &mut self.npcs[i];
+        let Some(plan) = best else {
+            npc.goal = Goal::Rest(REST_HOURS);
+            return;
+        };
+
+        if let (Some((good, amount)), Some(port)) = (plan.load, npc.ship.docked) {
+            if economy
+                .market_mut(port)
+                .is_some_and(|market| market.remove(good, amount))
+            {
+                npc.ship.cargo[good.index()] += amount;
+            }
+        }
+
+        match plan.goal {
+            Goal::Voyage => npc.bound_for = None,
+            Goal::Rest(_) => {}
+            // the course is backwards, so where it ends up is first
+            _ if npc.goal == Goal::Voyage => npc.bound_for = npc.course.first().copied(),
+            _ => {}
+        }
+        npc.goal = plan.goal;
+        npc.course = plan.course;
+    }
+
+    /// chasing and running from other ships ship `i` can see, the player's included
+    fn sightings(
+        &self,
+        i: usize,
+        world: &FullWorld,
+        (player, reputation): (&Ship, &Reputation),
+    ) -> Vec<Plan> {
+        let history = world.history();
+        let npc = &self.npcs[i];
+        let mut plans = Vec::new();
+
+        // nobody is chasing anybody into port
+        if npc.ship.docked.is_some() {
+            return plans;
+        }
+
+        for other in self.at_sea() {
+            let d = distance(npc.ship.position, other.ship.position);
+            if other.id == npc.id || d > SIGHT {
+                continue;
+            }
+            let closeness = 1. / (1. + d as f64);
+            let enemies = matches!((npc.civ, other.civ),
+                (Some(a), Some(b)) if history.relation(a, b) == Relation::War);
+
+            let plan = match (npc.role, other.role) {
+                (Role::Navy, Role::Merchant | Role::Navy) if enemies => {
+                    Plan::new(Goal::Chase(other.id), 5. * closeness)
+                }
+                (Role::Merchant, Role::Navy) if enemies => {
+                    Plan::new(Goal::Flee(other.id), 10. * closeness)
+                }
+                (Role::Merchant, Role::Pirate) | (Role::Pirate, Role::Navy) => {
+                    Plan::new(Goal::Flee(other.id), 10. * closeness)
+                }
+                (Role::Navy, Role::Pirate) => Plan::new(Goal::Chase(other.id), 8. * closeness),
+                (Role::Pirate, Role::Merchant)
+                    if other.ship.cargo_total() > 0
+                        && npc.ship.free_hold() > 0
+                        && distance(other.ship.position, player.position) > GUARD_RANGE =>
+                {
+                    let loot = 1. + f64::from(other.ship.cargo_total()) / 20.;
+                    Plan::new(Goal::Chase(other.id), 3. * loot * closeness)
+                }
+                _ => continue,
+            };
+
+            plans.push(plan);
+        }
+
+        let d = distance(npc.ship.position, player.position);
+        if player.docked.is_none() && d <= SIGHT {
+            let closeness = 1. / (1. + d as f64);
+            let hostile = npc
+                .civ
+                .is_some_and(|civ| reputation.relation(civ) == Relation::War);
+
+            match npc.role {
+                // the player is fair game to pirates, cargo or not. there's always their purse
+                Role::Pirate => {
+                    let loot = 1. + f64::from(player.cargo_total()) / 20.;
+                    plans.push(Plan::new(Goal::ChasePlayer, 3. * loot * closeness));
+                }
+                Role::Navy if hostile => plans.push(Plan::new(Goal::ChasePlayer, 8. * closeness)),
+                Role::Merchant if hostile => {
+                    plans.push(Plan::new(Goal::FleePlayer, 10. * closeness));
+                }
+                Role::Navy | Role::Merchant => {}
+            }
+        }
+
+        // chasing and fleeing happen a step at a time, since whoever is being chased keeps moving
+        for plan in &mut plans {
+            let (towards, target) = match plan.goal {
+                Goal::Chase(id) | Goal::Flee(id) => match self.get(id) {
+                    Some(other) => (matches!(plan.goal, Goal::Chase(_)), other.ship.position),
+                    None => continue,
+                },
+                Goal::ChasePlayer => (true, player.position),
+                Goal::FleePlayer => (false, player.position),
+                _ => continue,
+            };
+
+            let score = |p: Point<usize>| {
+                let dx = p.0 as f64 - target.0 as f64;
+                let dy = p.1 as f64 - target.1 as f64;
+                let d = dx.hypot(dy);
+                if towards {
+                    -d
+                } else {
+                    d
+                }
+            };
+
+            plan.course = best_step(world, npc.ship.position, score)
+                .into_iter()
+                .collect();
+        }
+
+        plans
+    }
+
+    /// trade runs a merchant could make from the port it's in, or getting back to port if it isn't
+    fn merchant_plans(&mut self, i: usize, chart: &SeaChart, economy: &Economy) -> Vec<Plan> {
+        if let Some(to) = self.npcs[i].charter {
+            return self.charter_plans(i, chart, to);
+        }
+
+        let world = chart.world();
+        let hold = self.npcs[i].ship.hold;
+        let mut plans = Vec::new();
+
+        let Some(from) = self.npcs[i].ship.docked else {
+            // chased out to sea, head for the closest port we can reach
+            return nearest_port(chart, self.npcs[i].ship.position)
+                .map(|route| Plan::voyage(&route, CARRY_ON))
+                .into_iter()
+                .collect();
+        };
+        let Some(here) = economy.market(from) else {
+            return plans;
+        };
+
+        let position = world.history().ports[from].location;
+        let mut partners: Vec<_> = world
+            .history()
+            .living_ports()
+            .filter(|&(id, _)| id != from)
+            .map(|(id, port)| (distance(position, port.location), id))
+            .collect();
+        partners.sort_unstable();
+        partners.truncate(TRADE_PARTNERS);
+
+        for (_, to) in partners {
+            let (Some(route), Some(there)) = (self.port_route(chart, from, to), economy.market(to))
+            else {
+                continue;
+            };
+
+            for good in Good::ALL {
+                let amount = here.stock(good).min(hold);
+                let margin = i64::from(there.sell_price(good)) - i64::from(here.buy_price(good));
+                if amount == 0 || margin <= 0 {
+                    continue;
+                }
+
+                // profit per hour of the whole trip, counting the time spent in port at the end
+                let profit = margin as f64 * f64::from(amount);
+                let mut plan = Plan::voyage(&route, profit / (route.hours + REST_HOURS) / 10.);
+                plan.load = Some((good, amount));
+                plans.push(plan);
+            }
+        }
+
+        plans
+    }
+
+    /// the way to the port a chartered ship is bound for, unless she's there already
+    fn charter_plans(&mut self, i: usize, chart: &SeaChart, to: PortId) -> Vec<Plan> {
+        let (position, docked) = (self.npcs[i].ship.position, self.npcs[i].ship.docked);
+
+        let route = match docked {
+            Some(from) if from == to => None,
+            Some(from) => self.port_route(chart, from, to),
+            None => chart.route(position, chart.world().history().ports[to].location),
+        };
+
+        route
+            .map(|route| Plan::voyage(&route, CARRY_ON))
+            .into_iter()
+            .collect()
+    }
+
+    /// patrols from port to port of the navy's own civilization
+    fn navy_plans(&mut self, i: usize, chart: &SeaChart) -> Vec<Plan> {
+        let world = chart.world();
+        let npc = &self.npcs[i];
+        let position = npc.ship.position;
+        let docked = npc.ship.docked;
+
+        let mut ports: Vec<_> = match npc.civ {
+            Some(civ) => world.history().ports_of(civ).map(|(id, _)| id).collect(),
+            None => Vec::new(),
+        };
+        // a navy with no ports left to guard goes wherever it can
+        if ports.is_empty() {
+            ports = world.history().living_ports().map(|(id, _)| id).collect();
+        }
+        ports.retain(|&port| Some(port) != docked);
+
+        let Some(&to) = self.rng.random_slice_entry(&ports) else {
+            return Vec::new();
+        };
+
+        let route = match docked {
+            Some(from) => self.port_route(chart, from, to),
+            None => chart.route(position, world.history().ports[to].location),
+        };
+
+        route
+            .map(|route| Plan::voyage(&route, CARRY_ON))
+            .into_iter()
+            .collect()
+    }
+
+    /// somewhere near a port to lie in wait for merchants, or a port to sell off the loot
+    fn pirate_plans(&mut self, i: usize, chart: &SeaChart) -> Vec<Plan> {
+        let world = chart.world();
+        let ship = &self.npcs[i].ship;
+        let position = ship.position;
+
+        if ship.docked.is_none() && ship.cargo_total() >= ship.hold / 2 {
+            // no questions asked about where it came from, as long as the price is right
+            return nearest_port(chart, position)
+                .map(|route| Plan::voyage(&route, CARRY_ON))
+                .into_iter()
+                .collect();
+        }
+        let ports: Vec<_> = world
+            .history()
+            .living_ports()
+            .map(|(_, p)| p.location)
+            .collect();
+
+        let Some(&(px, py)) = self.rng.random_slice_entry(&ports) else {
+            return Vec::new();
+        };
+        let mut offset = || {
+            let by = self.rng.range(AMBUSH_RANGE.0, AMBUSH_RANGE.1);
+            if self.rng.range(0, 2) == 0 {
+                -by
+            } else {
+                by
+            }
+        };
+        let (dx, dy) = (offset(), offset());
+        let (Some(x), Some(y)) = (px.checked_add_signed(dx), py.checked_add_signed(dy)) else {
+            return Vec::new();
+        };
+        // pirates keep out of ports, they aren't welcome there
+        if !world.is_navigable(x, y) || world.port_at(x, y).is_some() {
+            return Vec::new();
+        }
+
+        chart
+            .route(position, (x, y))
+            .map(|route| Plan::voyage(&route, CARRY_ON))
+            .into_iter()
+            .collect()
+    }
+
+    /// ship `pirate` takes everything aboard ship `victim` that fits into her hold
+    fn rob(&mut self, pirate: usize, victim: usize) {
+        for good in Good::ALL {
+            let taken = self.npcs[victim]
+                .ship
+                .cargo_of(good)
+                .min(self.npcs[pirate].ship.free_hold());
+            self.npcs[pirate].ship.cargo[good.index()] += taken;
+            self.npcs[victim].ship.cargo[good.index()] -= taken;
+        }
+    }
+
+    /// ships that caught up with each other. pirates rob merchants, navies fight pirates and
+    /// their enemies' shipping. every fight goes to whichever ship is stronger
+    fn encounters(&mut self, world: &FullWorld, player: Point<usize>) -> Vec<String> {
+        let mut news = Vec::new();
+        let mut sunk = Vec::new();
+
+        for a in 0..self.npcs.len() {
+            for b in 0..self.npcs.len() {
+                let (hunter, prey) = (&self.npcs[a], &self.npcs[b]);
+                if a == b
+                    || hunter.ship.docked.is_some()
+                    || prey.ship.docked.is_some()
+                    || distance(hunter.ship.position, prey.ship.position) > 1
+                    || sunk.contains(&a)
+                    || sunk.contains(&b)
+                {
+                    continue;
+                }
+                let nearby = distance(prey.ship.position, player) <= NEWS_RANGE;
+                let history = world.history();
+                let flag = |civ: Option<CivId>| {
+                    civ.map_or("nobody", |civ| history.civilizations[civ].name.as_str())
+                };
+                let enemies = matches!((hunter.civ, prey.civ),
+                    (Some(a), Some(b)) if history.relation(a, b) == Relation::War);
+                let stronger = strength(&hunter.ship) >= strength(&prey.ship);
+
+                match (hunter.role, prey.role) {
+                    (Role::Pirate, Role::Merchant)
+                        if prey.ship.cargo_total() > 0
+                            && hunter.ship.free_hold() > 0
+                            && distance(prey.ship.position, player) > GUARD_RANGE =>
+                    {
+                        if !stronger {
+                            if nearby {
+                                news.push(format!(
+                                    "{} beat off the pirate {}",
+                                    prey.ship.name, hunter.ship.name
+                                ));
+                            }
+
+                            // licking their wounds, rather than trying again straight away
+                            self.disengage(hunter.id);
+                            continue;
+                        }
+
+                        if nearby {
+                            news.push(format!(
+                                "{} was plundered by {}",
+                                prey.ship.name, hunter.ship.name
+                            ));
+                        }
+
+                        self.rob(a, b);
+                    }
+                    // navies and pirates fight it out whoever came looking for whom, and the
+                    // navy has the better of it when they're evenly matched
+                    (Role::Navy, Role::Pirate) | (Role::Pirate, Role::Navy) => {
+                        let (navy, pirate) = if hunter.role == Role::Navy {
+                            (hunter, prey)
+                        } else {
+                            (prey, hunter)
+                        };
+                        let navy_won = strength(&navy.ship) >= strength(&pirate.ship);
+
+                        if nearby {
+                            news.push(if navy_won {
+                                format!(
+                                    "{} of {} sank the pirate {}",
+                                    navy.ship.name,
+                                    flag(navy.civ),
+                                    pirate.ship.name
+                                )
+                            } else {
+                                format!(
+                                    "the pirate {} sank {} of {}",
+                                    pirate.ship.name,
+                                    navy.ship.name,
+                                    flag(navy.civ)
+                                )
+                            });
+                        }
+
+                        let navy_is_hunter = hunter.role == Role::Navy;
+                        sunk.push(if navy_won == navy_is_hunter { b } else { a });
+                    }
+                    // warships take whatever enemy ships they can, merchants and other warships
+                    // alike. anything stronger gets away, or turns the tables when it's their go
+                    (Role::Navy, Role::Merchant | Role::Navy) if enemies && stronger => {
+                        if nearby {
+                            news.push(format!(
+                                "{} of {} took {} of {} as a prize of war",
+                                hunter.ship.name,
+                                flag(hunter.civ),
+                                prey.ship.name,
+                                flag(prey.civ)
+                            ));
+                        }
+
+                        if let (Some(a), Some(b)) = (hunter.civ, prey.civ) {
+                            self.dealings.push((a, b, PRIZE_OPINION));
+                        }
+                        sunk.push(b);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        sunk.sort_unstable();
+        for i in sunk.into_iter().rev() {
+            self.npcs.remove(i);
+        }
+
+        news
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::navigation::Waters;
+    use crate::helpers::{Cancel, RectDimension};
+    use crate::worldgen::{gen_full_world, GenParam};
+
+    use super::*;
+
+    fn world() -> FullWorld {
+        let params = GenParam {
+            seed: 1,
+            world_size: RectDimension::new(80, 60),
+            max_civilizations: 6,
+            history_years: 40,
+            ..GenParam::default()
+        };
+
+        gen_full_world(&params, None, &Cancel::new()).unwrap()
+    }
+
+    /// two tiles of open sea next to each other, away from any port
+    fn side_by_side(world: &FullWorld) -> (Point<usize>, Point<usize>) {
+        let open = |x: usize, y: usize| world.is_navigable(x, y) && world.port_at(x, y).is_none();
+        let dimensions = world.dimensions();
+        let (width, height) = (
+            usize::from(dimensions.width),
+            usize::from(dimensions.height),
+        );
+
+        (0..height)
+            .flat_map(|y| (0..width - 1).map(move |x| (x, y)))
+            .find(|&(x, y)| open(x, y) && open(x + 1, y))
+            .map(|(x, y)| ((x, y), (x + 1, y)))
+            .unwrap()
+    }
+
+    /// a fleet of nothing but a ship of each of `roles`, at sea side by side
+    fn meeting(world: &FullWorld, roles: [(Role, Option<CivId>); 2]) -> (Fleet, [NpcId; 2]) {
+        let mut fleet = Fleet::new(world, RandomNumberGenerator::seeded(1));
+        fleet.npcs.clear();
+        let (a, b) = side_by_side(world);
+
+        let ids = [(roles[0], a), (roles[1], b)].map(|((role, civ), at)| {
+            let id = fleet.spawn(world, role, civ, 0);
+            let npc = fleet.get_mut(id).unwrap();
+            npc.ship.position = at;
+            npc.ship.docked = None;
+            id
+        });
+
+        (fleet, ids)
+    }
+
+    #[test]
+    fn stronger_ships_win_fights() {
+        let world = world();
+        let player = side_by_side(&world).0;
+
+        // navies usually have the guns to deal with pirates
+        let (mut fleet, [navy, pirate]) =
+            meeting(&world, [(Role::Navy, Some(0)), (Role::Pirate, None)]);
+        let news = fleet.encounters(&world, player);
+        assert!(fleet.get(navy).is_some() && fleet.get(pirate).is_none());
+        assert!(news[0].contains("sank the pirate"), "{news:?}");
+
+        // but not when they've hardly anyone left to work them, whoever came looking for whom
+        for roles in [
+            [(Role::Navy, Some(0)), (Role::Pirate, None)],
+            [(Role::Pirate, None), (Role::Navy, Some(0))],
+        ] {
+            let (mut fleet, ids) = meeting(&world, roles);
+            let navy = ids[usize::from(roles[0].0 != Role::Navy)];
+            let pirate = ids[usize::from(roles[0].0 == Role::Navy)];
+            fleet.get_mut(navy).unwrap().ship.crew = 1;
+
+            fleet.encounters(&world, player);
+            assert!(fleet.get(navy).is_none() && fleet.get(pirate).is_some());
+        }
+    }
+
+    #[test]
+    fn pirates_only_rob_merchants_they_can_beat() {
+        let world = world();
+        // the merchant is on the right. close enough to hear about it, too far to scare the pirate
+        // off
+        let (_, at) = side_by_side(&world);
+        let player = (at.0 + GUARD_RANGE + 1, at.1);
+
+        let (mut fleet, [pirate, merchant]) =
+            meeting(&world, [(Role::Pirate, None), (Role::Merchant, Some(0))]);
+        fleet.get_mut(merchant).unwrap().ship.cargo[Good::Rum.index()] = 20;
+        let news = fleet.encounters(&world, player);
+        assert_eq!(fleet.get(pirate).unwrap().ship.cargo_of(Good::Rum), 20);
+        assert_eq!(fleet.get(merchant).unwrap().ship.cargo_total(), 0);
+        assert!(news[0].contains("plundered"), "{news:?}");
+
+        let (mut fleet, [pirate, merchant]) =
+            meeting(&world, [(Role::Pirate, None), (Role::Merchant, Some(0))]);
+        let guarded = fleet.get_mut(merchant).unwrap();
+        guarded.ship.cargo[Good::Rum.index()] = 20;
+        guarded.ship.guns *= 10;
+        guarded.ship.crew *= 10;
+        let news = fleet.encounters(&world, player);
+        assert_eq!(fleet.get(pirate).unwrap().ship.cargo_total(), 0);
+        assert_eq!(fleet.get(merchant).unwrap().ship.cargo_of(Good::Rum), 20);
+        assert!(matches!(fleet.get(pirate).unwrap().goal, Goal::Rest(_)));
+        assert!(news[0].contains("beat off"), "{news:?}");
+    }
+
+    #[test]
+    fn sunk_ships_are_replaced() {
+        let world = world();
+        let mut fleet = Fleet::new(&world, RandomNumberGenerator::seeded(2));
+        let count =
+            |fleet: &Fleet, role: Role| fleet.npcs.iter().filter(|n| n.role == role).count();
+        let counts = [Role::Merchant, Role::Navy, Role::Pirate].map(|role| count(&fleet, role));
+        assert!(counts.iter().all(|&n| n > 0), "{counts:?}");
+
+        // a bad day for everyone, leaving one of each
+        for role in [Role::Merchant, Role::Navy, Role::Pirate] {
+            let ids: Vec<_> = fleet
+                .npcs
+                .iter()
+                .filter(|n| n.role == role)
+                .skip(1)
+                .map(|n| n.id)
+                .collect();
+            for id in ids {
+                fleet.remove(id);
+            }
+        }
+        fleet.replenish(&world);
+
+        assert_eq!(
+            [Role::Merchant, Role::Navy, Role::Pirate].map(|role| count(&fleet, role)),
+            counts
+        );
+    }
+
+    #[test]
+    fn port_routes_are_worked_out_once() {
+        let world = world();
+        let waters = Waters::new(&world);
+        let chart = SeaChart::new(&world, &waters);
+        let mut fleet = Fleet::new(&world, RandomNumberGenerator::seeded(3));
+
+        let ports: Vec<_> = world.history().living_ports().map(|(id, _)| id).collect();
+        let (from, to) = ports
+            .iter()
+            .flat_map(|&from| ports.iter().map(move |&to| (from, to)))
+            .find(|&(from, to)| from != to && chart.route_between(from, to).is_some())
+            .unwrap();
+
+        let route = fleet.port_route(&chart, from, to).unwrap();
+        assert!(fleet.routes.contains_key(&(from, to)));
+        assert!(!fleet.routes.contains_key(&(to, from)));
+
+        // the second time it comes straight out of the cache, even if the cache is wrong
+        fleet.routes.insert(
+            (from, to),
+            Some(Route {
+                hours: -1.,
+                ..route.clone()
+            }),
+        );
+        assert!(fleet.port_route(&chart, from, to).unwrap().hours < 0.);
+        assert_eq!(fleet.routes.len(), 1);
+    }
+
+    #[test]
+    fn ships_go_back_to_their_voyage_after_a_chase() {
+        let world = world();
+        let waters = Waters::new(&world);
+        let chart = SeaChart::new(&world, &waters);
+        let mut economy = Economy::new(&world);
+        let reputation = Reputation::new(&world);
+        // tucked away in port, out of everyone's way
+        let player = Ship::new("Player".to_string(), (0, 0), Some(0));
+
+        let (mut fleet, [navy, pirate]) =
+            meeting(&world, [(Role::Navy, Some(0)), (Role::Pirate, None)]);
+        let i = fleet.npcs.iter().position(|npc| npc.id == navy).unwrap();
+        let from = fleet.npcs[i].ship.position;
+        let dimensions = world.dimensions();
+        let (to, route) = (0..usize::from(dimensions.height))
+            .flat_map(|y| (0..usize::from(dimensions.width)).map(move |x| (x, y)))
+            .filter(|&to| distance(from, to) > SIGHT)
+            .find_map(|to| Some((to, chart.route(from, to)?)))
+            .unwrap();
+        fleet.npcs[i].goal = Goal::Voyage;
+        fleet.npcs[i].course = route.steps.iter().rev().copied().collect();
+
+        fleet.decide(i, &chart, &mut economy, (&player, &reputation));
+        assert_eq!(fleet.npcs[i].goal, Goal::Chase(pirate));
+        assert_eq!(fleet.npcs[i].bound_for, Some(to));
+
+        // the pirate gets clean away
+        fleet.remove(pirate);
+        let i = fleet.npcs.iter().position(|npc| npc.id == navy).unwrap();
+        fleet.decide(i, &chart, &mut economy, (&player, &reputation));
+        assert_eq!(fleet.npcs[i].goal, Goal::Voyage);
+        assert_eq!(fleet.npcs[i].course.first(), Some(&to));
+        assert_eq!(fleet.npcs[i].bound_for, None);
+    }
+}
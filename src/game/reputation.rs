@@ -0,0 +1,233 @@
+//! what the civilizations of the world make of the player
+//!
+//! how the civilizations feel about each other lives in the [`History`], which carries on where
+//! worldgen left off. how they feel about the player lives here. both sides get pushed around by
+//! the same sorts of things: ships being attacked, trade, gold changing hands under the table and
+//! wars being declared
+
+use serde::{Deserialize, Serialize};
+
+use crate::worldgen::history::{CivId, History, Relation};
+use crate::worldgen::FullWorld;
+
+/// standing with a civilization runs from -`MAX_STANDING` to `MAX_STANDING`
+const MAX_STANDING: i32 = 100;
+/// at or below this standing a civilization treats the player as an enemy
+const HOSTILE: i32 = -50;
+/// what firing on a ship does to the player's standing with whoever she sails for
+const FIRED_ON_STANDING: i32 = -10;
+/// what sinking or taking a ship does to the player's standing with whoever she sailed for
+const TAKEN_STANDING: i32 = -25;
+/// what ridding the seas of a pirate does to the player's standing with everyone
+const PIRATE_STANDING: i32 = 5;
+/// gold that has to change hands in a civilization's ports for them to think a bit better of the
+/// player
+const TRADE_PER_STANDING: u32 = 200;
+/// trading alone never gets anyone liked more than this
+const TRADE_CEILING: i32 = 30;
+/// what a bribe costs
+pub const BRIBE: u32 = 100;
+/// how much a bribe smooths things over
+const BRIBE_STANDING: i32 = 10;
+/// bribes only get the player back to being left alone, nobody is bought into liking them
+const BRIBE_CEILING: i32 = 0;
+/// what letters of marque cost
+pub const MARQUE: u32 = 300;
+/// civilizations only hand out letters of marque to people they like at least this much
+const MARQUE_STANDING: i32 = 10;
+
+/// the player's standing with every civilization
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reputation {
+    /// indexed by [`CivId`]. everyone starts out not knowing the player at all
+    standing: Vec<i32>,
+    /// the civilizations the player holds letters of marque from, indexed by [`CivId`]
+    marques: Vec<bool>,
+    /// gold traded in each civilization's ports that hasn't counted towards standing yet
+    trade: Vec<u32>,
+}
+
+impl Reputation {
+    pub fn new(world: &FullWorld) -> Self {
+        let civs = world.history().civilizations.len();
+
+        Reputation {
+            standing: vec![0; civs],
+            marques: vec![false; civs],
+            trade: vec![0; civs],
+        }
+    }
+
+    pub fn standing(&self, civ: CivId) -> i32 {
+        self.standing.get(civ).copied().unwrap_or_default()
+    }
+
+    pub fn change(&mut self, civ: CivId, by: i32) {
+        if let Some(standing) = self.standing.get_mut(civ) {
+            *standing = (*standing + by).clamp(-MAX_STANDING, MAX_STANDING);
+        }
+
+        // nobody keeps a privateer on the books once they've fallen out
+        if self.standing(civ) < 0 {
+            self.revoke(civ);
+        }
+    }
+
+    /// change the player's standing with everyone at once, for things the whole world hears about
+    pub fn change_all(&mut self, by: i32) {
+        for civ in 0..self.standing.len() {
+            self.change(civ, by);
+        }
+    }
+
+    /// where the player stands with `civ`, in the same terms civilizations use with each other.
+    /// holding letters of marque counts as an alliance
+    pub fn relation(&self, civ: CivId) -> Relation {
+        if self.standing(civ) <= HOSTILE {
+            Relation::War
+        } else if self.has_marque(civ) {
+            Relation::Alliance
+        } else {
+            Relation::Peace
+        }
+    }
+
+    /// whether `civ`'s ports will let the player in
+    pub fn welcome(&self, civ: CivId) -> bool {
+        self.relation(civ) != Relation::War
+    }
+
+    pub fn has_marque(&self, civ: CivId) -> bool {
+        self.marques.get(civ).copied().unwrap_or_default()
+    }
+
+    fn revoke(&mut self, civ: CivId) {
+        if let Some(marque) = self.marques.get_mut(civ) {
+            *marque = false;
+        }
+    }
+
+    /// buy letters of marque from `civ` out of `gold`, licensing the player to prey on its
+    /// enemies. returns what came of asking
+    pub fn buy_marque(&mut self, history: &History, civ: CivId, gold: &mut u32) -> String {
+        let name = &history.civilizations[civ].name;
+
+        if self.has_marque(civ) {
+            format!("you already sail under letters of marque from {name}")
+        } else if !history.at_war(civ) {
+            format!("{name} is at peace with everyone, and has no use for privateers")
+        } else if self.standing(civ) < MARQUE_STANDING {
+            format!("{name} doesn't trust you enough to give you letters of marque")
+        } else if *gold < MARQUE {
+            format!("letters of marque from {name} cost {MARQUE} gold")
+        } else {
+            *gold -= MARQUE;
+            if let Some(marque) = self.marques.get_mut(civ) {
+                *marque = true;
+            }
+            format!("you bought letters of marque from {name}. its enemies are fair game")
+        }
+    }
+
+    /// the civilizations that have a marque out on `civ`'s ships, because they're at war with it
+    fn sanctioned_by(&self, history: &History, civ: CivId) -> Vec<CivId> {
+        (0..self.marques.len())
+            .filter(|&issuer| {
+                self.marques[issuer] && history.relation(issuer, civ) == Relation::War
+            })
+            .collect()
+    }
+
+    /// the player fired on a ship sailing for `civ`, or a pirate if there's no `civ`, and `taken`
+    /// if they sank or took her too. her friends take it badly and her enemies don't mind a bit
+    pub fn attacked(&mut self, history: &History, civ: Option<CivId>, taken: bool) {
+        let Some(victim) = civ else {
+            if taken {
+                self.change_all(PIRATE_STANDING);
+            }
+            return;
+        };
+        let by = if taken {
+            TAKEN_STANDING
+        } else {
+            FIRED_ON_STANDING
+        };
+
+        // a privateer doing what they were paid for gets thanked for it
+        let sanctioned = self.sanctioned_by(history, victim);
+        for civ in 0..self.standing.len() {
+            if civ == victim || !history.is_alive(civ) {
+                continue;
+            }
+
+            match history.relation(victim, civ) {
+                Relation::Alliance => self.change(civ, by / 2),
+                Relation::War if sanctioned.contains(&civ) => self.change(civ, -by / 2),
+                Relation::War => self.change(civ, -by / 5),
+                Relation::Peace => {}
+            }
+        }
+
+        // licensed or not, the victim never sees it that way
+        self.change(victim, by);
+        self.revoke(victim);
+    }
+
+    /// `gold` changed hands between the player and a port of `civ`
+    pub fn traded(&mut self, civ: CivId, gold: u32) {
+        let Some(trade) = self.trade.get_mut(civ) else {
+            return;
+        };
+        *trade += gold;
+        let earned = *trade / TRADE_PER_STANDING;
+        *trade %= TRADE_PER_STANDING;
+
+        let standing = self.standing(civ);
+        if standing < TRADE_CEILING {
+            let earned = i32::try_from(earned).unwrap_or(i32::MAX);
+            self.change(civ, earned.min(TRADE_CEILING - standing));
+        }
+    }
+
+    /// grease a few palms in `civ` out of `gold`. returns what came of it
+    pub fn bribe(&mut self, history: &History, civ: CivId, gold: &mut u32) -> String {
+        let name = &history.civilizations[civ].name;
+
+        if self.standing(civ) >= BRIBE_CEILING {
+            format!("nobody in {name} needs bribing")
+        } else if *gold < BRIBE {
+            format!("it would take {BRIBE} gold to buy any goodwill in {name}")
+        } else {
+            *gold -= BRIBE;
+            let by = BRIBE_STANDING.min(BRIBE_CEILING - self.standing(civ));
+            self.change(civ, by);
+            format!("{BRIBE} gold quietly changed hands, and {name} looks the other way")
+        }
+    }
+
+    /// something changed between the civilizations. letters of marque from anyone who is no
+    /// longer at war with anybody are worthless. returns who withdrew them
+    pub fn review_marques(&mut self, history: &History) -> Vec<CivId> {
+        let mut withdrawn = Vec::new();
+
+        for civ in 0..self.marques.len() {
+            if self.marques[civ] && !history.at_war(civ) {
+                self.marques[civ] = false;
+                withdrawn.push(civ);
+            }
+        }
+
+        withdrawn
+    }
+
+    /// a word for how `civ` thinks of the player
+    pub fn describe(&self, civ: CivId) -> &'static str {
+        match self.standing(civ) {
+            ..=HOSTILE => "hated",
+            -49..=-10 => "distrusted",
+            -9..=9 => "unknown",
+            10..=49 => "liked",
+            50.. => "honoured",
+        }
+    }
+}
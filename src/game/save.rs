@@ -0,0 +1,233 @@
+//! writing games out to files and reading them back in
+//!
+//! saves are JSON, with the whole world and everything going on in it, so nothing has to be
+//! generated again to load one and everyone is just where they were. every save says which version
+//! of the format it's in, and only saves in this build's format load
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::RectDimension;
+
+use super::navigation::Waters;
+use super::Game;
+
+/// where F5 saves the game to
+pub const SAVE_PATH: &str = "pirate_sim_save.json";
+/// where the game saves itself every so often
+pub const AUTOSAVE_PATH: &str = "pirate_sim_autosave.json";
+/// how many save slots there are to pick from in the menu
+pub const SLOTS: usize = 3;
+/// which version of the save format this build writes. anything else won't load
+const SAVE_VERSION: u32 = 6;
+
+/// where save slot `slot` is kept, counting from 1
+pub fn slot_path(slot: usize) -> PathBuf {
+    PathBuf::from(format!("pirate_sim_save_{slot}.json"))
+}
+
+/// what goes into a save file, borrowed from a game being played
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    /// when in the game it was saved, so slots can be told apart without loading them
+    date: String,
+    ship: &'a str,
+    game: &'a Game,
+}
+
+/// the start of a save file, enough to tell what's in it
+#[derive(Deserialize)]
+struct Header {
+    version: Option<u32>,
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    ship: String,
+}
+
+/// what comes back out of a save file
+#[derive(Deserialize)]
+struct LoadedFile {
+    game: Game,
+}
+
+pub fn save(game: &Game, path: &Path) -> io::Result<()> {
+    let file = SaveFile {
+        version: SAVE_VERSION,
+        date: game.clock.date().to_string(),
+        ship: &game.player.name,
+        game,
+    };
+
+    fs::write(path, serde_json::to_string(&file)?)?;
+    info!("saved game to {}", path.display());
+
+    Ok(())
+}
+
+/// what's saved at `path`, like "year 251, spring day 3, 14:00 - the Salty Gull". None if there's
+/// nothing there
+pub fn describe(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+
+    Some(match serde_json::from_str::<Header>(&text) {
+        Ok(Header {
+            version: Some(SAVE_VERSION),
+            date,
+            ship,
+            ..
+        }) => format!("{date} - {ship}"),
+        Ok(Header {
+            version: Some(version),
+            ..
+        }) => format!("made by another version of the game (format {version})"),
+        Ok(Header { version: None, .. }) => "made by a much older version of the game".into(),
+        Err(_) => "unreadable".into(),
+    })
+}
+
+/// pick up a saved game where it left off
+pub fn load(path: &Path, screen: RectDimension) -> io::Result<Game> {
+    let text = fs::read_to_string(path)?;
+    let header: Header = serde_json::from_str(&text)?;
+
+    match header.version {
+        Some(SAVE_VERSION) => {
+            let mut game = serde_json::from_str::<LoadedFile>(&text)?.game;
+            // nothing that can be worked out again gets saved
+            game.waters = Waters::new(&game.world);
+            game.screen = screen;
+            game.clock.paused = true;
+            game.look();
+
+            Ok(game)
+        }
+        Some(version) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} was saved in format {version}, but this version of the game reads format {SAVE_VERSION}",
+                path.display()
+            ),
+        )),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is from before saves had a format version, and can't be read any more",
+                path.display()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::Cancel;
+    use crate::worldgen::{gen_full_world, GenParam};
+
+    use super::*;
+
+    const SCREEN: RectDimension = RectDimension::new(105, 105);
+
+    fn game() -> Game {
+        let params = GenParam {
+            seed: 11,
+            world_size: RectDimension::new(60, 40),
+            history_years: 20,
+            ..GenParam::default()
+        };
+
+        Game::new(
+            gen_full_world(&params, None, &Cancel::new()).unwrap(),
+            SCREEN,
+        )
+    }
+
+    /// somewhere to save to that no other test is using
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pirate_sim_test_{}_{name}.json",
+            std::process::id()
+        ))
+    }
+
+    /// save `game` to `name`, change the save with `edit`, and try loading it back
+    fn load_edited(
+        game: &Game,
+        name: &str,
+        edit: impl FnOnce(&mut serde_json::Value),
+    ) -> io::Result<Game> {
+        let path = temp_path(name);
+        save(game, &path).unwrap();
+
+        let mut file: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        edit(&mut file);
+        fs::write(&path, file.to_string())?;
+
+        let loaded = load(&path, SCREEN);
+        fs::remove_file(&path).ok();
+        loaded
+    }
+
+    #[test]
+    fn games_round_trip() {
+        let game = game();
+        let path = temp_path("round_trip");
+        save(&game, &path).unwrap();
+
+        let loaded = load(&path, SCREEN).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&game).unwrap()
+        );
+        assert_eq!(
+            loaded.waters.at(loaded.player.position),
+            game.waters.at(game.player.position)
+        );
+    }
+
+    #[test]
+    fn loaded_games_roll_the_same_dice() {
+        let mut game = game();
+        let path = temp_path("dice");
+        save(&game, &path).unwrap();
+
+        let mut loaded = load(&path, SCREEN).unwrap();
+        fs::remove_file(&path).ok();
+
+        for _ in 0..10 {
+            assert_eq!(loaded.rng.next_u64(), game.rng.next_u64());
+        }
+    }
+
+    #[test]
+    fn other_versions_dont_load() {
+        let game = game();
+
+        let error = load_edited(&game, "old_version", |file| {
+            file["version"] = (SAVE_VERSION - 1).into();
+        })
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains(&format!(
+            "was saved in format {}, but this version of the game reads format {SAVE_VERSION}",
+            SAVE_VERSION - 1
+        )));
+
+        let error = load_edited(&game, "no_version", |file| {
+            file.as_object_mut().unwrap().remove("version");
+        })
+        .err()
+        .unwrap();
+        assert!(error
+            .to_string()
+            .contains("from before saves had a format version"));
+    }
+}
@@ -0,0 +1,156 @@
+//! ships and how they get around
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{points_around, Point};
+use crate::worldgen::climate::Vector;
+use crate::worldgen::history::PortId;
+use crate::worldgen::FullWorld;
+
+use super::economy::{Good, GOOD_COUNT};
+
+/// how many tiles an average ship makes per hour in calm water with no wind
+const BASE_SPEED: f64 = 1.;
+/// ships never go slower than this, even sailing straight into the wind
+const MIN_SPEED: f64 = 0.2;
+/// how much a tailwind (or headwind) changes the speed of a ship
+const WIND_EFFECT: f64 = 0.6;
+/// how much a current changes the speed of a ship
+const CURRENT_EFFECT: f64 = 0.5;
+/// water shallower than this fraction of the deepest point has to be sailed carefully
+const SHALLOW_WATER: f64 = 0.1;
+/// how much slower ships go next to pack ice, picking their way between the floes
+const NEAR_ICE: f64 = 0.6;
+/// how many units of goods a ship can carry
+const HOLD_SIZE: u32 = 100;
+/// how many hands an ordinary ship sails with
+pub const CREW: u32 = 20;
+/// how many guns an ordinary ship carries, half on either side
+const GUNS: u32 = 8;
+/// how much battering a hull in good repair can take before the ship goes down
+pub const MAX_HULL: u32 = 100;
+/// the state of the rigging when all of it is in good repair
+pub const MAX_SAILS: u32 = 100;
+/// how many tiles the lookouts can see on a clear day, with all the rigging up to climb
+const SIGHT: f64 = 8.;
+/// the fastest any ship can go, with the strongest wind and current right behind it
+pub const MAX_SPEED: f64 = BASE_SPEED + WIND_EFFECT + CURRENT_EFFECT;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ship {
+    pub name: String,
+    pub position: Point<usize>,
+    /// the port the ship is currently tied up in
+    pub docked: Option<PortId>,
+    /// how many units of each good are aboard, indexed by [`Good::index`]
+    pub cargo: [u32; GOOD_COUNT],
+    /// how many units of goods fit aboard
+    pub hold: u32,
+    /// what's left of the hull, out of [`MAX_HULL`]. at 0 the ship sinks
+    pub hull: u32,
+    /// what's left of the rigging, out of [`MAX_SAILS`]
+    pub sails: u32,
+    pub crew: u32,
+    pub guns: u32,
+}
+
+impl Ship {
+    pub fn new(name: String, position: Point<usize>, docked: Option<PortId>) -> Self {
+        Ship {
+            name,
+            position,
+            docked,
+            cargo: [0; GOOD_COUNT],
+            hold: HOLD_SIZE,
+            hull: MAX_HULL,
+            sails: MAX_SAILS,
+            crew: CREW,
+            guns: GUNS,
+        }
+    }
+
+    pub fn cargo_of(&self, good: Good) -> u32 {
+        self.cargo[good.index()]
+    }
+
+    /// how many units of goods are aboard, all told
+    pub fn cargo_total(&self) -> u32 {
+        self.cargo.iter().sum()
+    }
+
+    /// how many more units of goods fit aboard
+    pub fn free_hold(&self) -> u32 {
+        self.hold.saturating_sub(self.cargo_total())
+    }
+
+    /// how much of her usual speed the ship makes with the rigging she has left
+    pub fn trim(&self) -> f64 {
+        0.4 + 0.6 * f64::from(self.sails) / f64::from(MAX_SAILS)
+    }
+
+    /// how many tiles the lookouts can see on a clear day. with the masts shot away there's less
+    /// to climb
+    pub fn sight(&self) -> f64 {
+        SIGHT * (0.5 + 0.5 * f64::from(self.sails) / f64::from(MAX_SAILS))
+    }
+
+    /// patch up `amount` worth of damage to both the hull and the rigging
+    pub fn repair(&mut self, amount: u32) {
+        self.hull = (self.hull + amount).min(MAX_HULL);
+        self.sails = (self.sails + amount).min(MAX_SAILS);
+    }
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+/// how many hours it takes to sail from `from` to the neighbouring tile `to`
+///
+/// returns None if `to` can't be sailed into at all, because it is land, ice or off the map
+pub fn sailing_hours(world: &FullWorld, from: Point<usize>, to: Point<usize>) -> Option<f64> {
+    if !world.is_navigable(to.0, to.1) {
+        return None;
+    }
+
+    let heading = (to.0 as f64 - from.0 as f64, to.1 as f64 - from.1 as f64);
+    let distance = heading.0.hypot(heading.1);
+    if distance == 0. {
+        return Some(0.);
+    }
+    let heading = (heading.0 / distance, heading.1 / distance);
+
+    let mut speed = BASE_SPEED
+        + dot(heading, world.wind_at(from.0, from.1)) * WIND_EFFECT
+        + dot(heading, world.current_at(from.0, from.1)) * CURRENT_EFFECT;
+
+    // ports are on land, so they don't have a depth worth worrying about
+    let terrain = world.terrain();
+    if terrain.is_sea(to.0, to.1) && terrain.depth(to.0, to.1) < terrain.max_depth() * SHALLOW_WATER
+    {
+        speed *= 0.5;
+    }
+
+    let near_ice = points_around(to.0 as isize, to.1 as isize)
+        .into_iter()
+        .filter_map(|(x, y)| Some((usize::try_from(x).ok()?, usize::try_from(y).ok()?)))
+        .any(|(x, y)| world.in_bounds(x, y) && world.is_frozen(x, y));
+    if near_ice {
+        speed *= NEAR_ICE;
+    }
+
+    Some(distance / speed.max(MIN_SPEED))
+}
+
+/// the name of the compass direction `v` is pointing in. y goes down the screen, so down is south
+#[allow(clippy::cast_sign_loss)]
+pub fn compass(v: Vector) -> &'static str {
+    const DIRECTIONS: [&str; 8] = ["E", "SE", "S", "SW", "W", "NW", "N", "NE"];
+
+    if v.0 == 0. && v.1 == 0. {
+        return "-";
+    }
+
+    let octant = (v.1.atan2(v.0) / std::f64::consts::FRAC_PI_4).round();
+    DIRECTIONS[octant.rem_euclid(8.) as usize]
+}
@@ -0,0 +1,193 @@
+//! the trading screen, for buying and selling goods in port
+
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, NAVY, WHITE, YELLOW};
+use log::error;
+
+use crate::render::{Frame, RenderPacket, RenderTick};
+use crate::worldgen::history::PortId;
+
+use super::economy::Good;
+use super::Game;
+
+/// rows taken up by the header above the list of goods
+const HEADER_ROWS: usize = 4;
+
+/// the list of goods with what they go for, as a frame covering the whole screen
+fn frame(game: &Game, port: PortId, selected: usize, message: &str) -> Frame {
+    let history = game.world.history();
+    let mut frame = Frame::blank(game.screen);
+
+    frame.print(
+        0,
+        0,
+        &format!(
+            "Trading at {} of {} - {} gold, hold {}/{}",
+            history.ports[port].name,
+            history.civilizations[history.ports[port].owner].name,
+            game.gold,
+            game.player.cargo_total(),
+            game.player.hold
+        ),
+        YELLOW,
+        BLACK,
+    );
+    frame.print(
+        0,
+        1,
+        "[up/down] pick  [b/right] buy  [s/left] sell  [tab] one/ten at a time  [esc] back",
+        GRAY,
+        BLACK,
+    );
+    frame.print(0, 2, message, WHITE, BLACK);
+
+    let Some(market) = game.economy.market(port) else {
+        return frame;
+    };
+
+    frame.print(
+        0,
+        HEADER_ROWS - 1,
+        &format!(
+            "  {:<12}{:>8}{:>8}{:>8}{:>10}",
+            "good", "stock", "buy", "sell", "aboard"
+        ),
+        GRAY,
+        BLACK,
+    );
+    for (row, good) in Good::ALL.into_iter().enumerate() {
+        let bg = if row == selected { NAVY } else { BLACK };
+        let line = format!(
+            "  {:<12}{:>8}{:>8}{:>8}{:>10}",
+            good.to_string(),
+            market.stock(good),
+            market.buy_price(good),
+            market.sell_price(good),
+            game.player.cargo_of(good)
+        );
+
+        frame.print(0, HEADER_ROWS + row, &line, WHITE, bg);
+    }
+
+    frame
+}
+
+/// buy up to `amount` of `good`, as much as the market, the hold and the player's purse allow
+fn buy(game: &mut Game, port: PortId, good: Good, amount: u32) -> String {
+    let Some(market) = game.economy.market_mut(port) else {
+        return "there's no market here".into();
+    };
+
+    let mut bought = 0;
+    let mut paid = 0;
+    while bought < amount && game.player.free_hold() > 0 {
+        let price = market.buy_price(good);
+        if game.gold < price || !market.remove(good, 1) {
+            break;
+        }
+
+        game.gold -= price;
+        game.player.cargo[good.index()] += 1;
+        bought += 1;
+        paid += price;
+    }
+
+    let owner = game.world.history().ports[port].owner;
+    game.reputation.traded(owner, paid);
+
+    match bought {
+        0 if game.player.free_hold() == 0 => "the hold is full".into(),
+        0 if market.stock(good) == 0 => format!("there's no {good} for sale"),
+        0 => format!("you can't afford any {good}"),
+        _ => format!("bought {bought} {good} for {paid} gold"),
+    }
+}
+
+/// sell up to `amount` of `good` from the hold
+fn sell(game: &mut Game, port: PortId, good: Good, amount: u32) -> String {
+    let Some(market) = game.economy.market_mut(port) else {
+        return "there's no market here".into();
+    };
+
+    let mut sold = 0;
+    let mut earned = 0;
+    while sold < amount && game.player.cargo_of(good) > 0 {
+        // prices drop as the market fills up, so sell one at a time
+        let price = market.sell_price(good);
+        market.add(good, 1);
+
+        game.gold += price;
+        game.player.cargo[good.index()] -= 1;
+        sold += 1;
+        earned += price;
+    }
+
+    let owner = game.world.history().ports[port].owner;
+    game.reputation.traded(owner, earned);
+
+    if sold == 0 {
+        format!("you have no {good} to sell")
+    } else {
+        format!("sold {sold} {good} for {earned} gold")
+    }
+}
+
+/// show the trading screen for `port` until the player backs out of it
+///
+/// returns false if the window was closed while trading
+pub fn trade(
+    game: &mut Game,
+    port: PortId,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send trading screen to render thread. Error: {e}");
+        }
+    };
+
+    let mut selected = 0;
+    let mut message = String::new();
+    // whether to buy and sell ten at a time instead of one
+    let mut by_ten = false;
+
+    send(frame(game, port, selected, &message));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        let amount = if by_ten { 10 } else { 1 };
+        let good = Good::ALL[selected];
+        match key {
+            VirtualKeyCode::Escape => return true,
+            VirtualKeyCode::Tab => {
+                by_ten = !by_ten;
+                message = if by_ten {
+                    "trading ten at a time".into()
+                } else {
+                    "trading one at a time".into()
+                };
+            }
+            VirtualKeyCode::Up => selected = selected.saturating_sub(1),
+            VirtualKeyCode::Down => selected = (selected + 1).min(Good::ALL.len() - 1),
+            VirtualKeyCode::B | VirtualKeyCode::Right => {
+                message = buy(game, port, good, amount);
+            }
+            VirtualKeyCode::S | VirtualKeyCode::Left => {
+                message = sell(game, port, good, amount);
+            }
+            _ => continue,
+        }
+
+        send(frame(game, port, selected, &message));
+    }
+
+    // the render thread hung up on us
+    false
+}
@@ -0,0 +1,226 @@
+//! treasure maps, and digging up what they lead to
+//!
+//! maps can be bought from shady characters in port, or taken off pirates. they don't say where
+//! they are, so it's up to the player to recognise the coastline
+
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, WHITE, YELLOW};
+use log::error;
+
+use crate::render::{Frame, RenderPacket, RenderTick};
+use crate::worldgen::treasure::{Treasure, TreasureId};
+use crate::worldgen::FullWorld;
+
+use super::{clock, compass, Game};
+
+/// what a treasure map costs in port
+const MAP_PRICE: u32 = 150;
+/// how far from the ship a shore party will go looking for somewhere to dig
+const DIG_RANGE: usize = 3;
+/// how long digging takes, whether anything turns up or not
+const DIG_HOURS: u64 = 6;
+
+/// how many steps it takes to get from `a` to `b`, diagonals included
+fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// a hint at where a map is, for anyone who can't place the coastline
+fn clue(world: &FullWorld, treasure: &Treasure) -> String {
+    let nearest = world
+        .history()
+        .living_ports()
+        .min_by_key(|(_, port)| distance(port.location, treasure.location));
+
+    match nearest {
+        Some((_, port)) => {
+            let offset = (
+                treasure.location.0 as f64 - port.location.0 as f64,
+                treasure.location.1 as f64 - port.location.1 as f64,
+            );
+            format!(
+                "scrawled in the corner: \"{} leagues {} of {}\"",
+                distance(treasure.location, port.location),
+                compass(offset),
+                port.name
+            )
+        }
+        None => "there's nothing written on it".to_string(),
+    }
+}
+
+/// the treasures nobody has dug up and the player has no map to yet
+fn unknown_treasures(game: &Game) -> Vec<TreasureId> {
+    (0..game.world.treasures().len())
+        .filter(|id| !game.world.treasures()[*id].found && !game.maps.contains(id))
+        .collect()
+}
+
+/// buy a map from someone in the port the player is docked at
+pub fn buy_map(game: &mut Game) {
+    if game.player.docked.is_none() {
+        game.message = "you need to be in port to find anyone selling maps".into();
+        return;
+    }
+    if game.gold < MAP_PRICE {
+        game.message = format!("treasure maps go for {MAP_PRICE} gold");
+        return;
+    }
+
+    let left = unknown_treasures(game);
+    let Some(&id) = game.rng.random_slice_entry(&left) else {
+        game.message = "nobody here has any maps to sell".into();
+        return;
+    };
+
+    game.gold -= MAP_PRICE;
+    game.maps.push(id);
+    game.message = format!(
+        "a one-eyed old sailor sold you a map to the hoard of {} for {MAP_PRICE} gold",
+        game.world.treasures()[id].owner
+    );
+}
+
+/// go through the pockets of a pirate crew, who might have a map on them. returns whose hoard it
+/// leads to
+pub fn take_map(game: &mut Game, rng: &mut RandomNumberGenerator) -> Option<String> {
+    let id = *rng.random_slice_entry(&unknown_treasures(game))?;
+    game.maps.push(id);
+
+    Some(game.world.treasures()[id].owner.clone())
+}
+
+/// send a party ashore to dig around wherever the ship is
+pub fn dig(game: &mut Game) {
+    if game.player.docked.is_some() {
+        game.message = "the harbourmaster won't have you digging up the docks".into();
+        return;
+    }
+
+    let position = game.player.position;
+    let spot = (0..game.world.treasures().len()).find(|&id| {
+        let treasure = &game.world.treasures()[id];
+        !treasure.found && distance(treasure.location, position) <= DIG_RANGE
+    });
+
+    game.clock.next_turn();
+    game.pass_time(DIG_HOURS * clock::MINUTES_PER_HOUR);
+
+    game.message = match spot.and_then(|id| Some((id, game.world.dig_up(id)?))) {
+        Some((id, gold)) => {
+            game.gold += gold;
+            game.maps.retain(|&map| map != id);
+            format!(
+                "you dug up the hoard of {}, {gold} gold!",
+                game.world.treasures()[id].owner
+            )
+        }
+        None => "you dig for hours and find nothing but sand".into(),
+    };
+}
+
+fn frame(game: &Game, page: usize) -> Frame {
+    let mut frame = Frame::blank(game.screen);
+    let id = game.maps[page];
+    let treasure = &game.world.treasures()[id];
+
+    frame.print(
+        0,
+        0,
+        &format!(
+            "treasure map {} of {} - the hoard of {}, buried in the year {}",
+            page + 1,
+            game.maps.len(),
+            treasure.owner,
+            treasure.buried
+        ),
+        YELLOW,
+        BLACK,
+    );
+    frame.print(0, 1, "[left/right] another map  [esc] back", GRAY, BLACK);
+
+    let map = treasure.map(&game.world);
+    frame.draw(&map, 2, 3);
+    frame.print(
+        0,
+        map.dimensions().height as usize + 4,
+        &clue(&game.world, treasure),
+        WHITE,
+        BLACK,
+    );
+
+    frame
+}
+
+/// look through the player's treasure maps until they back out
+///
+/// returns false if the window was closed while looking
+pub fn maps(
+    game: &mut Game,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+) -> bool {
+    if game.maps.is_empty() {
+        game.message = "you don't have any treasure maps".into();
+        return true;
+    }
+
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send treasure map to render thread. Error: {e}");
+        }
+    };
+
+    let mut page = 0;
+    send(frame(game, page));
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::X => return true,
+            VirtualKeyCode::Left => page = page.checked_sub(1).unwrap_or(game.maps.len() - 1),
+            VirtualKeyCode::Right => page = (page + 1) % game.maps.len(),
+            _ => continue,
+        }
+
+        send(frame(game, page));
+    }
+
+    // the render thread hung up on us
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_only_lead_to_treasure_still_to_be_found() {
+        let mut game = Game::for_tests(1);
+        assert_eq!(game.world.treasures().len(), 2);
+        assert!(game.player.docked.is_some());
+
+        game.world.dig_up(0).unwrap();
+        game.gold = MAP_PRICE * 3;
+
+        buy_map(&mut game);
+        assert!(game.message.contains("sold you a map"), "{}", game.message);
+        assert_eq!(game.maps, [1]);
+
+        // the one still buried is already mapped, and the other is long gone
+        for _ in 0..5 {
+            buy_map(&mut game);
+            assert_eq!(game.message, "nobody here has any maps to sell");
+        }
+        assert_eq!(game.maps, [1]);
+        assert_eq!(game.gold, MAP_PRICE * 2);
+        assert!(take_map(&mut game, &mut RandomNumberGenerator::seeded(1)).is_none());
+    }
+}
@@ -0,0 +1,284 @@
+//! the weather, drifting across the map on the wind
+//!
+//! weather comes in systems: a patch of fog, a storm, a calm or a front, each centred somewhere and
+//! reaching out a few tiles around that. they're born at random, blown along by the wind where
+//! they are and blow themselves out after a while. storms are more likely towards the poles and in
+//! winter, and the doldrums around the equator are where the calms happen
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::RGBA;
+use serde::{Deserialize, Serialize};
+
+use crate::render::Tile;
+use crate::worldgen::climate::{latitude, Vector};
+use crate::worldgen::FullWorld;
+
+use super::clock::Season;
+
+/// how likely a new system is to turn up somewhere in any given hour
+const SPAWN_CHANCE: f64 = 0.15;
+/// there's never more than this many systems at once
+const MAX_SYSTEMS: usize = 16;
+/// how many tiles an hour a system moves for every unit of wind behind it
+const DRIFT: f64 = 0.3;
+/// how many hours of weather there's already been when a game starts
+const WARM_UP_HOURS: u32 = 72;
+/// how much of the rigging a storm tears up every hour, for an ordinary crew
+pub const STORM_SAILS: f64 = 3.;
+/// how much of the hull a storm batters every hour, for an ordinary crew
+pub const STORM_HULL: f64 = 1.;
+
+/// what the weather is doing at a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Conditions {
+    Fair,
+    /// no wind to speak of
+    Calm,
+    Fog,
+    /// rain and a stiff breeze
+    Front,
+    Storm,
+}
+
+impl Conditions {
+    /// how much of what can be seen on a fair day can be seen in this weather
+    pub fn visibility(self) -> f64 {
+        match self {
+            Conditions::Fair | Conditions::Calm => 1.,
+            Conditions::Fog => 0.3,
+            Conditions::Front => 0.7,
+            Conditions::Storm => 0.4,
+        }
+    }
+
+    /// how much of her usual speed a ship makes in this weather
+    pub fn speed(self) -> f64 {
+        match self {
+            Conditions::Fair => 1.,
+            Conditions::Calm => 0.5,
+            // feeling the way along
+            Conditions::Fog => 0.8,
+            Conditions::Front => 1.15,
+            // running under bare poles
+            Conditions::Storm => 0.7,
+        }
+    }
+
+    /// which conditions win out where two systems overlap
+    fn severity(self) -> u8 {
+        match self {
+            Conditions::Fair => 0,
+            Conditions::Calm => 1,
+            Conditions::Front => 2,
+            Conditions::Fog => 3,
+            Conditions::Storm => 4,
+        }
+    }
+
+    /// draw the weather over a tile of the map
+    pub fn overlay(self, tile: Tile) -> Tile {
+        let tint = match self {
+            Conditions::Fair => return tile,
+            Conditions::Calm => RGBA::from_u8(40, 40, 10, 255),
+            Conditions::Fog => RGBA::from_u8(90, 90, 90, 255),
+            Conditions::Front => RGBA::from_u8(20, 30, 70, 255),
+            Conditions::Storm => RGBA::from_u8(70, 10, 60, 255),
+        };
+
+        tile.with_bg(tint)
+    }
+}
+
+impl std::fmt::Display for Conditions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Conditions::Fair => "fair",
+            Conditions::Calm => "becalmed",
+            Conditions::Fog => "fog",
+            Conditions::Front => "rain",
+            Conditions::Storm => "storm",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// one patch of weather
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct System {
+    conditions: Conditions,
+    /// where the middle of it is, in tiles
+    centre: Vector,
+    radius: f64,
+    /// how many more hours it lasts
+    hours: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Weather {
+    systems: Vec<System>,
+    /// hours that passed without being enough to move the weather along yet
+    pending: f64,
+    rng: RandomNumberGenerator,
+}
+
+impl Weather {
+    pub fn new(world: &FullWorld, season: Season, rng: RandomNumberGenerator) -> Self {
+        let mut weather = Weather {
+            systems: Vec::new(),
+            pending: 0.,
+            rng,
+        };
+        weather.advance(world, f64::from(WARM_UP_HOURS), season);
+
+        weather
+    }
+
+    /// the weather at (x, y)
+    pub fn at(&self, x: usize, y: usize) -> Conditions {
+        let (x, y) = (x as f64, y as f64);
+
+        self.systems
+            .iter()
+            .filter(|s| (s.centre.0 - x).hypot(s.centre.1 - y) <= s.radius)
+            .map(|s| s.conditions)
+            .max_by_key(|c| c.severity())
+            .unwrap_or(Conditions::Fair)
+    }
+
+    /// let `hours` of weather go by
+    pub fn advance(&mut self, world: &FullWorld, hours: f64, season: Season) {
+        self.pending += hours;
+
+        while self.pending >= 1. {
+            self.pending -= 1.;
+            self.hour(world, season);
+        }
+    }
+
+    /// an hour of weather: everything drifts on the wind, some of it blows out and sometimes
+    /// something new turns up
+    fn hour(&mut self, world: &FullWorld, season: Season) {
+        let dimensions = world.dimensions();
+        let (width, height) = (f64::from(dimensions.width), f64::from(dimensions.height));
+
+        for system in &mut self.systems {
+            #[allow(clippy::cast_sign_loss)]
+            let (x, y) = (system.centre.0 as usize, system.centre.1 as usize);
+            let wind = world.wind_at(x, y);
+            system.centre.0 += wind.0 * DRIFT;
+            system.centre.1 += wind.1 * DRIFT;
+            system.hours -= 1.;
+        }
+        self.systems.retain(|s| {
+            s.hours > 0.
+                && (0. ..width).contains(&s.centre.0)
+                && (0. ..height).contains(&s.centre.1)
+        });
+
+        if self.systems.len() < MAX_SYSTEMS && self.rng.range(0., 1.) < SPAWN_CHANCE {
+            let x = self.rng.range(0, dimensions.width as usize);
+            let y = self.rng.range(0, dimensions.height as usize);
+            let conditions = self.pick(latitude(y, dimensions), season);
+            let (radius, hours) = match conditions {
+                Conditions::Storm => (self.rng.range(2., 5.), self.rng.range(12., 36.)),
+                Conditions::Fog => (self.rng.range(3., 7.), self.rng.range(6., 24.)),
+                Conditions::Calm => (self.rng.range(4., 9.), self.rng.range(12., 48.)),
+                Conditions::Front | Conditions::Fair => {
+                    (self.rng.range(5., 10.), self.rng.range(24., 48.))
+                }
+            };
+
+            self.systems.push(System {
+                conditions,
+                centre: (x as f64 + 0.5, y as f64 + 0.5),
+                radius,
+                hours,
+            });
+        }
+    }
+
+    /// what sort of weather turns up at `latitude` in `season`
+    fn pick(&mut self, latitude: f64, season: Season) -> Conditions {
+        let (storm, fog, calm) = match season {
+            Season::Winter => (1.6, 1., 0.6),
+            Season::Autumn => (1.3, 1.3, 0.8),
+            Season::Spring => (0.9, 1.3, 1.),
+            Season::Summer => (0.6, 0.8, 1.5),
+        };
+        let weights = [
+            // out towards the poles the weather is wild
+            (Conditions::Storm, storm * (0.3 + 1.5 * latitude)),
+            (Conditions::Fog, fog * (0.5 + latitude)),
+            // and around the equator there's often no wind at all
+            (Conditions::Calm, calm * 2. * (1. - latitude).powi(2)),
+            (Conditions::Front, 1.5),
+        ];
+
+        let total: f64 = weights.iter().map(|(_, w)| w).sum();
+        let mut roll = self.rng.range(0., total);
+        for (conditions, weight) in weights {
+            if roll < weight {
+                return conditions;
+            }
+            roll -= weight;
+        }
+
+        Conditions::Front
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather(systems: Vec<System>) -> Weather {
+        Weather {
+            systems,
+            pending: 0.,
+            rng: RandomNumberGenerator::seeded(6),
+        }
+    }
+
+    /// how many of 4000 new systems at `latitude` in `season` are `conditions`
+    fn picked(latitude: f64, season: Season, conditions: Conditions) -> usize {
+        let mut weather = weather(Vec::new());
+
+        (0..4000)
+            .filter(|_| weather.pick(latitude, season) == conditions)
+            .count()
+    }
+
+    #[test]
+    fn storms_keep_to_the_poles_and_calms_to_the_equator() {
+        let polar_storms = picked(0.9, Season::Winter, Conditions::Storm);
+        assert!(polar_storms > 2 * picked(0.1, Season::Winter, Conditions::Storm));
+        assert!(polar_storms > picked(0.9, Season::Summer, Conditions::Storm));
+
+        let doldrums = picked(0., Season::Summer, Conditions::Calm);
+        assert!(doldrums > 2 * picked(0.6, Season::Summer, Conditions::Calm));
+        assert!(doldrums > picked(0., Season::Winter, Conditions::Calm));
+        assert_eq!(picked(1., Season::Summer, Conditions::Calm), 0);
+    }
+
+    #[test]
+    fn the_worst_weather_wins() {
+        let system = |conditions, centre| System {
+            conditions,
+            centre,
+            radius: 3.,
+            hours: 10.,
+        };
+        let weather = weather(vec![
+            system(Conditions::Fog, (10.5, 10.5)),
+            system(Conditions::Storm, (14.5, 10.5)),
+            system(Conditions::Calm, (10.5, 13.5)),
+        ]);
+
+        assert_eq!(weather.at(12, 10), Conditions::Storm);
+        assert_eq!(weather.at(10, 12), Conditions::Fog);
+        assert_eq!(weather.at(10, 16), Conditions::Calm);
+        assert_eq!(weather.at(8, 10), Conditions::Fog);
+        assert_eq!(weather.at(30, 30), Conditions::Fair);
+    }
+}
@@ -1,6 +1,13 @@
 //! helper functions and structures that will be used throughout the codebase
 #![allow(dead_code)]
 
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
 /// a tuple of  (x: A, y: A) meant to represent a point in space
 pub type Point<A> = (A, A);
 
@@ -18,28 +25,6 @@ impl<A: num_traits::Num + PartialOrd> Distance for A {
     }
 }
 
-/// Takes an index, the width and height of a rectangular array, and converts from the i to an x and y location
-///
-/// return is (x,y)
-/// this may be bugged. either this or [point_to_index]
-pub fn index_to_point(i: usize, w: usize, h: usize) -> Point<usize> {
-    // x is i modulo width
-    // y is (i - x) / width
-    //
-
-    (i % w, (i - (i % w)) / h)
-    // let x = i % h;
-
-    // (x, (i - x) / w)
-}
-
-/// takes an x, a y, and a width and converts from x and y to the index of a rectangular array
-///
-/// this may be bugged. either this or [index_to_point]
-pub fn point_to_index(x: usize, y: usize, w: usize) -> usize {
-    x * w + y
-}
-
 ///  returns all of the points surrounding a given point
 pub fn points_around<A: num_traits::Num + Copy>(x: A, y: A) -> [Point<A>; 8] {
     // get generic one
@@ -58,26 +43,561 @@ pub fn points_around<A: num_traits::Num + Copy>(x: A, y: A) -> [Point<A>; 8] {
 }
 
 /// a rectangle with a height and width
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RectDimension {
-    pub width: u8,
-    pub height: u8,
+    pub width: u16,
+    pub height: u16,
 }
 
 impl RectDimension {
-    pub fn new(width: u8, height: u8) -> Self {
+    pub const fn new(width: u16, height: u16) -> Self {
         RectDimension { width, height }
     }
 
-    pub fn area(&self) -> usize {
+    pub fn area(self) -> usize {
         self.width as usize * self.height as usize
     }
 
-    pub fn point_to_index(&self, x: usize, y: usize) -> usize {
-        point_to_index(x, y, self.width as usize)
+    /// whether (x, y) is inside the rectangle
+    pub fn contains(self, x: usize, y: usize) -> bool {
+        x < self.width as usize && y < self.height as usize
+    }
+
+    /// where (x, y) is in a row by row array of the rectangle. doesn't check (x, y) is inside it
+    pub fn point_to_index(self, x: usize, y: usize) -> usize {
+        y * self.width as usize + x
+    }
+
+    /// the (x, y) of index `i` in a row by row array of the rectangle
+    pub fn index_to_point(self, i: usize) -> Point<usize> {
+        let width = (self.width as usize).max(1);
+
+        (i % width, i / width)
+    }
+}
+
+/// which tiles count as next to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbours {
+    /// up, down, left and right
+    Four,
+    /// the diagonals as well
+    Eight,
+}
+
+/// what happens at the edges of a grid when looking for neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// nothing is past the edge
+    Never,
+    /// the left and right edges are next to each other
+    Horizontally,
+    /// the top and bottom edges are next to each other
+    Vertically,
+    /// both, like the surface of a doughnut
+    Always,
+}
+
+/// a rectangle of `T`s, one for every (x, y) in it, kept row by row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "GridData<T>")]
+pub struct Grid<T> {
+    dimensions: RectDimension,
+    cells: Vec<T>,
+}
+
+/// a [`Grid`] as it's read in, before checking it has a cell for every (x, y)
+#[derive(Deserialize)]
+struct GridData<T> {
+    dimensions: RectDimension,
+    cells: Vec<T>,
+}
+
+impl<T> TryFrom<GridData<T>> for Grid<T> {
+    type Error = String;
+
+    fn try_from(data: GridData<T>) -> Result<Self, Self::Error> {
+        let GridData { dimensions, cells } = data;
+        let len = cells.len();
+
+        Grid::from_vec(dimensions, cells).ok_or_else(|| {
+            format!(
+                "a {}x{} grid needs {} cells, not {len}",
+                dimensions.width,
+                dimensions.height,
+                dimensions.area()
+            )
+        })
+    }
+}
+
+// derived Default would want T: Default, and an empty grid doesn't need one
+impl<T> Default for Grid<T> {
+    fn default() -> Self {
+        Grid {
+            dimensions: RectDimension::default(),
+            cells: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// a grid with `value` everywhere
+    pub fn filled(dimensions: RectDimension, value: T) -> Self {
+        Grid {
+            dimensions,
+            cells: vec![value; dimensions.area()],
+        }
+    }
+
+    /// set every cell back to `value`
+    pub fn fill(&mut self, value: T) {
+        self.cells.fill(value);
+    }
+}
+
+impl<T> Grid<T> {
+    /// build a grid by calling `f` with every (x, y), row by row from the top left
+    pub fn from_fn(dimensions: RectDimension, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let cells = (0..dimensions.area())
+            .map(|i| {
+                let (x, y) = dimensions.index_to_point(i);
+                f(x, y)
+            })
+            .collect();
+
+        Grid { dimensions, cells }
     }
 
-    pub fn index_to_point(&self, i: usize) -> Point<usize> {
-        index_to_point(i, self.width as usize, self.height as usize)
+    /// build a grid by calling `f` with every (x, y), spread out over every core. comes out
+    /// exactly the same as [`Grid::from_fn`] would, as long as `f` doesn't care what order it's
+    /// called in
+    pub fn par_from_fn(dimensions: RectDimension, f: impl Fn(usize, usize) -> T + Sync) -> Self
+    where
+        T: Send,
+    {
+        let cells = (0..dimensions.area())
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = dimensions.index_to_point(i);
+                f(x, y)
+            })
+            .collect();
+
+        Grid { dimensions, cells }
+    }
+
+    /// wrap up cells that are already row by row. None if there aren't exactly enough of them
+    pub fn from_vec(dimensions: RectDimension, cells: Vec<T>) -> Option<Self> {
+        (cells.len() == dimensions.area()).then_some(Grid { dimensions, cells })
+    }
+
+    pub fn dimensions(&self) -> RectDimension {
+        self.dimensions
+    }
+
+    pub fn width(&self) -> usize {
+        self.dimensions.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.dimensions.height as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        self.dimensions.contains(x, y)
+    }
+
+    /// where (x, y) is kept, if it's on the grid at all
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        self.in_bounds(x, y)
+            .then(|| self.dimensions.point_to_index(x, y))
+    }
+
+    /// which (x, y) is kept at index `i`, if there's one there at all
+    pub fn point(&self, i: usize) -> Option<Point<usize>> {
+        (i < self.len()).then(|| self.dimensions.index_to_point(i))
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.index(x, y).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        self.index(x, y).map(|i| &mut self.cells[i])
+    }
+
+    /// put `value` at (x, y), handing back what was there. None (and `value` is dropped) if (x, y)
+    /// is off the grid
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> Option<T> {
+        self.get_mut(x, y)
+            .map(|cell| std::mem::replace(cell, value))
+    }
+
+    /// every cell, row by row
+    pub fn as_slice(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.cells
+    }
+
+    /// every cell, row by row
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// every (x, y) on the grid, row by row
+    pub fn points(&self) -> impl Iterator<Item = Point<usize>> {
+        let dimensions = self.dimensions;
+
+        (0..self.len()).map(move |i| dimensions.index_to_point(i))
+    }
+
+    /// every cell along with where it is, row by row
+    pub fn enumerate(&self) -> impl Iterator<Item = (Point<usize>, &T)> {
+        self.points().zip(&self.cells)
+    }
+
+    /// row `y`, from left to right
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        (y < self.height()).then(|| &self.cells[y * self.width()..(y + 1) * self.width()])
+    }
+
+    /// every row, from the top down
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks can't be 0 wide, but an empty grid has no rows to chunk anyway
+        self.cells.chunks(self.width().max(1))
+    }
+
+    /// column `x`, from the top down. empty if `x` is off the grid
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        let height = if x < self.width() { self.height() } else { 0 };
+
+        self.cells
+            .iter()
+            .skip(x)
+            .step_by(self.width().max(1))
+            .take(height)
+    }
+
+    /// every column, from left to right
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width()).map(|x| self.column(x))
+    }
+
+    /// the points next to (x, y), without (x, y) itself or any point twice. points past the edges
+    /// come back around to the other side if the grid wraps that way
+    pub fn neighbours(
+        &self,
+        (x, y): Point<usize>,
+        neighbours: Neighbours,
+        wrap: Wrap,
+    ) -> impl Iterator<Item = Point<usize>> {
+        const FOUR: [Point<isize>; 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+        const EIGHT: [Point<isize>; 8] = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ];
+
+        let offsets: &[Point<isize>] = match neighbours {
+            Neighbours::Four => &FOUR,
+            Neighbours::Eight => &EIGHT,
+        };
+        let wraps_x = matches!(wrap, Wrap::Horizontally | Wrap::Always);
+        let wraps_y = matches!(wrap, Wrap::Vertically | Wrap::Always);
+        let step = |from: usize, by: isize, size: usize, wraps: bool| {
+            if wraps {
+                (from + size).checked_add_signed(by).map(|to| to % size)
+            } else {
+                from.checked_add_signed(by).filter(|&to| to < size)
+            }
+        };
+
+        // on a grid only one or two wide, wrapping can come back around to the same point
+        let mut found = Vec::with_capacity(offsets.len());
+        if self.in_bounds(x, y) {
+            for &(dx, dy) in offsets {
+                let to = (
+                    step(x, dx, self.width(), wraps_x),
+                    step(y, dy, self.height(), wraps_y),
+                );
+                if let (Some(nx), Some(ny)) = to {
+                    if (nx, ny) != (x, y) && !found.contains(&(nx, ny)) {
+                        found.push((nx, ny));
+                    }
+                }
+            }
+        }
+
+        found.into_iter()
+    }
+
+    /// a grid the same size, with `f` applied to every cell
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            dimensions: self.dimensions,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// a grid made from every pair of cells at the same (x, y) in this grid and `other`. None if
+    /// they aren't the same size
+    pub fn zip<U, V>(&self, other: &Grid<U>, mut f: impl FnMut(&T, &U) -> V) -> Option<Grid<V>> {
+        (self.width() == other.width() && self.height() == other.height()).then(|| Grid {
+            dimensions: self.dimensions,
+            cells: self
+                .cells
+                .iter()
+                .zip(&other.cells)
+                .map(|(a, b)| f(a, b))
+                .collect(),
+        })
+    }
+}
+
+impl<T> Index<Point<usize>> for Grid<T> {
+    type Output = T;
+
+    /// panics if (x, y) is off the grid, use [`Grid::get`] if it might be
+    fn index(&self, (x, y): Point<usize>) -> &T {
+        let Some(i) = Grid::index(self, x, y) else {
+            panic!(
+                "({x}, {y}) is off a {}x{} grid",
+                self.width(),
+                self.height()
+            );
+        };
+
+        &self.cells[i]
+    }
+}
+
+impl<T> IndexMut<Point<usize>> for Grid<T> {
+    fn index_mut(&mut self, (x, y): Point<usize>) -> &mut T {
+        let Some(i) = Grid::index(self, x, y) else {
+            panic!(
+                "({x}, {y}) is off a {}x{} grid",
+                self.width(),
+                self.height()
+            );
+        };
+
+        &mut self.cells[i]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Grid<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.iter()
+    }
+}
+
+/// a flag shared between threads to ask long running work to stop early. every clone is the same
+/// flag, and once it's set it stays set
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ask everything holding this flag to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// any grid up to 40 on a side, holding something different in every cell
+    fn grid() -> impl Strategy<Value = Grid<usize>> {
+        (0u16..40, 0u16..40).prop_map(|(width, height)| {
+            Grid::from_fn(RectDimension::new(width, height), |x, y| x * 1000 + y)
+        })
+    }
+
+    fn wrap() -> impl Strategy<Value = Wrap> {
+        prop_oneof![
+            Just(Wrap::Never),
+            Just(Wrap::Horizontally),
+            Just(Wrap::Vertically),
+            Just(Wrap::Always),
+        ]
+    }
+
+    fn neighbours() -> impl Strategy<Value = Neighbours> {
+        prop_oneof![Just(Neighbours::Four), Just(Neighbours::Eight)]
+    }
+
+    #[test]
+    fn rows_are_rows() {
+        let grid = Grid::from_fn(RectDimension::new(3, 2), |x, y| (x, y));
+
+        assert_eq!(grid.row(1), Some(&[(0, 1), (1, 1), (2, 1)][..]));
+        assert_eq!(
+            grid.column(2).copied().collect::<Vec<_>>(),
+            [(2, 0), (2, 1)]
+        );
+        assert_eq!(grid.get(2, 1), Some(&(2, 1)));
+        assert_eq!(grid.get(1, 2), None);
+    }
+
+    #[test]
+    fn grids_with_the_wrong_number_of_cells_dont_load() {
+        let grid = Grid::from_fn(RectDimension::new(3, 2), |x, y| x + y);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(serde_json::from_str::<Grid<usize>>(&json).unwrap(), grid);
+
+        let short = r#"{"dimensions":{"width":3,"height":2},"cells":[0,1,2]}"#;
+        let error = serde_json::from_str::<Grid<usize>>(short).unwrap_err();
+        assert!(error.to_string().contains("needs 6 cells, not 3"));
+    }
+
+    #[test]
+    fn neighbours_wrap_around_corners() {
+        let grid = Grid::filled(RectDimension::new(5, 4), ());
+        let mut around: Vec<_> = grid
+            .neighbours((0, 0), Neighbours::Eight, Wrap::Always)
+            .collect();
+        around.sort_unstable();
+
+        assert_eq!(
+            around,
+            [
+                (0, 1),
+                (0, 3),
+                (1, 0),
+                (1, 1),
+                (1, 3),
+                (4, 0),
+                (4, 1),
+                (4, 3)
+            ]
+        );
+        assert_eq!(
+            grid.neighbours((0, 0), Neighbours::Four, Wrap::Never)
+                .count(),
+            2
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn index_round_trips(grid in grid(), i in 0usize..2000) {
+            match grid.point(i) {
+                Some((x, y)) => {
+                    prop_assert_eq!(grid.index(x, y), Some(i));
+                    prop_assert_eq!(grid.get(x, y), Some(&(x * 1000 + y)));
+                }
+                None => prop_assert!(i >= grid.len()),
+            }
+        }
+
+        #[test]
+        fn point_round_trips(grid in grid(), x in 0usize..50, y in 0usize..50) {
+            match grid.index(x, y) {
+                Some(i) => prop_assert_eq!(grid.point(i), Some((x, y))),
+                None => prop_assert!(x >= grid.width() || y >= grid.height()),
+            }
+        }
+
+        #[test]
+        fn set_then_get(mut grid in grid(), x in 0usize..50, y in 0usize..50) {
+            let old = grid.get(x, y).copied();
+
+            prop_assert_eq!(grid.set(x, y, usize::MAX), old);
+            prop_assert_eq!(grid.get(x, y).copied(), old.map(|_| usize::MAX));
+        }
+
+        #[test]
+        fn rows_and_columns_cover_the_grid(grid in grid()) {
+            let rows: Vec<_> = grid.rows().flatten().copied().collect();
+            prop_assert_eq!(&rows[..], grid.as_slice());
+            prop_assert!(grid.rows().all(|row| row.len() == grid.width()));
+
+            prop_assert_eq!(grid.columns().count(), grid.width());
+            for (x, column) in grid.columns().enumerate() {
+                let column: Vec<_> = column.copied().collect();
+                let expected: Vec<_> = (0..grid.height()).map(|y| x * 1000 + y).collect();
+                prop_assert_eq!(column, expected);
+            }
+        }
+
+        #[test]
+        fn neighbours_are_next_door(
+            grid in grid(),
+            x in 0usize..40,
+            y in 0usize..40,
+            neighbours in neighbours(),
+            wrap in wrap(),
+        ) {
+            let around: Vec<_> = grid.neighbours((x, y), neighbours, wrap).collect();
+
+            let most = match neighbours {
+                Neighbours::Four => 4,
+                Neighbours::Eight => 8,
+            };
+            prop_assert!(around.len() <= most);
+            for (i, &(nx, ny)) in around.iter().enumerate() {
+                prop_assert!(grid.in_bounds(nx, ny));
+                prop_assert_ne!((nx, ny), (x, y));
+                prop_assert!(!around[..i].contains(&(nx, ny)));
+                // being next to each other goes both ways
+                prop_assert!(grid.neighbours((nx, ny), neighbours, wrap).any(|p| p == (x, y)));
+            }
+        }
+
+        #[test]
+        fn map_and_zip_keep_their_places(grid in grid()) {
+            let doubled = grid.map(|&cell| cell * 2);
+            prop_assert_eq!(doubled.dimensions(), grid.dimensions());
+
+            let zipped = grid.zip(&doubled, |&a, &b| b - a);
+            prop_assert_eq!(zipped, Some(grid.clone()));
+
+            let other = Grid::filled(
+                RectDimension::new(grid.dimensions().width + 1, grid.dimensions().height),
+                0,
+            );
+            prop_assert!(grid.zip(&other, |_, _| ()).is_none());
+        }
+
+        #[test]
+        fn par_from_fn_matches_from_fn(grid in grid()) {
+            let parallel = Grid::par_from_fn(grid.dimensions(), |x, y| x * 1000 + y);
+            prop_assert_eq!(parallel, grid);
+        }
     }
 }
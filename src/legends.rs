@@ -0,0 +1,399 @@
+//! the legends screen, for reading back through the history of a generated world
+//!
+//! lives on the work thread like everything else, building frames of text and sending them over
+//! to the renderer whenever the player presses a key. also handles exporting a written chronicle
+//! for when there is no renderer at all.
+
+use std::fmt::Write;
+use std::sync::mpsc::{Receiver, Sender};
+
+use bracket_lib::terminal::{VirtualKeyCode, BLACK, GRAY, NAVY, RED, WHITE, YELLOW};
+use log::error;
+use serde::Serialize;
+
+use crate::helpers::RectDimension;
+use crate::render::{Camera, Frame, RenderPacket, RenderTick, Tile};
+use crate::worldgen::history::{CaptainId, CivId, History, PortId};
+use crate::worldgen::{self, FullWorld};
+
+/// rows taken up by the header above the event list
+const HEADER_ROWS: usize = 3;
+/// how many years a single step of the year filter covers
+const YEAR_STEP: u32 = 10;
+
+/// which events are currently being listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+    All,
+    /// every event in the [`YEAR_STEP`] years starting at this year
+    Years(u32),
+    Civilization(CivId),
+    Place(PortId),
+    Person(CaptainId),
+}
+
+struct Legends<'a> {
+    world: &'a FullWorld,
+    history: &'a History,
+    screen: RectDimension,
+    filter: Filter,
+    /// indices into [`History::events`] passing the current filter
+    shown: Vec<usize>,
+    /// index into shown
+    selected: usize,
+    /// index into shown of the first row on screen
+    scroll: usize,
+}
+
+impl<'a> Legends<'a> {
+    fn new(world: &'a FullWorld, screen: RectDimension) -> Self {
+        let mut legends = Legends {
+            world,
+            history: world.history(),
+            screen,
+            filter: Filter::All,
+            shown: Vec::new(),
+            selected: 0,
+            scroll: 0,
+        };
+        legends.refresh();
+
+        legends
+    }
+
+    fn list_rows(&self) -> usize {
+        (self.screen.height as usize)
+            .saturating_sub(HEADER_ROWS)
+            .max(1)
+    }
+
+    /// recalculate which events are shown after the filter changed
+    fn refresh(&mut self) {
+        let history = self.history;
+
+        self.shown = history
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| match self.filter {
+                Filter::All => true,
+                Filter::Years(start) => (start..start + YEAR_STEP).contains(&event.year),
+                Filter::Civilization(civ) => history.involves_civ(event, civ),
+                Filter::Place(port) => event.kind.ports().contains(&port),
+                Filter::Person(captain) => event.kind.captain() == Some(captain),
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    /// move the selection by `by` rows, keeping it on screen
+    fn move_selection(&mut self, by: isize) {
+        let last = self.shown.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(by).min(last);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + self.list_rows() {
+            self.scroll = self.selected + 1 - self.list_rows();
+        }
+    }
+
+    /// switch to the next kind of filter, skipping kinds there is nothing to filter by
+    fn next_filter(&mut self) {
+        let history = self.history;
+
+        self.filter = match self.filter {
+            Filter::All => Filter::Years(0),
+            Filter::Years(_) if !history.civilizations.is_empty() => Filter::Civilization(0),
+            Filter::Years(_) | Filter::Civilization(_) if !history.ports.is_empty() => {
+                Filter::Place(0)
+            }
+            Filter::Years(_) | Filter::Civilization(_) | Filter::Place(_)
+                if !history.captains.is_empty() =>
+            {
+                Filter::Person(0)
+            }
+            _ => Filter::All,
+        };
+
+        self.refresh();
+    }
+
+    /// pick the previous or next year, civilization, place or person to filter by
+    fn change_subject(&mut self, forward: bool) {
+        let cycle = |current: usize, len: usize| {
+            if forward {
+                (current + 1) % len
+            } else {
+                (current + len - 1) % len
+            }
+        };
+
+        self.filter = match self.filter {
+            Filter::All => Filter::All,
+            Filter::Years(start) if forward => {
+                Filter::Years((start + YEAR_STEP).min(self.history.years))
+            }
+            Filter::Years(start) => Filter::Years(start.saturating_sub(YEAR_STEP)),
+            Filter::Civilization(civ) => {
+                Filter::Civilization(cycle(civ, self.history.civilizations.len()))
+            }
+            Filter::Place(port) => Filter::Place(cycle(port, self.history.ports.len())),
+            Filter::Person(captain) => Filter::Person(cycle(captain, self.history.captains.len())),
+        };
+
+        self.refresh();
+    }
+
+    fn filter_name(&self) -> String {
+        match self.filter {
+            Filter::All => "everything".into(),
+            Filter::Years(start) => format!("years {}-{}", start, start + YEAR_STEP - 1),
+            Filter::Civilization(civ) => self.history.civilizations[civ].name.clone(),
+            Filter::Place(port) => self.history.ports[port].name.clone(),
+            Filter::Person(captain) => self.history.captains[captain].name.clone(),
+        }
+    }
+
+    /// the list of events, as a frame covering the whole screen
+    fn frame(&self) -> Frame {
+        let mut frame = Frame::blank(self.screen);
+
+        frame.print(
+            0,
+            0,
+            &format!(
+                "Legends - {} years, showing {} events about {}",
+                self.history.years,
+                self.shown.len(),
+                self.filter_name()
+            ),
+            YELLOW,
+            BLACK,
+        );
+        frame.print(
+            0,
+            1,
+            "[tab] filter  [left/right] change filter  [enter] show on map  [esc] back",
+            GRAY,
+            BLACK,
+        );
+
+        for (row, &event) in self
+            .shown
+            .iter()
+            .skip(self.scroll)
+            .take(self.list_rows())
+            .enumerate()
+        {
+            let event = &self.history.events[event];
+            let bg = if row + self.scroll == self.selected {
+                NAVY
+            } else {
+                BLACK
+            };
+            let line = format!("{:>5} {}", event.year, self.history.describe(event));
+
+            frame.print(0, HEADER_ROWS + row, &line, WHITE, bg);
+        }
+
+        frame
+    }
+
+    /// the world map with the selected event marked on it, if it happened anywhere
+    fn map_frame(&self) -> Option<Frame> {
+        let event = &self.history.events[*self.shown.get(self.selected)?];
+        let (x, y) = self.history.location_of(event)?;
+
+        // centred on wherever it happened, with room for the two lines underneath
+        let space = RectDimension::new(self.screen.width, self.screen.height.saturating_sub(2));
+        let camera = Camera::centred((x, y), self.world.dimensions(), space);
+
+        let mut frame = Frame::blank(self.screen);
+        frame.draw(&worldgen::world_frame(self.world, camera), 0, 0);
+        frame.set_on_map(camera, (x, y), Tile::new('X', RED, BLACK));
+
+        let below_map = camera.view.height as usize;
+        frame.print(
+            0,
+            below_map,
+            &format!("{}: {}", event.year, self.history.describe(event)),
+            WHITE,
+            BLACK,
+        );
+        frame.print(0, below_map + 1, "press any key to go back", GRAY, BLACK);
+
+        Some(frame)
+    }
+}
+
+/// show the legends screen until the player backs out of it
+///
+/// returns false if the window was closed while browsing
+pub fn browse(
+    world: &FullWorld,
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+    screen: RectDimension,
+) -> bool {
+    let mut legends = Legends::new(world, screen);
+    let send = |frame: Frame| {
+        if let Err(e) = sender.send(RenderPacket::NewFrame(frame)) {
+            error!("Unable to send legends to render thread. Error: {e}");
+        }
+    };
+
+    send(legends.frame());
+
+    // true while the map is up instead of the list
+    let mut on_map = false;
+
+    while let Ok(tick) = receiver.recv() {
+        let key = match tick {
+            RenderTick::Key(key) => key,
+            RenderTick::LoopClosed => return false,
+            _ => continue,
+        };
+
+        if on_map {
+            on_map = false;
+            send(legends.frame());
+            continue;
+        }
+
+        let page = isize::try_from(legends.list_rows()).unwrap_or(isize::MAX);
+        match key {
+            VirtualKeyCode::Escape => return true,
+            VirtualKeyCode::Up => legends.move_selection(-1),
+            VirtualKeyCode::Down => legends.move_selection(1),
+            VirtualKeyCode::PageUp => legends.move_selection(-page),
+            VirtualKeyCode::PageDown => legends.move_selection(page),
+            VirtualKeyCode::Home => legends.move_selection(isize::MIN),
+            VirtualKeyCode::End => legends.move_selection(isize::MAX),
+            VirtualKeyCode::Tab => legends.next_filter(),
+            VirtualKeyCode::Left => legends.change_subject(false),
+            VirtualKeyCode::Right => legends.change_subject(true),
+            VirtualKeyCode::Return => {
+                if let Some(map) = legends.map_frame() {
+                    on_map = true;
+                    send(map);
+                    continue;
+                }
+            }
+            _ => continue,
+        }
+
+        send(legends.frame());
+    }
+
+    // the render thread hung up on us
+    false
+}
+
+#[derive(Serialize)]
+struct ChronicleEntry {
+    year: u32,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Chronicle<'a> {
+    history: &'a History,
+    chronicle: Vec<ChronicleEntry>,
+}
+
+/// the whole history as plain text, one event per line
+pub fn chronicle_text(history: &History) -> String {
+    let mut text = String::new();
+
+    for event in &history.events {
+        // writing to a string can't fail
+        let _ = writeln!(text, "{:>5} {}", event.year, history.describe(event));
+    }
+
+    text
+}
+
+/// the whole history as json. both the raw history and the written out chronicle are included,
+/// so it can be read by people and by other tools alike
+pub fn chronicle_json(history: &History) -> serde_json::Result<String> {
+    let chronicle = Chronicle {
+        history,
+        chronicle: history
+            .events
+            .iter()
+            .map(|event| ChronicleEntry {
+                year: event.year,
+                text: history.describe(event),
+            })
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&chronicle)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{Cancel, RectDimension};
+    use crate::worldgen::{gen_full_world, GenParam};
+
+    use super::*;
+
+    fn history() -> History {
+        let params = GenParam {
+            seed: 5,
+            world_size: RectDimension::new(80, 60),
+            history_years: 60,
+            ..GenParam::default()
+        };
+
+        gen_full_world(&params, None, &Cancel::new())
+            .unwrap()
+            .history()
+            .clone()
+    }
+
+    #[test]
+    fn chronicles_tell_every_event_once_in_order() {
+        let history = history();
+        assert!(!history.events.is_empty());
+
+        let text = chronicle_text(&history);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), history.events.len());
+
+        let mut last_year = 0;
+        for (line, event) in lines.iter().zip(&history.events) {
+            let (year, description) = line.trim_start().split_once(' ').unwrap();
+            let year: u32 = year.parse().unwrap();
+
+            assert_eq!(year, event.year);
+            assert_eq!(description, history.describe(event));
+            assert!(year >= last_year, "{line} is out of order");
+            last_year = year;
+        }
+    }
+
+    #[test]
+    fn chronicle_json_reads_back() {
+        let history = history();
+        let json: serde_json::Value =
+            serde_json::from_str(&chronicle_json(&history).unwrap()).unwrap();
+
+        let read: History = serde_json::from_value(json["history"].clone()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&read.events).unwrap(),
+            serde_json::to_value(&history.events).unwrap()
+        );
+
+        let chronicle = json["chronicle"].as_array().unwrap();
+        assert_eq!(chronicle.len(), history.events.len());
+        for (entry, event) in chronicle.iter().zip(&history.events) {
+            assert_eq!(entry["year"], event.year);
+            assert_eq!(entry["text"], history.describe(event).as_str());
+        }
+    }
+}
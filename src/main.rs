@@ -1,72 +1,210 @@
 #![warn(clippy::pedantic, clippy::perf)]
 // we do not target 32 bit
 #![allow(clippy::cast_possible_truncation)]
+// nothing we count comes close to 2^52
+#![allow(clippy::cast_precision_loss)]
+// map coordinates are nowhere near isize::MAX
+#![allow(clippy::cast_possible_wrap)]
 
+mod game;
 mod helpers;
+mod legends;
 mod render;
 mod worldgen;
 
+use std::path::Path;
 use std::sync::mpsc;
 use std::thread;
 use std::{error::Error, time::UNIX_EPOCH};
 
-use log::{trace, warn};
+use log::{error, info, trace, warn};
 
-use helpers::RectDimension;
+use helpers::{Cancel, RectDimension};
+use worldgen::{config, GenParam, WorldGenError};
 
 use crate::render::{RenderPacket, RenderTick, Renderer};
 
-fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+/// anything smaller is all pole
+const MIN_WORLD_SIZE: u16 = 10;
+
+/// read a world size like "2048x1024"
+fn parse_size(size: &str) -> Option<RectDimension> {
+    let (width, height) = size.split_once('x')?;
+    let size = RectDimension::new(width.trim().parse().ok()?, height.trim().parse().ok()?);
+
+    (size.width >= MIN_WORLD_SIZE && size.height >= MIN_WORLD_SIZE).then_some(size)
+}
+
+/// write out exactly what a world was made from to `PS_WRITE_CONFIG`, if it's set, so the same
+/// world can be made again
+fn write_config(params: &GenParam) -> Result<(), config::ConfigError> {
+    if let Ok(path) = std::env::var("PS_WRITE_CONFIG") {
+        config::save(params, Path::new(&path))?;
+        info!("wrote config of seed {} to {path}", params.seed);
+    }
+
+    Ok(())
+}
+
+fn main() {
     env_logger::init();
 
-    let seed = std::env::var("PS_SEED").map_or_else(
-        |_| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as usize
+    // anything that goes wrong gets one line saying what, rather than a debug dump
+    if let Err(e) = run() {
+        eprintln!("pirate_sim: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// generate a world to play from `params`, showing it being made as it goes. None if there's no
+/// world to play, because it couldn't be made or the window closed first
+fn new_game(
+    params: &GenParam,
+    dimensions: RectDimension,
+    render_s: &mut mpsc::Sender<RenderPacket>,
+    input_r: &mpsc::Receiver<RenderTick>,
+    closed: &Cancel,
+) -> Option<game::Game> {
+    match worldgen::gen_full_world(params, Some((render_s, input_r)), closed) {
+        Ok(world) => Some(game::Game::new(world, dimensions)),
+        // the window's gone, so whatever went wrong was most likely just that
+        Err(WorldGenError::Cancelled) => None,
+        Err(_) if closed.is_cancelled() => None,
+        Err(e) => {
+            // leave it up on screen, it's all the window is good for now
+            error!("Unable to generate a world. Error: {e}");
+            let message = render::string_to_frame(&format!("Unable to generate a world: {e}"));
+            render_s.send(RenderPacket::NewFrame(message)).ok();
+            None
+        }
+    }
+}
+
+#[allow(clippy::similar_names)]
+fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
+    // everything about the world comes from a config file if there is one, otherwise the defaults
+    // with a new seed
+    let mut gen = match std::env::var("PS_CONFIG") {
+        Ok(path) => config::load(Path::new(&path))
+            .map_err(|e| format!("unable to use config file {path}: {e}"))?,
+        Err(_) => GenParam {
+            seed: config::clock_seed(),
+            ..GenParam::default()
         },
-        |s| s.parse().unwrap(),
-    ) as u64;
+    };
+
+    // anything set in the environment wins over the config
+    if let Ok(seed) = std::env::var("PS_SEED") {
+        gen.seed = seed
+            .trim()
+            .parse()
+            .map_err(|_| format!("PS_SEED should be a whole number, not {seed:?}"))?;
+    }
 
     trace!("Starting render test");
 
-    //TODO: make sure Xs and Ys align with width/height correctly throughout the program x = width, y = height
-    //TODO: feature to regenerate with new seed graphically?
-    let gen = worldgen::GenParam {
-        seed,
-        // 178/255 around 70%
-        target_water: 178,
-        max_ports: 20,
-        max_civilizations: 4,
-
-        world_size: RectDimension::new(100, 100),
-        max_polar_tiles: 400,
-        min_polar_tiles: 280,
-    };
+    // like "2048x1024", width first
+    if let Ok(size) = std::env::var("PS_WORLD_SIZE") {
+        match parse_size(&size) {
+            Some(size) => gen.world_size = size,
+            None => warn!(
+                "Unable to read world size {size:?}, it should look like 2048x1024 and be at least {MIN_WORLD_SIZE} each way. Using {}x{} instead",
+                gen.world_size.width, gen.world_size.height
+            ),
+        }
+    }
+
+    // the terrain of one of the presets, by name
+    if let Ok(name) = std::env::var("PS_TERRAIN") {
+        match config::preset(&name) {
+            Some(preset) => gen.terrain = preset.terrain,
+            None => warn!(
+                "Unknown terrain preset {name:?}, it should be one of {}. Using the usual terrain instead",
+                config::preset_names().join(", ")
+            ),
+        }
+    }
+
+    let seed = gen.seed;
+    gen.validate()?;
+
+    // headless, just write out the chronicle of a world and quit
+    if let Ok(path) = std::env::var("PS_CHRONICLE") {
+        let world = worldgen::gen_full_world(&gen, None, &Cancel::new())?;
+
+        let is_json = std::path::Path::new(&path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+        let chronicle = if is_json {
+            legends::chronicle_json(world.history())?
+        } else {
+            legends::chronicle_text(world.history())
+        };
+
+        std::fs::write(&path, chronicle)?;
+        info!("wrote chronicle of seed {seed} to {path}");
+        write_config(world.params())?;
+
+        return Ok(());
+    }
 
     let dimensions = RectDimension::new(105, 105);
     let ctx = bracket_lib::terminal::BTermBuilder::simple(dimensions.width, dimensions.height)?
         .with_title("Pirate Sim World Gen")
         .build()?;
 
-    let (render_s, render_r) = mpsc::channel::<RenderPacket>();
+    let (mut render_s, render_r) = mpsc::channel::<RenderPacket>();
     let (input_s, input_r) = mpsc::channel::<RenderTick>();
 
-    let renderer = Renderer::new_blank(render_r, input_s, dimensions);
+    // generation gives up as soon as the window closes, rather than finishing a world nobody will see
+    let closed = Cancel::new();
+    let renderer =
+        Renderer::new_blank(render_r, input_s, dimensions).cancel_on_close(closed.clone());
 
     // TODO: set up render thread, send receiver and sender to renderer
+    // pick up a saved game instead of generating a new world
+    let load = std::env::var("PS_LOAD").ok();
+
     thread::spawn(move || {
-        worldgen::gen_full_world(gen, Some((render_s, input_r)));
+        let mut saved = load.and_then(|path| match game::load(Path::new(&path), dimensions) {
+            Ok(game) => Some(game),
+            Err(e) => {
+                error!("Unable to load saved game from {path}, generating a new world. Error: {e}");
+                None
+            }
+        });
+
+        // round again every time the player asks for a new world from the menu
+        loop {
+            let game = match saved.take() {
+                Some(game) => game,
+                None => match new_game(&gen, dimensions, &mut render_s, &input_r, &closed) {
+                    Some(game) => game,
+                    None => return,
+                },
+            };
+            if let Err(e) = write_config(game.world().params()) {
+                error!("Unable to write out config. Error: {e}");
+            }
+
+            match game::play(game, &render_s, &input_r) {
+                Some(params) => {
+                    info!("generating a new world with seed {}", params.seed);
+                    gen = params;
+                }
+                None => return,
+            }
+        }
     });
 
-    renderer.start_render(ctx).unwrap();
+    renderer.start_render(ctx)?;
 
     Ok(())
 }
 
 /// a test function to use while architecting renderer
-#[allow(unused)]
+#[allow(unused, clippy::similar_names)]
 fn render_test() -> Result<(), Box<dyn Error + Send + Sync>> {
     use render::{RenderPacket, RenderTick, Renderer};
 
@@ -95,9 +233,9 @@ fn render_test() -> Result<(), Box<dyn Error + Send + Sync>> {
                                 .as_secs()
                         );
 
-                        trace!("{}", message);
+                        trace!("{message}");
 
-                        let to_display = render::string_to_frame(message);
+                        let to_display = render::string_to_frame(&message);
 
                         render_s.send(RenderPacket::NewFrame(to_display)).unwrap();
                     }
@@ -112,7 +250,7 @@ fn render_test() -> Result<(), Box<dyn Error + Send + Sync>> {
         }
     });
 
-    renderer.start_render(ctx).unwrap();
+    renderer.start_render(ctx)?;
 
     Ok(())
 }
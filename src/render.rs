@@ -1,12 +1,13 @@
-//! a renderer meant to live in its own thread and be passed "RenderRequests"
+//! a renderer meant to live in its own thread and be passed [`RenderPacket`]s
 //! passing back any inputs from the player, if necessary
 //!
 //! might have to handle logging on single thread as well?
 
 use std::{
-    collections::HashMap,
     error::Error,
-    sync::mpsc::{Receiver, Sender},
+    fmt::Display,
+    sync::mpsc::{Receiver, RecvTimeoutError, SendError, Sender},
+    time::Duration,
 };
 
 use bracket_lib::terminal::{
@@ -14,7 +15,7 @@ use bracket_lib::terminal::{
 };
 use log::{debug, trace, warn};
 
-use crate::helpers::RectDimension;
+use crate::helpers::{Cancel, Grid, Point, RectDimension};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Tile {
@@ -29,23 +30,50 @@ impl Tile {
             char,
         }
     }
+
+    /// the same tile on a different background
+    pub fn with_bg<BG: Into<RGBA>>(self, bg: BG) -> Self {
+        Tile {
+            colors: ColorPair::new(self.colors.fg, bg),
+            char: self.char,
+        }
+    }
+
+    /// the same tile drawn washed out and darker, for things remembered rather than seen
+    pub fn dimmed(self) -> Self {
+        // how far towards black the colours go
+        const DIM: f32 = 0.5;
+
+        let dim = |colour: RGBA| {
+            let faded = colour.desaturate();
+            faded.lerp(RGBA::from_f32(0., 0., 0., faded.a), DIM)
+        };
+
+        Tile {
+            colors: ColorPair::new(dim(self.colors.fg), dim(self.colors.bg)),
+            char: self.char,
+        }
+    }
 }
 
+/// how far from the left or right edge of the screen a gui should be drawn
+#[derive(Debug, Clone, Copy)]
 pub enum OffsetX {
     Left(i8),
-    Righ(i8)
+    Right(i8),
 }
 
+/// how far from the top or bottom edge of the screen a gui should be drawn
+#[derive(Debug, Clone, Copy)]
 pub enum OffsetY {
     Above(i8),
-    Below(i8)
+    Below(i8),
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct GUI {
-    // offset from the rendered frame
-    // - x for offset from left, -y for offset from bottom
-    // TODO: better way to specify offset
-    pub offset: (OffsetX,OffsetY),
+    // offset from the edges of the screen
+    pub offset: (OffsetX, OffsetY),
     pub to_render: Option<Frame>,
 }
 
@@ -59,9 +87,10 @@ pub enum RenderPacket {
     RegisterGUI(u8, Option<GUI>),
     // priority,
     RegisterGUIs(u8, Vec<Option<GUI>>),
+    /// replace the frame of an already registered gui, None hides it
     UpdateGUI {
-        id: u8,
-        update: Frame,
+        id: usize,
+        update: Option<Frame>,
     },
 }
 
@@ -72,16 +101,103 @@ pub enum RenderTick {
     Key(VirtualKeyCode),
     LoopClosed,
     // priority, id
+    #[allow(unused)]
     RegisteredGUI(u8, usize),
     // priority, ids
+    #[allow(unused)]
     RegisteredGUIs(u8, Vec<usize>), // TODO: error packet
 }
 
+/// something going wrong between the renderer and whoever is talking to it
+#[derive(Debug)]
+pub enum RenderError {
+    /// the other end of a channel has gone away, most likely because the window was closed
+    Disconnected,
+    /// the renderer took too long to answer
+    TimedOut,
+    /// the terminal itself couldn't keep going
+    Terminal(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::Disconnected => write!(f, "the window was closed"),
+            RenderError::TimedOut => write!(f, "the window stopped responding"),
+            RenderError::Terminal(e) => write!(f, "the window ran into a problem: {e}"),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Terminal(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for RenderError {
+    fn from(_: SendError<T>) -> Self {
+        RenderError::Disconnected
+    }
+}
+
+impl From<RecvTimeoutError> for RenderError {
+    fn from(e: RecvTimeoutError) -> Self {
+        match e {
+            RecvTimeoutError::Timeout => RenderError::TimedOut,
+            RecvTimeoutError::Disconnected => RenderError::Disconnected,
+        }
+    }
+}
+
 /// information necessary to render a single frame
 #[derive(Debug, Clone)]
 pub struct Frame {
-    pub to_render: Vec<Tile>,
-    pub dimensions: RectDimension,
+    pub to_render: Grid<Tile>,
+}
+
+/// the stretch of a map that's on screen, for maps too big to show all at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Camera {
+    /// the map tile in the top left corner of the view
+    pub corner: Point<usize>,
+    /// how much of the map is in view
+    pub view: RectDimension,
+}
+
+impl Camera {
+    /// look at as much of a `map` sized map as fits in `space`, with `focus` as near the middle as
+    /// it can be without showing anything past the edges of the map
+    pub fn centred(focus: Point<usize>, map: RectDimension, space: RectDimension) -> Self {
+        let view = RectDimension::new(map.width.min(space.width), map.height.min(space.height));
+        let corner = |at: usize, size: u16, shown: u16| {
+            at.saturating_sub(shown as usize / 2)
+                .min(size as usize - shown as usize)
+        };
+
+        Camera {
+            corner: (
+                corner(focus.0, map.width, view.width),
+                corner(focus.1, map.height, view.height),
+            ),
+            view,
+        }
+    }
+
+    /// where map tile (x, y) is in the view, if it's in view at all
+    pub fn to_screen(self, (x, y): Point<usize>) -> Option<Point<usize>> {
+        let (vx, vy) = (x.checked_sub(self.corner.0)?, y.checked_sub(self.corner.1)?);
+
+        self.view.contains(vx, vy).then_some((vx, vy))
+    }
+
+    /// the map tile at (x, y) in the view
+    pub fn to_map(self, (x, y): Point<usize>) -> Point<usize> {
+        (self.corner.0 + x, self.corner.1 + y)
+    }
 }
 
 /// For rendering. all rendering must be on main thread or X11 gets mad
@@ -92,35 +208,51 @@ pub struct Renderer {
     should_rerender: bool,
     // storing large-ish frames and extremely small Offset{X,Y} pair seperately makes it so we can
     // loop through offsets multiple times with far less performance hit
-    gui_frames: Vec<(u8,Option<Frame>)>,
-    gui_offset: Vec<(OffsetX,OffsetY)>,
+    gui_frames: Vec<(u8, Option<Frame>)>,
+    gui_offset: Vec<(OffsetX, OffsetY)>,
+    /// set once the window is gone, so long running work knows to give up
+    closed: Option<Cancel>,
 }
 
-/// take a string and construct a frame to render that string 
+/// take a string and construct a frame to render that string
 ///
 /// It's the caller's job to ensure that the rendering window has proper dimensions for the
 /// returned frame
-pub fn string_to_frame(string: String) -> Frame {
-    let dimension = if string.len() > 100 {
-        let x = 100;
-        let y = (string.len() - 100) / 100 + 1;
-
-        RectDimension::new(x as u8, y as u8)
-    } else {
-        RectDimension::new(string.len() as u8, 3)
-    };
-
-    let tiles = string.chars().into_iter().map(|c| Tile {
-        char: c,
-        colors: ColorPair {
-            fg: WHITE.into(),
-            bg: BLACK.into(),
-        },
-    });
-
-    Frame {
-        to_render: tiles.collect(),
-        dimensions: dimension,
+pub fn string_to_frame(string: &str) -> Frame {
+    let chars: Vec<_> = string.chars().collect();
+    // wrapped onto as many rows of 100 as it takes. anything too long for even that is cut off
+    let dimension = RectDimension::new(
+        chars.len().min(100) as u16,
+        u16::try_from(chars.len().div_ceil(100)).unwrap_or(u16::MAX),
+    );
+
+    let mut frame = Frame::blank(dimension);
+    for (i, &c) in chars.iter().enumerate() {
+        frame.set(i % 100, i / 100, Tile::new(c, WHITE, BLACK));
+    }
+
+    frame
+}
+
+/// register a single gui and wait for the renderer to hand back its id
+///
+/// any other ticks that come in while waiting are dropped
+pub fn register_gui(
+    sender: &Sender<RenderPacket>,
+    receiver: &Receiver<RenderTick>,
+    priority: u8,
+    gui: GUI,
+) -> Option<usize> {
+    sender
+        .send(RenderPacket::RegisterGUI(priority, Some(gui)))
+        .ok()?;
+
+    loop {
+        if let RenderTick::RegisteredGUI(_, id) =
+            receiver.recv_timeout(Duration::from_secs(3)).ok()?
+        {
+            return Some(id);
+        }
     }
 }
 
@@ -128,21 +260,17 @@ impl GameState for Renderer {
     // the main loop of the renderer
     fn tick(&mut self, ctx: &mut bracket_lib::terminal::BTerm) {
         if ctx.quitting {
-            self.sender.send(RenderTick::LoopClosed).unwrap();
+            self.close();
         }
 
         // a key is pressed?
         if let Some(key) = ctx.key {
-            match self.sender.send(RenderTick::Key(key)) {
-                Ok(_) => {}
-                Err(e) => {
-                    debug!(
-                        "Unable to send packet key from render thread to work thread. error: {}. This may only be an issue if your game goes unresponsive.",
-                        e.to_string()
-                    );
-                }
-            };
-        };
+            if let Err(e) = self.sender.send(RenderTick::Key(key)) {
+                debug!(
+                    "Unable to send packet key from render thread to work thread. error: {e}. This may only be an issue if your game goes unresponsive."
+                );
+            }
+        }
 
         if let Ok(r_packet) = self.receiver.try_recv() {
             match r_packet {
@@ -154,34 +282,35 @@ impl GameState for Renderer {
                 }
                 RenderPacket::ChangeSize(new_size) => {
                     ctx.set_char_size_and_resize_window(
-                        new_size.width as u32,
-                        new_size.height as u32,
+                        u32::from(new_size.width),
+                        u32::from(new_size.height),
                     );
                 }
                 RenderPacket::RegisterGUI(priority, to_register) => {
                     let id = self.register_gui(priority, to_register);
 
-                    self.sender
-                        .send(RenderTick::RegisteredGUI(priority, id))
-                        .unwrap();
+                    if let Err(e) = self.sender.send(RenderTick::RegisteredGUI(priority, id)) {
+                        warn!("Unable to hand back id of gui {id}. Error: {e}");
+                    }
                 }
                 RenderPacket::RegisterGUIs(priority, to_register) => {
                     let ids = to_register
                         .into_iter()
-                        .map(|gui| {
-                            let id = self.register_gui(priority, gui);
-                            id
-                        })
+                        .map(|gui| self.register_gui(priority, gui))
                         .collect();
 
-                    self.sender
-                        .send(RenderTick::RegisteredGUIs(priority, ids))
-                        .unwrap();
+                    if let Err(e) = self.sender.send(RenderTick::RegisteredGUIs(priority, ids)) {
+                        warn!("Unable to hand back ids of guis. Error: {e}");
+                    }
                 }
-                RenderPacket::UpdateGUI { id, update } => {
-                    todo!();
-                } // Query packet?
-            };
+                RenderPacket::UpdateGUI { id, update } => match self.gui_frames.get_mut(id) {
+                    Some((_, frame)) => {
+                        *frame = update;
+                        self.should_rerender = true;
+                    }
+                    None => warn!("tried to update gui {id} which was never registered"),
+                }, // Query packet?
+            }
         }
 
         // check for render packets
@@ -191,31 +320,29 @@ impl GameState for Renderer {
             // TODO: render diffing if render takes large amounts of time
             ctx.cls();
 
-            // render gui first, keep track of already rendered areas, gui offset amt?
-            let mut x_offset = 0;
-            let mut y_offset = 0;
-            let mut rendered_ponts = Vec::new();
-            rendered_ponts.resize(ctx.get_char_size().0 as usize* ctx.get_char_size().1 as usize, false);
-
-
-            // TODO: 0 reason to clone this
-            // Option<Frame> maybe? would be hacky, but definetly better perf
-            let cf = self.cur_frame.clone();
-            self.render_frame(&cf, ctx, (0, 0));
-
-            // sort & render guis next
-
-            // TODO this is so sketch
-            let mut to_sort: Vec<_> = (0..self.guis.len()).into_iter().collect();
+            Self::render_frame(&self.cur_frame, ctx, (0, 0));
 
-            to_sort.sort_by_key(|idx| self.guis[*idx].0);
+            // sort & render guis next, lowest priority first so higher priorities end up on top
+            let mut to_sort: Vec<_> = (0..self.gui_frames.len()).collect();
+            to_sort.sort_by_key(|idx| self.gui_frames[*idx].0);
 
+            let (screen_w, screen_h) = ctx.get_char_size();
             for idx in to_sort {
-                if let Some(to_render) = &self.guis[idx].1 {
-                    let x,y;  
-                    if (to_render.offset.0  > 0 ){
-                        x = 
-                    }
+                if let Some(frame) = &self.gui_frames[idx].1 {
+                    let x = match self.gui_offset[idx].0 {
+                        OffsetX::Left(o) => u32::from(o.unsigned_abs()),
+                        OffsetX::Right(o) => screen_w.saturating_sub(
+                            u32::from(frame.dimensions().width) + u32::from(o.unsigned_abs()),
+                        ),
+                    };
+                    let y = match self.gui_offset[idx].1 {
+                        OffsetY::Above(o) => u32::from(o.unsigned_abs()),
+                        OffsetY::Below(o) => screen_h.saturating_sub(
+                            u32::from(frame.dimensions().height) + u32::from(o.unsigned_abs()),
+                        ),
+                    };
+
+                    Self::render_frame(frame, ctx, (x as usize, y as usize));
                 }
             }
 
@@ -226,18 +353,17 @@ impl GameState for Renderer {
 }
 
 impl Renderer {
-    fn render_frame(&mut self, frame: &Frame, ctx: &mut BTerm, start_point: (u8, u8)) -> () {
+    fn render_frame(frame: &Frame, ctx: &mut BTerm, start_point: (usize, usize)) {
         trace!(
             "rendering frame with dimension ({},{}) and offset ({},{}).",
-            frame.dimensions.width,
-            frame.dimensions.height,
+            frame.dimensions().width,
+            frame.dimensions().height,
             start_point.0,
             start_point.1
         );
-        for i in 0..frame.to_render.len() {
-            let (local_x, local_y) = frame.dimensions.index_to_point(i);
-            let x = local_x + start_point.0 as usize;
-            let y = local_y + start_point.1 as usize;
+        for ((local_x, local_y), ct) in frame.to_render.enumerate() {
+            let x = local_x + start_point.0;
+            let y = local_y + start_point.1;
 
             if x as u32 > ctx.get_char_size().0 {
                 warn!(
@@ -256,8 +382,6 @@ impl Renderer {
                 break;
             }
 
-            let ct = self.cur_frame.to_render[i];
-
             ctx.set(x, y, ct.colors.fg, ct.colors.bg, to_cp437(ct.char));
         }
     }
@@ -265,13 +389,18 @@ impl Renderer {
     fn register_gui(&mut self, priority: u8, gui: Option<GUI>) -> usize {
         // register gui, sort,
 
-        let new_id = self.guis.len();
+        let new_id = self.gui_frames.len();
 
-        if let &Some(_) = &gui {
-            self.should_rerender = true;
-        }
+        let (offset, frame) = match gui {
+            Some(gui) => {
+                self.should_rerender = true;
+                (gui.offset, gui.to_render)
+            }
+            None => ((OffsetX::Left(0), OffsetY::Above(0)), None),
+        };
 
-        self.guis.push((priority, gui));
+        self.gui_frames.push((priority, frame));
+        self.gui_offset.push(offset);
 
         new_id
     }
@@ -285,25 +414,89 @@ impl Renderer {
         Self {
             receiver,
             sender,
-            cur_frame: Frame::new(dimensions),
+            cur_frame: Frame::blank(dimensions),
             should_rerender: false,
-            guis: Vec::new(),
+            gui_frames: Vec::new(),
+            gui_offset: Vec::new(),
+            closed: None,
+        }
+    }
+
+    /// set `cancel` when the window closes. the work thread might be too busy to read a
+    /// [`RenderTick::LoopClosed`] until it's done, but it can check this as it goes
+    pub fn cancel_on_close(mut self, cancel: Cancel) -> Self {
+        self.closed = Some(cancel);
+        self
+    }
+
+    /// tell the work thread the window is gone
+    fn close(&mut self) {
+        if let Some(closed) = &self.closed {
+            closed.cancel();
+        }
+
+        // nobody left to tell is fine, there's nobody left to care either
+        if self.sender.send(RenderTick::LoopClosed).is_err() {
+            debug!("work thread was gone before the window closed");
         }
     }
 
     /// meant to be spawned in seperate render thread
-    pub fn start_render(self, ctx: BTerm) -> Result<(), Box<dyn Error + Send + Sync>> {
+    pub fn start_render(self, ctx: BTerm) -> Result<(), RenderError> {
         // start ticking here
-        main_loop(ctx, self)?;
+        main_loop(ctx, self).map_err(RenderError::Terminal)
+    }
+}
 
-        Ok(())
+// closing the window straight from the title bar drops the renderer without ever ticking with
+// `quitting` set
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.close();
     }
 }
 impl Frame {
-    fn new(dimensions: RectDimension) -> Self {
+    /// a frame entirely filled with black spaces, ready to be drawn on
+    pub fn blank(dimensions: RectDimension) -> Self {
         Self {
-            to_render: Vec::new(),
-            dimensions,
+            to_render: Grid::filled(dimensions, Tile::new(' ', WHITE, BLACK)),
+        }
+    }
+
+    pub fn dimensions(&self) -> RectDimension {
+        self.to_render.dimensions()
+    }
+
+    /// replace the tile at (x, y). anything outside of the frame is ignored
+    pub fn set(&mut self, x: usize, y: usize, tile: Tile) {
+        self.to_render.set(x, y, tile);
+    }
+
+    /// replace the tile showing map tile `point`, if `camera` can see it
+    pub fn set_on_map(&mut self, camera: Camera, point: Point<usize>, tile: Tile) {
+        if let Some((x, y)) = camera.to_screen(point) {
+            self.set(x, y, tile);
+        }
+    }
+
+    /// copy all of `other` onto this frame with its top left corner at (x, y)
+    pub fn draw(&mut self, other: &Frame, x: usize, y: usize) {
+        for ((ox, oy), &tile) in other.to_render.enumerate() {
+            self.set(x + ox, y + oy, tile);
+        }
+    }
+
+    /// write `text` starting at (x, y), cutting off anything that runs off the right edge
+    pub fn print<FG: Into<RGBA> + Copy, BG: Into<RGBA> + Copy>(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: &str,
+        fg: FG,
+        bg: BG,
+    ) {
+        for (i, c) in text.chars().enumerate() {
+            self.set(x + i, y, Tile::new(c, fg, bg));
         }
     }
 }
@@ -0,0 +1,199 @@
+//! prevailing winds, ocean currents and biomes
+//!
+//! the equator runs across the middle of the map and the poles are at the top and bottom, so
+//! winds are laid out in bands by latitude like on earth, with a bit of noise on top to keep
+//! things from looking too regular. biomes come from how warm and wet a tile is
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use bracket_lib::{
+    noise::{FastNoise, NoiseType},
+    random::RandomNumberGenerator,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Grid, Point, RectDimension};
+
+use super::{GenContext, TerrainMap};
+
+/// currents are a lot weaker than the wind that drives them
+const CURRENT_STRENGTH: f64 = 0.4;
+/// how far (in radians) noise is allowed to turn the wind away from its prevailing direction
+const WIND_WOBBLE: f64 = 0.6;
+/// moisture is read from the same noise as the wind, just far enough away not to line up with it
+const MOISTURE_OFFSET: f32 = 1000.;
+/// land higher than this fraction of the way from sea level to the highest peak is mountains
+const MOUNTAIN_HEIGHT: f64 = 0.5;
+/// land lower than this fraction of the way to the highest peak is beach, if it's by the sea
+const BEACH_HEIGHT: f64 = 0.05;
+
+/// a direction and strength. strength is between 0 and 1, with 1 being a strong wind
+pub type Vector = Point<f64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Biome {
+    #[default]
+    Ocean,
+    Beach,
+    Grassland,
+    Forest,
+    Jungle,
+    Desert,
+    Tundra,
+    Mountain,
+}
+
+impl Display for Biome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Biome::Ocean => "ocean",
+            Biome::Beach => "beach",
+            Biome::Grassland => "grassland",
+            Biome::Forest => "forest",
+            Biome::Jungle => "jungle",
+            Biome::Desert => "desert",
+            Biome::Tundra => "tundra",
+            Biome::Mountain => "mountains",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Climate {
+    /// the prevailing wind for every tile
+    wind: Grid<Vector>,
+    /// the surface current for every tile. always (0, 0) on land
+    current: Grid<Vector>,
+    /// what grows (or doesn't) on every tile
+    biomes: Grid<Biome>,
+}
+
+impl Climate {
+    pub fn wind_at(&self, x: usize, y: usize) -> Vector {
+        self.wind[(x, y)]
+    }
+
+    pub fn current_at(&self, x: usize, y: usize) -> Vector {
+        self.current[(x, y)]
+    }
+
+    pub fn biome_at(&self, x: usize, y: usize) -> Biome {
+        self.biomes[(x, y)]
+    }
+}
+
+/// how far from the equator `y` is. 0 at the equator, 1 at either pole
+pub fn latitude(y: usize, dimensions: RectDimension) -> f64 {
+    let half = f64::from(dimensions.height) / 2.;
+
+    ((y as f64 + 0.5 - half) / half).abs().min(1.)
+}
+
+/// the wind you'd get with no noise at all at a given y
+fn prevailing_wind(y: usize, dimensions: RectDimension) -> Vector {
+    let lat = latitude(y, dimensions);
+    // +1 if poleward is down the screen, -1 if it is up
+    let poleward = if (y as f64) < f64::from(dimensions.height) / 2. {
+        -1.
+    } else {
+        1.
+    };
+
+    if lat < 1. / 3. {
+        // trade winds, blowing west and in towards the equator
+        (-0.8, -0.3 * poleward)
+    } else if lat < 2. / 3. {
+        // westerlies, blowing east and out towards the poles
+        (0.9, 0.3 * poleward)
+    } else {
+        // polar easterlies
+        (-0.6, -0.2 * poleward)
+    }
+}
+
+/// pick a biome for a land tile
+///
+/// `elevation` goes from 0 at sea level to 1 at the highest peak, `moisture` is between 0 and 1
+fn biome(elevation: f64, latitude: f64, moisture: f64, coastal: bool) -> Biome {
+    // it gets colder towards the poles and up mountains
+    let temperature = 1. - latitude - elevation * 0.5;
+
+    if elevation > MOUNTAIN_HEIGHT {
+        Biome::Mountain
+    } else if temperature < 0.2 {
+        Biome::Tundra
+    } else if coastal && elevation < BEACH_HEIGHT {
+        Biome::Beach
+    } else if temperature > 0.65 {
+        match moisture {
+            m if m > 0.6 => Biome::Jungle,
+            m if m < 0.35 => Biome::Desert,
+            _ => Biome::Grassland,
+        }
+    } else {
+        match moisture {
+            m if m > 0.5 => Biome::Forest,
+            m if m < 0.2 => Biome::Desert,
+            _ => Biome::Grassland,
+        }
+    }
+}
+
+/// rotate `v` by `angle` radians
+fn rotate(v: Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+
+    (v.0 * cos - v.1 * sin, v.0 * sin + v.1 * cos)
+}
+
+pub fn gen_climate(terrain: &TerrainMap, context: &mut GenContext) -> Climate {
+    let dimensions = terrain.dimensions;
+    let mut noise = FastNoise::seeded(context.rng.next_u64());
+    noise.set_noise_type(NoiseType::Perlin);
+    noise.set_frequency(0.08);
+
+    let coast: HashSet<_> = terrain.coastal_tiles().into_iter().collect();
+    let land_height = (terrain.max_height - terrain.sea_level).max(f64::EPSILON);
+
+    let wind = Grid::par_from_fn(dimensions, |x, y| {
+        let wobble = f64::from(noise.get_noise(x as f32, y as f32));
+
+        rotate(prevailing_wind(y, dimensions), wobble * WIND_WOBBLE)
+    });
+
+    // currents follow the wind, but only where there is water to move
+    let current = Grid::par_from_fn(dimensions, |x, y| {
+        if terrain.is_sea(x, y) {
+            let (wx, wy) = wind[(x, y)];
+            (wx * CURRENT_STRENGTH, wy * CURRENT_STRENGTH)
+        } else {
+            (0., 0.)
+        }
+    });
+
+    let biomes = Grid::par_from_fn(dimensions, |x, y| {
+        if terrain.is_sea(x, y) {
+            return Biome::Ocean;
+        }
+
+        let moisture =
+            f64::from(noise.get_noise(x as f32 + MOISTURE_OFFSET, y as f32 + MOISTURE_OFFSET))
+                * 0.5
+                + 0.5;
+        // sea air keeps the coast a little wetter
+        let coastal = coast.contains(&(x, y));
+        let moisture = if coastal { moisture + 0.1 } else { moisture };
+        let elevation = -terrain.depth(x, y) / land_height;
+
+        biome(elevation, latitude(y, dimensions), moisture, coastal)
+    });
+
+    Climate {
+        wind,
+        current,
+        biomes,
+    }
+}
@@ -0,0 +1,259 @@
+//! reading and writing [`GenParam`]s as TOML config files
+//!
+//! a config file only has to give whatever it wants different from the defaults. it can also start
+//! from one of the named presets with `preset = "archipelago"`, in which case everything else in it
+//! is laid over the top of that preset. anything given replaces the preset's value whole, so a
+//! `[terrain.Plates]` table only has to list the plate settings it's changing, but it can't mix
+//! them into a preset that uses noise
+
+use std::error::Error;
+use std::fmt::Display;
+use std::path::Path;
+use std::{fs, io};
+
+use serde::Serialize;
+
+use super::noise::Preset;
+use super::tectonics::Tectonics;
+use super::{BaseTerrain, GenParam, WorldGenError};
+
+/// the name of the preset with plate tectonics, on top of the names of the noise presets
+const PLATES: &str = "plates";
+
+/// why a config file couldn't be used
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// it isn't TOML, or doesn't fit the shape of a [`GenParam`]
+    Parse(toml::de::Error),
+    /// the params couldn't be turned into TOML
+    Write(toml::ser::Error),
+    /// it asks for a preset that doesn't exist
+    UnknownPreset(String),
+    /// it reads fine, but no world could be made out of it
+    Invalid(WorldGenError),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{e}"),
+            ConfigError::Parse(e) => write!(f, "{e}"),
+            ConfigError::Write(e) => write!(f, "unable to write out the world's params, {e}"),
+            ConfigError::UnknownPreset(name) => write!(
+                f,
+                "there's no preset called {name:?}, it should be one of {}",
+                preset_names().join(", ")
+            ),
+            ConfigError::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Write(e) => Some(e),
+            ConfigError::Invalid(e) => Some(e),
+            ConfigError::UnknownPreset(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Write(e)
+    }
+}
+
+impl From<WorldGenError> for ConfigError {
+    fn from(e: WorldGenError) -> Self {
+        ConfigError::Invalid(e)
+    }
+}
+
+/// the name of every preset
+pub fn preset_names() -> Vec<String> {
+    Preset::ALL
+        .iter()
+        .map(ToString::to_string)
+        .chain([PLATES.to_string()])
+        .collect()
+}
+
+/// the params going by `name`, ignoring case
+pub fn preset(name: &str) -> Option<GenParam> {
+    let defaults = GenParam::default();
+
+    if name.trim().eq_ignore_ascii_case(PLATES) {
+        return Some(GenParam {
+            target_water: 65.,
+            terrain: BaseTerrain::Plates(Tectonics::default()),
+            ..defaults
+        });
+    }
+
+    let terrain = Preset::from_name(name)?;
+    let target_water = match terrain {
+        Preset::Classic => defaults.target_water,
+        Preset::Archipelago => 80.,
+        Preset::Pangaea | Preset::Continents => 65.,
+    };
+
+    Some(GenParam {
+        target_water,
+        terrain: BaseTerrain::Noise(terrain.pipeline()),
+        ..defaults
+    })
+}
+
+/// a seed that's different every time, for when nobody picked one
+pub fn clock_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+
+    // folded down to 63 bits, so it still fits in a toml integer when written out to a config
+    #[allow(clippy::cast_possible_truncation)]
+    let folded = (nanos as u64) ^ ((nanos >> 64) as u64);
+    folded & (u64::MAX >> 1)
+}
+
+/// read params out of a config file, checking they make sense. a config without a seed gets a new
+/// one every time it's read
+pub fn from_toml(text: &str) -> Result<GenParam, ConfigError> {
+    let mut table: toml::Table = text.parse()?;
+
+    let base = match table.remove("preset") {
+        Some(toml::Value::String(name)) => preset(&name).ok_or(ConfigError::UnknownPreset(name))?,
+        Some(other) => return Err(ConfigError::UnknownPreset(other.to_string())),
+        None => GenParam::default(),
+    };
+    if !table.contains_key("seed") {
+        table.insert("seed".into(), to_value(clock_seed())?);
+    }
+
+    let mut params = toml::Table::try_from(&base)?;
+    params.extend(table);
+    let params: GenParam = params.try_into()?;
+    params.validate()?;
+
+    Ok(params)
+}
+
+/// params written out as a complete config, seed and all, so the same world can be made again
+pub fn to_toml(params: &GenParam) -> Result<String, ConfigError> {
+    Ok(toml::to_string_pretty(params)?)
+}
+
+pub fn load(path: &Path) -> Result<GenParam, ConfigError> {
+    from_toml(&fs::read_to_string(path)?)
+}
+
+pub fn save(params: &GenParam, path: &Path) -> Result<(), ConfigError> {
+    fs::write(path, to_toml(params)?)?;
+
+    Ok(())
+}
+
+fn to_value(value: impl Serialize) -> Result<toml::Value, ConfigError> {
+    Ok(toml::Value::try_from(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_round_trip() {
+        for name in preset_names() {
+            let params = GenParam {
+                seed: 12345,
+                ..preset(&name).unwrap()
+            };
+
+            assert_eq!(from_toml(&to_toml(&params).unwrap()).unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn configs_lay_over_presets() {
+        let params = from_toml(
+            r#"
+            preset = "archipelago"
+            seed = 7
+            target_water = 55.5
+            world_size = { width = 300, height = 200 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            params,
+            GenParam {
+                seed: 7,
+                target_water: 55.5,
+                world_size: crate::helpers::RectDimension::new(300, 200),
+                ..preset("archipelago").unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn partial_tables_fill_in_defaults() {
+        let params = from_toml("[terrain.Plates]\nplates = 30").unwrap();
+
+        assert_eq!(
+            params.terrain,
+            BaseTerrain::Plates(Tectonics {
+                plates: 30,
+                ..Tectonics::default()
+            })
+        );
+    }
+
+    #[test]
+    fn bad_configs_are_errors() {
+        assert!(matches!(
+            from_toml(r#"preset = "atlantis""#),
+            Err(ConfigError::UnknownPreset(_))
+        ));
+        assert!(matches!(
+            from_toml("target_water = 150"),
+            Err(ConfigError::Invalid(WorldGenError::InvalidParam { .. }))
+        ));
+        assert!(matches!(
+            from_toml("min_polar_tiles = 500"),
+            Err(ConfigError::Invalid(
+                WorldGenError::PolarTilesInverted { .. }
+            ))
+        ));
+        assert!(matches!(
+            from_toml("max_ports = \"lots\""),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn clock_seeds_differ_and_write_out() {
+        let seeds: Vec<_> = (0..20).map(|_| clock_seed()).collect();
+        assert!(seeds.windows(2).any(|pair| pair[0] != pair[1]));
+
+        let params = from_toml("").unwrap();
+        assert_eq!(from_toml(&to_toml(&params).unwrap()).unwrap(), params);
+    }
+}
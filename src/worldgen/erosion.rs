@@ -0,0 +1,675 @@
+//! wearing the base height map down into something more lived in
+//!
+//! erosion is a list of passes run one after another over the whole map, each one working on
+//! whatever the last left behind. rain cuts valleys, loose rock slides down slopes too steep to
+//! hold it, ice grinds out the land near the poles and waves chew at the coast
+//!
+//! every pass is spread over as many cores as there are, but always works from a snapshot of the
+//! map and adds things up in the same order, so a seed makes the same world however many threads
+//! it runs on
+
+use std::collections::VecDeque;
+
+use bracket_lib::random::RandomNumberGenerator;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Cancel, Grid, Neighbours, Point, Wrap};
+
+use super::{stop_if_cancelled, Pole, WorldGenError};
+
+/// how many raindrops run down the map at once. fixed rather than going by how many cores there
+/// are, so the map comes out the same on any machine
+const RAIN_BATCH: usize = 1024;
+
+/// one pass of [`Erosion`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pass {
+    /// raindrops landing all over and running downhill, picking up dirt on the way down and
+    /// dropping it where the ground levels out
+    Rain {
+        /// how many drops fall, per tile of the map
+        drops_per_tile: f32,
+        /// how much of the slope under it a drop takes with it at each step
+        strength: f64,
+        /// how many tiles a drop can run before it soaks in
+        max_steps: u16,
+    },
+    /// rock sliding down any slope steeper than `talus`, settling as scree at the bottom
+    Thermal {
+        /// the biggest drop to a neighbour that can hold. any more than this slides
+        talus: f64,
+        /// how much of the extra slides each time, 0-1
+        strength: f64,
+        /// how many times to let everything slide
+        iterations: u8,
+    },
+    /// ice spreading out from the poles, grinding the land down and digging its valleys out
+    /// deeper, which makes fjords where they reach the sea
+    Glacial {
+        /// how many tiles from frozen ground the ice reaches
+        reach: u16,
+        /// how far the ice grinds the land down, right up against the pole
+        depth: f64,
+    },
+    /// waves eating into the coast. exposed high ground breaks off into cliffs, sometimes leaving
+    /// a sea stack out on its own, and low ground gets washed flat into beaches
+    Coastal {
+        /// how likely an exposed bit of coast is to fall into the sea each time, 0-1
+        strength: f64,
+        /// how high above the sea land has to be to make a cliff rather than a beach
+        cliff_height: f64,
+        /// how likely a bit of fallen cliff is to be left standing as a sea stack instead, 0-1
+        stack_chance: f64,
+        /// how many times the waves come in
+        iterations: u8,
+    },
+}
+
+/// how to erode a world, pass by pass
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Erosion {
+    pub passes: Vec<Pass>,
+}
+
+impl Default for Erosion {
+    fn default() -> Self {
+        Erosion {
+            passes: vec![
+                Pass::Rain {
+                    drops_per_tile: 0.5,
+                    strength: 0.1,
+                    max_steps: 64,
+                },
+                Pass::Thermal {
+                    talus: 6.,
+                    strength: 0.5,
+                    iterations: 8,
+                },
+                Pass::Glacial {
+                    reach: 12,
+                    depth: 20.,
+                },
+                Pass::Coastal {
+                    strength: 0.3,
+                    cliff_height: 10.,
+                    stack_chance: 0.1,
+                    iterations: 3,
+                },
+            ],
+        }
+    }
+}
+
+impl Erosion {
+    /// check every pass could run
+    pub fn validate(&self) -> Result<(), WorldGenError> {
+        for (i, pass) in self.passes.iter().enumerate() {
+            let param = format!("erosion pass {}", i + 1);
+            let chances: &[(&str, f64)] = match *pass {
+                Pass::Rain { strength, .. } | Pass::Thermal { strength, .. } => {
+                    &[("strength", strength)]
+                }
+                Pass::Coastal {
+                    strength,
+                    stack_chance,
+                    ..
+                } => &[("strength", strength), ("stack_chance", stack_chance)],
+                Pass::Glacial { .. } => &[],
+            };
+
+            for &(name, value) in chances {
+                if !(0. ..=1.).contains(&value) {
+                    return Err(WorldGenError::invalid(
+                        format!("{param} {name}"),
+                        format!("should be from 0 to 1, not {value}"),
+                    ));
+                }
+            }
+
+            // a negative talus lets everything slide and a negative depth raises the land instead
+            let sizes: &[(&str, f64)] = match *pass {
+                Pass::Thermal { talus, .. } => &[("talus", talus)],
+                Pass::Glacial { depth, .. } => &[("depth", depth)],
+                Pass::Rain { .. } | Pass::Coastal { .. } => &[],
+            };
+
+            for &(name, value) in sizes {
+                if !(value >= 0. && value.is_finite()) {
+                    return Err(WorldGenError::invalid(
+                        format!("{param} {name}"),
+                        format!("should be 0 or more, not {value}"),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Pass {
+    /// wear `height_map` down, where anything at or under `sea_level` is underwater. gives up
+    /// partway through with [`WorldGenError::Cancelled`] if `cancel` is set
+    ///
+    /// `sea_level` is only where the sea was going in. most passes move ground across it, so it has
+    /// to be solved again for the map that comes out
+    pub(super) fn run(
+        &self,
+        height_map: &mut Grid<f64>,
+        sea_level: f64,
+        pole: &Pole,
+        rng: &mut RandomNumberGenerator,
+        cancel: &Cancel,
+    ) -> Result<(), WorldGenError> {
+        stop_if_cancelled(cancel)?;
+
+        match *self {
+            Pass::Rain {
+                drops_per_tile,
+                strength,
+                max_steps,
+            } => rain(
+                height_map,
+                sea_level,
+                drops_per_tile,
+                strength,
+                max_steps,
+                rng,
+                cancel,
+            )?,
+            Pass::Thermal {
+                talus,
+                strength,
+                iterations,
+            } => {
+                for _ in 0..iterations {
+                    stop_if_cancelled(cancel)?;
+                    thermal(height_map, talus, strength);
+                }
+            }
+            Pass::Glacial { reach, depth } => glacial(height_map, sea_level, pole, reach, depth),
+            Pass::Coastal {
+                strength,
+                cliff_height,
+                stack_chance,
+                iterations,
+            } => {
+                for _ in 0..iterations {
+                    stop_if_cancelled(cancel)?;
+                    coastal(
+                        height_map,
+                        sea_level,
+                        strength,
+                        cliff_height,
+                        stack_chance,
+                        rng,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// the lowest neighbour of `point`, if any are lower than it
+fn downhill(height_map: &Grid<f64>, point: Point<usize>) -> Option<Point<usize>> {
+    height_map
+        .neighbours(point, Neighbours::Eight, Wrap::Never)
+        .filter(|&next| height_map[next] < height_map[point])
+        .min_by(|&a, &b| height_map[a].total_cmp(&height_map[b]))
+}
+
+/// a number from 0 to 1 that only depends on `seed` and `i`, so tiles can each roll their own dice
+/// in any order and on any thread and still come out the same. splitmix64
+fn roll(seed: u64, i: u64) -> f64 {
+    let mut z = seed.wrapping_add(i.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn rain(
+    height_map: &mut Grid<f64>,
+    sea_level: f64,
+    drops_per_tile: f32,
+    strength: f64,
+    max_steps: u16,
+    rng: &mut RandomNumberGenerator,
+    cancel: &Cancel,
+) -> Result<(), WorldGenError> {
+    if height_map.is_empty() {
+        return Ok(());
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    let drops = (height_map.len() as f32 * drops_per_tile.max(0.)).round() as usize;
+    // where every drop lands is picked up front, so it's the same however the work gets split up
+    let landings: Vec<_> = (0..drops)
+        .map(|_| {
+            (
+                rng.range(0, height_map.width()),
+                rng.range(0, height_map.height()),
+            )
+        })
+        .collect();
+
+    // drops in the same batch all run down the map as it was before any of them, then what they
+    // moved is added up in order
+    for batch in landings.chunks(RAIN_BATCH) {
+        stop_if_cancelled(cancel)?;
+
+        let runs: Vec<_> = batch
+            .par_iter()
+            .map(|&landed| run_downhill(height_map, landed, sea_level, strength, max_steps))
+            .collect();
+
+        for run in runs {
+            for (point, change) in run {
+                height_map[point] += change;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// the way a drop landing at `at` goes, as how much it changes every tile on the way
+fn run_downhill(
+    height_map: &Grid<f64>,
+    mut at: Point<usize>,
+    sea_level: f64,
+    strength: f64,
+    max_steps: u16,
+) -> Vec<(Point<usize>, f64)> {
+    let mut changes = Vec::new();
+    let mut carried = 0.;
+
+    for _ in 0..max_steps {
+        // a drop stops once it reaches the sea or a hollow, and leaves everything it was carrying
+        // there
+        if height_map[at] <= sea_level {
+            break;
+        }
+        let Some(next) = downhill(height_map, at) else {
+            break;
+        };
+
+        // never dig below where the water's going, or it would carve out pits
+        let drop = height_map[at] - height_map[next];
+        let taken = (drop * strength).min(drop);
+        changes.push((at, -taken));
+        carried += taken;
+        at = next;
+    }
+
+    changes.push((at, carried));
+    changes
+}
+
+fn thermal(height_map: &mut Grid<f64>, talus: f64, strength: f64) {
+    let dimensions = height_map.dimensions();
+    let before = &*height_map;
+    // how far over `talus` the drop from `from` to `to` is
+    let extra = |from: Point<usize>, to: Point<usize>| before[from] - before[to] - talus;
+
+    // how much slides off every tile, and the total extra it's shared out by
+    let sliding = Grid::par_from_fn(dimensions, |x, y| {
+        let (mut total, mut worst) = (0., 0_f64);
+        for next in before.neighbours((x, y), Neighbours::Eight, Wrap::Never) {
+            let extra = extra((x, y), next);
+            if extra > 0. {
+                total += extra;
+                worst = worst.max(extra);
+            }
+        }
+
+        // half of the worst slope at most, or the tiles would just swap places
+        (worst * 0.5 * strength.clamp(0., 1.), total)
+    });
+
+    // every tile loses what slides off it and picks up its share of whatever slides off the tiles
+    // above it. worked out against the map as it was, so it doesn't matter which tile goes first
+    let settled = Grid::par_from_fn(dimensions, |x, y| {
+        let mut height = before[(x, y)] - sliding[(x, y)].0;
+        for above in before.neighbours((x, y), Neighbours::Eight, Wrap::Never) {
+            let extra = extra(above, (x, y));
+            let (slid, total) = sliding[above];
+            if extra > 0. && total > 0. {
+                height += slid * extra / total;
+            }
+        }
+
+        height
+    });
+
+    *height_map = settled;
+}
+
+fn glacial(height_map: &mut Grid<f64>, sea_level: f64, pole: &Pole, reach: u16, depth: f64) {
+    // how far every tile near enough is from frozen ground
+    let mut distance = Grid::filled(height_map.dimensions(), None);
+    let mut queue = VecDeque::new();
+    for &(x, y) in &pole.frozen_tiles {
+        let point = (usize::from(x), usize::from(y));
+        if height_map.in_bounds(point.0, point.1) {
+            distance[point] = Some(0);
+            queue.push_back(point);
+        }
+    }
+
+    while let Some(point) = queue.pop_front() {
+        let Some(steps) = distance[point] else {
+            continue;
+        };
+        if steps >= reach {
+            continue;
+        }
+
+        for next in height_map.neighbours(point, Neighbours::Four, Wrap::Never) {
+            if distance[next].is_none() {
+                distance[next] = Some(steps + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let before = &*height_map;
+    let carved = Grid::par_from_fn(before.dimensions(), |x, y| {
+        let height = before[(x, y)];
+        let (Some(steps), true) = (distance[(x, y)], height > sea_level) else {
+            return height;
+        };
+
+        // ice runs down into valleys and piles up there, so those get dug out hardest
+        let around: Vec<_> = before
+            .neighbours((x, y), Neighbours::Eight, Wrap::Never)
+            .map(|next| before[next])
+            .collect();
+        let average = around.iter().sum::<f64>() / around.len().max(1) as f64;
+        let valley = ((average - height) / depth.max(f64::EPSILON)).clamp(0., 1.);
+
+        let ice = 1. - f64::from(steps) / f64::from(reach.max(1));
+        height - depth * ice * (0.5 + valley)
+    });
+
+    *height_map = carved;
+}
+
+fn coastal(
+    height_map: &mut Grid<f64>,
+    sea_level: f64,
+    strength: f64,
+    cliff_height: f64,
+    stack_chance: f64,
+    rng: &mut RandomNumberGenerator,
+) {
+    let seed = rng.next_u64();
+    let before = &*height_map;
+    let is_sea = |point: Point<usize>| before[point] <= sea_level;
+
+    // where the foot of a cliff of `height` ends up once it's fallen into the sea
+    let fallen = |height: f64| sea_level - (height - sea_level).min(cliff_height) * 0.1;
+
+    // every tile's new height, and whether it's been left standing as a sea stack
+    let worn = Grid::par_from_fn(before.dimensions(), |x, y| {
+        let point = (x, y);
+        let height = before[point];
+        let touches_sea = before
+            .neighbours(point, Neighbours::Eight, Wrap::Never)
+            .any(is_sea);
+        if is_sea(point) || !touches_sea {
+            return (height, false);
+        }
+
+        // how much open water the tile faces. headlands sticking out into the sea get the worst of
+        // it, tiles tucked away in bays hardly any
+        let (mut sea, mut tiles) = (0, 0);
+        for dy in -2..=2_isize {
+            for dx in -2..=2_isize {
+                let near = (x.checked_add_signed(dx), y.checked_add_signed(dy));
+                if let (Some(nx), Some(ny)) = near {
+                    if before.in_bounds(nx, ny) && (nx, ny) != point {
+                        tiles += 1;
+                        sea += usize::from(is_sea((nx, ny)));
+                    }
+                }
+            }
+        }
+        let exposure = sea as f64 / f64::from(tiles);
+
+        let above = height - sea_level;
+        if above > cliff_height {
+            // the waves undercut it until it falls, leaving a cliff behind. once in a while a
+            // harder bit is left standing
+            let i = before.index(x, y).unwrap_or_default() as u64 * 2;
+            if roll(seed, i) >= strength * exposure {
+                (height, false)
+            } else if roll(seed, i + 1) < stack_chance {
+                (height, true)
+            } else {
+                (fallen(height), false)
+            }
+        } else {
+            // low ground gets washed flat, down towards the waterline
+            (
+                sea_level + above * (1. - strength * exposure).max(0.1),
+                false,
+            )
+        }
+    });
+
+    // the land around a sea stack falls away with the rest of the cliff, leaving it out on its own
+    let isolated = Grid::par_from_fn(before.dimensions(), |x, y| {
+        let (height, stack) = worn[(x, y)];
+        let by_stack = worn
+            .neighbours((x, y), Neighbours::Eight, Wrap::Never)
+            .any(|next| worn[next].1);
+
+        if !stack && by_stack && height > sea_level {
+            fallen(height)
+        } else {
+            height
+        }
+    });
+
+    *height_map = isolated;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::RectDimension;
+    use crate::worldgen::noise::Preset;
+
+    use super::*;
+
+    /// a continent made and worn down from `seed` on a pool of `threads` threads
+    fn eroded(seed: u64, threads: usize) -> Grid<f64> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let mut height_map = Preset::Continents
+                .pipeline()
+                .height_map(RectDimension::new(120, 80), &mut rng);
+            let pole = Pole {
+                frozen_tiles: (0..120).map(|x| (x, 0)).collect(),
+            };
+
+            for pass in Erosion::default().passes {
+                pass.run(&mut height_map, 0., &pole, &mut rng, &Cancel::new())
+                    .unwrap();
+            }
+
+            height_map
+        })
+    }
+
+    #[test]
+    fn same_world_on_any_number_of_threads() {
+        for seed in [1, 42] {
+            let one = eroded(seed, 1);
+
+            for threads in [2, 5] {
+                // compared bit for bit, close enough isn't good enough
+                let bits = |grid: &Grid<f64>| grid.iter().map(|h| h.to_bits()).collect::<Vec<_>>();
+                assert_eq!(bits(&eroded(seed, threads)), bits(&one));
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_passes_stop() {
+        let mut rng = RandomNumberGenerator::seeded(3);
+        let mut height_map = Preset::Classic
+            .pipeline()
+            .height_map(RectDimension::new(40, 40), &mut rng);
+        let before = height_map.clone();
+        let pole = Pole {
+            frozen_tiles: (0..40).map(|x| (x, 0)).collect(),
+        };
+        let cancel = Cancel::new();
+        cancel.clone().cancel();
+
+        for pass in Erosion::default().passes {
+            assert!(matches!(
+                pass.run(&mut height_map, 0., &pole, &mut rng, &cancel),
+                Err(WorldGenError::Cancelled)
+            ));
+        }
+        assert!(height_map.iter().eq(before.iter()));
+    }
+
+    #[test]
+    fn negative_sizes_are_invalid() {
+        let invalid = |pass| {
+            matches!(
+                Erosion { passes: vec![pass] }.validate(),
+                Err(WorldGenError::InvalidParam { .. })
+            )
+        };
+
+        assert!(invalid(Pass::Glacial {
+            reach: 12,
+            depth: -20.,
+        }));
+        assert!(invalid(Pass::Thermal {
+            talus: -1.,
+            strength: 0.5,
+            iterations: 8,
+        }));
+        assert!(invalid(Pass::Thermal {
+            talus: f64::NAN,
+            strength: 0.5,
+            iterations: 8,
+        }));
+        assert!(Erosion::default().validate().is_ok());
+    }
+
+    /// the biggest drop from any tile to one of its neighbours
+    fn steepest(height_map: &Grid<f64>) -> f64 {
+        height_map
+            .points()
+            .flat_map(|point| {
+                height_map
+                    .neighbours(point, Neighbours::Eight, Wrap::Never)
+                    .map(move |next| height_map[point] - height_map[next])
+            })
+            .fold(0., f64::max)
+    }
+
+    #[test]
+    fn steep_slopes_slide_down() {
+        // a spike sticking up out of a plain
+        let mut height_map = Grid::from_fn(RectDimension::new(9, 9), |x, y| {
+            if (x, y) == (4, 4) {
+                100.
+            } else {
+                0.
+            }
+        });
+        let total: f64 = height_map.iter().sum();
+
+        let mut worst = steepest(&height_map);
+        for _ in 0..20 {
+            thermal(&mut height_map, 6., 0.5);
+            let now = steepest(&height_map);
+            assert!(now < worst || now <= 6., "{now} after {worst}");
+            worst = now;
+        }
+        assert!(worst < 50.);
+        // nothing is lost, it just ends up lower down
+        assert!((height_map.iter().sum::<f64>() - total).abs() < 1e-9);
+
+        // and anything gentle enough is left alone
+        let gentle = Grid::from_fn(RectDimension::new(9, 9), |x, _| x as f64 * 5.);
+        let mut slid = gentle.clone();
+        thermal(&mut slid, 6., 0.5);
+        assert!(slid.iter().eq(gentle.iter()));
+    }
+
+    #[test]
+    fn ice_only_reaches_so_far() {
+        // a plateau with a strip of sea down the side, frozen along the top
+        let land = |x: usize| if x == 0 { -5. } else { 50. };
+        let mut height_map = Grid::from_fn(RectDimension::new(20, 20), |x, _| land(x));
+        let pole = Pole {
+            frozen_tiles: (0..20).map(|x| (x, 0)).collect(),
+        };
+
+        glacial(&mut height_map, 0., &pole, 5, 10.);
+        for ((x, y), height) in height_map.points().map(|p| (p, height_map[p])) {
+            if x == 0 || y >= 5 {
+                assert!((height - land(x)).abs() < f64::EPSILON, "({x}, {y})");
+            } else {
+                assert!(height < land(x), "({x}, {y})");
+            }
+        }
+        // worn down hardest right up against the ice
+        assert!(height_map[(10, 0)] < height_map[(10, 4)]);
+    }
+
+    #[test]
+    fn waves_flatten_beaches_and_drop_cliffs() {
+        // sea to the left, low ground at the top and high ground at the bottom
+        let before = Grid::from_fn(RectDimension::new(30, 40), |x, y| match (x, y) {
+            (0..10, _) => -10.,
+            (_, 0..20) => 3.,
+            _ => 50.,
+        });
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let mut height_map = before.clone();
+        coastal(&mut height_map, 0., 1., 10., 0., &mut rng);
+
+        let coast = |y: usize| height_map[(10, y)];
+        for y in 1..19 {
+            assert!(coast(y) < 3. && coast(y) > 0., "{}", coast(y));
+        }
+        assert!((21..40).all(|y| coast(y) <= 0. || coast(y) >= 50.));
+        assert!((21..40).any(|y| coast(y) <= 0.));
+        // inland is out of reach of the waves
+        assert!((11..30)
+            .all(|x| (0..40).all(|y| height_map[(x, y)].to_bits() == before[(x, y)].to_bits())));
+
+        // tiles left standing as stacks end up cut off from the rest of the land
+        let mut height_map = before.clone();
+        coastal(&mut height_map, 0., 1., 10., 0.3, &mut rng);
+        let mut mainland = vec![(29, 39)];
+        let mut reached = Grid::filled(height_map.dimensions(), false);
+        while let Some(point) = mainland.pop() {
+            if reached[point] || height_map[point] <= 0. {
+                continue;
+            }
+            reached[point] = true;
+            mainland.extend(height_map.neighbours(point, Neighbours::Eight, Wrap::Never));
+        }
+        assert!((21..40).any(|y| height_map[(10, y)] > 0. && !reached[(10, y)]));
+    }
+}
@@ -0,0 +1,1096 @@
+//! year-by-year history simulation for the civilizations of a world
+//!
+//! civilizations are placed as a single capital port, then every year they grow, expand along
+//! the coast, trade, make friends and go to war with each other. everything that happens is
+//! written down as a [`HistoricalEvent`] so it can be read back later.
+
+use bracket_lib::random::RandomNumberGenerator;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Distance, Point};
+
+use super::{names, FullWorld, GenContext, GenParam, WorldGenError};
+
+/// index into [`History::civilizations`]
+pub type CivId = usize;
+/// index into [`History::ports`]
+pub type PortId = usize;
+/// index into [`History::captains`]
+pub type CaptainId = usize;
+
+/// population of a freshly founded capital
+const CAPITAL_POPULATION: u32 = 500;
+/// population a single port can comfortably support
+const PORT_CAPACITY: u32 = 5000;
+/// a port has to be at least this big before it will send out settlers
+const EXPANSION_POPULATION: u32 = 2000;
+/// how many people leave with the settlers to found a new port
+const SETTLERS: u32 = 300;
+/// how far away from their home port settlers are willing to go
+const EXPANSION_RANGE: usize = 12;
+/// ports will never be founded closer than this to each other
+const MIN_PORT_DISTANCE: usize = 3;
+/// civilizations with ports this close to each other start getting on each others nerves
+const BORDER_DISTANCE: usize = 20;
+
+/// how two civilizations currently feel about each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Relation {
+    Peace,
+    War,
+    Alliance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Civilization {
+    pub name: String,
+    /// the port the civilization started from
+    pub capital: PortId,
+    pub founded: u32,
+    /// the year the civilization lost its last port
+    pub fallen: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Port {
+    pub name: String,
+    pub location: Point<usize>,
+    /// the civilization currently owning the port
+    pub owner: CivId,
+    pub population: u32,
+    pub founded: u32,
+    pub destroyed: Option<u32>,
+}
+
+impl Port {
+    pub fn is_alive(&self) -> bool {
+        self.destroyed.is_none()
+    }
+}
+
+/// a famous captain. not every sailor, only those worth telling stories about
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Captain {
+    pub name: String,
+    pub civ: CivId,
+    /// the year the captain first made a name for themselves
+    pub rose: u32,
+    pub died: Option<u32>,
+    /// raids and voyages the captain has taken part in
+    pub deeds: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EventKind {
+    CivilizationFounded {
+        civ: CivId,
+        capital: PortId,
+    },
+    CivilizationFell {
+        civ: CivId,
+    },
+    PortFounded {
+        civ: CivId,
+        port: PortId,
+        from: PortId,
+    },
+    PortCaptured {
+        port: PortId,
+        from: CivId,
+        by: CivId,
+        captain: Option<CaptainId>,
+    },
+    PortDestroyed {
+        port: PortId,
+        from: CivId,
+        by: CivId,
+        captain: Option<CaptainId>,
+    },
+    RaidRepelled {
+        port: PortId,
+        defender: CivId,
+        attacker: CivId,
+        captain: Option<CaptainId>,
+    },
+    Plague {
+        port: PortId,
+        civ: CivId,
+        dead: u32,
+    },
+    WarDeclared {
+        aggressor: CivId,
+        defender: CivId,
+    },
+    PeaceSigned {
+        a: CivId,
+        b: CivId,
+    },
+    AllianceFormed {
+        a: CivId,
+        b: CivId,
+    },
+    AllianceBroken {
+        a: CivId,
+        b: CivId,
+    },
+    TradeVoyage {
+        from: PortId,
+        from_civ: CivId,
+        to: PortId,
+        to_civ: CivId,
+        captain: Option<CaptainId>,
+    },
+    CaptainRose {
+        captain: CaptainId,
+    },
+    CaptainDied {
+        captain: CaptainId,
+    },
+    /// a fight at sea during play, between the player and someone else
+    SeaBattle {
+        location: Point<usize>,
+        /// whoever came out on top, or got away
+        victor: String,
+        vanquished: String,
+        /// who the player's opponent sailed for, if anyone
+        civ: Option<CivId>,
+        fate: Fate,
+    },
+}
+
+/// how a [`EventKind::SeaBattle`] ended, for the losing ship
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fate {
+    Sunk,
+    Boarded,
+    Struck,
+    /// the victor got away, and nobody really lost
+    Escaped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalEvent {
+    pub year: u32,
+    pub kind: EventKind,
+}
+
+impl EventKind {
+    /// every civilization taking part in the event
+    pub fn civs(&self) -> Vec<CivId> {
+        match *self {
+            EventKind::CivilizationFounded { civ, .. }
+            | EventKind::CivilizationFell { civ }
+            | EventKind::PortFounded { civ, .. }
+            | EventKind::Plague { civ, .. } => vec![civ],
+            EventKind::PortCaptured { from, by, .. }
+            | EventKind::PortDestroyed { from, by, .. } => {
+                vec![from, by]
+            }
+            EventKind::RaidRepelled {
+                defender, attacker, ..
+            } => vec![defender, attacker],
+            EventKind::WarDeclared {
+                aggressor,
+                defender,
+            } => vec![aggressor, defender],
+            EventKind::PeaceSigned { a, b }
+            | EventKind::AllianceFormed { a, b }
+            | EventKind::AllianceBroken { a, b } => vec![a, b],
+            EventKind::TradeVoyage {
+                from_civ, to_civ, ..
+            } => vec![from_civ, to_civ],
+            EventKind::SeaBattle { civ, .. } => civ.into_iter().collect(),
+            // captains are looked up through the history, they always belong to one civ
+            EventKind::CaptainRose { .. } | EventKind::CaptainDied { .. } => Vec::new(),
+        }
+    }
+
+    /// every port the event happened at
+    pub fn ports(&self) -> Vec<PortId> {
+        match *self {
+            EventKind::CivilizationFounded { capital, .. } => vec![capital],
+            EventKind::PortFounded { port, from, .. } => vec![port, from],
+            EventKind::PortCaptured { port, .. }
+            | EventKind::PortDestroyed { port, .. }
+            | EventKind::RaidRepelled { port, .. }
+            | EventKind::Plague { port, .. } => vec![port],
+            EventKind::TradeVoyage { from, to, .. } => vec![from, to],
+            _ => Vec::new(),
+        }
+    }
+
+    /// the famous captain involved, if there was one
+    pub fn captain(&self) -> Option<CaptainId> {
+        match *self {
+            EventKind::PortCaptured { captain, .. }
+            | EventKind::PortDestroyed { captain, .. }
+            | EventKind::RaidRepelled { captain, .. }
+            | EventKind::TradeVoyage { captain, .. } => captain,
+            EventKind::CaptainRose { captain } | EventKind::CaptainDied { captain } => {
+                Some(captain)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// the event log and final political state of a world
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    /// how many years were simulated
+    pub years: u32,
+    pub civilizations: Vec<Civilization>,
+    pub ports: Vec<Port>,
+    pub captains: Vec<Captain>,
+    /// every event, sorted by year
+    pub events: Vec<HistoricalEvent>,
+    /// relations[a][b], always symmetric
+    relations: Vec<Vec<Relation>>,
+    /// how much civ a likes civ b, -100 to 100. symmetric like relations
+    opinions: Vec<Vec<i32>>,
+}
+
+impl History {
+    pub fn relation(&self, a: CivId, b: CivId) -> Relation {
+        self.relations[a][b]
+    }
+
+    pub fn opinion(&self, a: CivId, b: CivId) -> i32 {
+        self.opinions[a][b]
+    }
+
+    pub fn is_alive(&self, civ: CivId) -> bool {
+        self.civilizations[civ].fallen.is_none()
+    }
+
+    /// whether `civ` is still around and fighting anyone who is
+    pub fn at_war(&self, civ: CivId) -> bool {
+        self.is_alive(civ)
+            && (0..self.civilizations.len())
+                .any(|other| self.is_alive(other) && self.relation(civ, other) == Relation::War)
+    }
+
+    /// all ports currently standing, along with their id
+    pub fn living_ports(&self) -> impl Iterator<Item = (PortId, &Port)> {
+        self.ports.iter().enumerate().filter(|(_, p)| p.is_alive())
+    }
+
+    /// every port currently owned by `civ`
+    pub fn ports_of(&self, civ: CivId) -> impl Iterator<Item = (PortId, &Port)> {
+        self.living_ports().filter(move |(_, p)| p.owner == civ)
+    }
+
+    pub fn population_of(&self, civ: CivId) -> u32 {
+        self.ports_of(civ).map(|(_, p)| p.population).sum()
+    }
+
+    /// whether `civ` took part in `event`, either directly or through one of its captains
+    pub fn involves_civ(&self, event: &HistoricalEvent, civ: CivId) -> bool {
+        event.kind.civs().contains(&civ)
+            || event
+                .kind
+                .captain()
+                .is_some_and(|c| self.captains[c].civ == civ)
+    }
+
+    /// where on the map the event took place, if it happened anywhere in particular
+    pub fn location_of(&self, event: &HistoricalEvent) -> Option<Point<usize>> {
+        if let EventKind::SeaBattle { location, .. } = event.kind {
+            return Some(location);
+        }
+
+        event
+            .kind
+            .ports()
+            .first()
+            .map(|&port| self.ports[port].location)
+    }
+
+    /// a single sentence telling what happened, without the year
+    // one arm for every kind of event, there's no making it shorter
+    #[allow(clippy::too_many_lines)]
+    pub fn describe(&self, event: &HistoricalEvent) -> String {
+        let civ = |c: CivId| self.civilizations[c].name.as_str();
+        let port = |p: PortId| self.ports[p].name.as_str();
+        // "captain x of the y" if a captain was involved, otherwise "the y"
+        let led = |captain: Option<CaptainId>, c: CivId| match captain {
+            Some(captain) => format!("{} of {}", self.captains[captain].name, civ(c)),
+            None => format!("the fleets of {}", civ(c)),
+        };
+
+        match event.kind {
+            EventKind::CivilizationFounded { civ: c, capital } => {
+                format!(
+                    "{} was founded, with its capital at {}",
+                    civ(c),
+                    port(capital)
+                )
+            }
+            EventKind::CivilizationFell { civ: c } => {
+                format!("{} lost its last port and fell", civ(c))
+            }
+            EventKind::PortFounded {
+                civ: c,
+                port: p,
+                from,
+            } => format!(
+                "settlers from {} founded {} for {}",
+                port(from),
+                port(p),
+                civ(c)
+            ),
+            EventKind::PortCaptured {
+                port: p,
+                from,
+                by,
+                captain,
+            } => format!(
+                "{} captured {} from {}",
+                led(captain, by),
+                port(p),
+                civ(from)
+            ),
+            EventKind::PortDestroyed {
+                port: p,
+                from,
+                by,
+                captain,
+            } => format!(
+                "{} burned {} of {} to the ground",
+                led(captain, by),
+                port(p),
+                civ(from)
+            ),
+            EventKind::RaidRepelled {
+                port: p,
+                defender,
+                attacker,
+                captain,
+            } => format!(
+                "{} of {} drove off a raid by {}",
+                port(p),
+                civ(defender),
+                led(captain, attacker)
+            ),
+            EventKind::Plague { port: p, dead, .. } => {
+                format!("plague struck {}, killing {}", port(p), dead)
+            }
+            EventKind::WarDeclared {
+                aggressor,
+                defender,
+            } => format!("{} declared war on {}", civ(aggressor), civ(defender)),
+            EventKind::PeaceSigned { a, b } => {
+                format!("{} and {} signed a peace", civ(a), civ(b))
+            }
+            EventKind::AllianceFormed { a, b } => {
+                format!("{} and {} formed an alliance", civ(a), civ(b))
+            }
+            EventKind::AllianceBroken { a, b } => {
+                format!("the alliance of {} and {} broke", civ(a), civ(b))
+            }
+            EventKind::TradeVoyage {
+                from,
+                from_civ,
+                to,
+                captain,
+                ..
+            } => format!(
+                "{} sailed from {} to {} to trade",
+                led(captain, from_civ),
+                port(from),
+                port(to)
+            ),
+            EventKind::CaptainRose { captain } => format!(
+                "{} made a name for themselves sailing for {}",
+                self.captains[captain].name,
+                civ(self.captains[captain].civ)
+            ),
+            EventKind::CaptainDied { captain } => format!(
+                "{} of {} died",
+                self.captains[captain].name,
+                civ(self.captains[captain].civ)
+            ),
+            EventKind::SeaBattle {
+                ref victor,
+                ref vanquished,
+                fate,
+                ..
+            } => match fate {
+                Fate::Sunk => format!("{victor} sank {vanquished} at sea"),
+                Fate::Boarded => format!("{victor} boarded and took {vanquished}"),
+                Fate::Struck => format!("{vanquished} struck her colours to {victor}"),
+                Fate::Escaped => format!("{victor} fought off {vanquished} and got away"),
+            },
+        }
+    }
+
+    /// add something that happened in `year`, which can't be before the last event already
+    /// recorded or the events end up out of order
+    pub(crate) fn push_event(&mut self, year: u32, kind: EventKind) {
+        self.events.push(HistoricalEvent { year, kind });
+    }
+
+    pub(crate) fn set_relation(&mut self, a: CivId, b: CivId, relation: Relation) {
+        self.relations[a][b] = relation;
+        self.relations[b][a] = relation;
+    }
+
+    pub fn change_opinion(&mut self, a: CivId, b: CivId, by: i32) {
+        let new = (self.opinions[a][b] + by).clamp(-100, 100);
+        self.opinions[a][b] = new;
+        self.opinions[b][a] = new;
+    }
+
+    fn add_port(
+        &mut self,
+        name: String,
+        location: Point<usize>,
+        owner: CivId,
+        year: u32,
+    ) -> PortId {
+        self.ports.push(Port {
+            name,
+            location,
+            owner,
+            population: 0,
+            founded: year,
+            destroyed: None,
+        });
+
+        self.ports.len() - 1
+    }
+
+    /// the shortest distance between any two ports of the two civilizations
+    fn border_distance(&self, a: CivId, b: CivId) -> Option<usize> {
+        self.ports_of(a)
+            .flat_map(|(_, pa)| {
+                self.ports_of(b)
+                    .map(|(_, pb)| tile_distance(pa.location, pb.location))
+            })
+            .min()
+    }
+
+    /// a living captain of `civ` to lead something, if there is one
+    fn pick_captain(&self, civ: CivId, rng: &mut RandomNumberGenerator) -> Option<CaptainId> {
+        let candidates: Vec<_> = (0..self.captains.len())
+            .filter(|&c| self.captains[c].civ == civ && self.captains[c].died.is_none())
+            .collect();
+
+        rng.random_slice_entry(&candidates).copied()
+    }
+
+    /// `captain` pulled off whatever they were leading
+    fn credit(&mut self, captain: Option<CaptainId>) {
+        if let Some(captain) = captain {
+            self.captains[captain].deeds += 1;
+        }
+    }
+}
+
+fn tile_distance(a: Point<usize>, b: Point<usize>) -> usize {
+    Distance::distance(a.0, b.0) + Distance::distance(a.1, b.1)
+}
+
+/// multiply a population by `by`, rounding down
+#[allow(clippy::cast_sign_loss)]
+fn scale(population: u32, by: f64) -> u32 {
+    (f64::from(population) * by.max(0.)) as u32
+}
+
+/// roll for something that should happen with roughly `chance` probability each year, scaled by
+/// how intense history is supposed to be
+/// how eventful history is, from `history_intensity`. 1 is normal
+pub(crate) fn eventfulness(params: &GenParam) -> f64 {
+    f64::from(params.history_intensity) / 128.
+}
+
+fn roll(rng: &mut RandomNumberGenerator, chance: f64, intensity: f64) -> bool {
+    rng.rand::<f64>() < chance * intensity
+}
+
+/// all land tiles touching the sea that aren't frozen
+fn coastal_tiles(world: &FullWorld) -> Vec<Point<usize>> {
+    world
+        .terrain
+        .coastal_tiles()
+        .into_iter()
+        .filter(|&(x, y)| !world.pole.is_frozen(x, y))
+        .collect()
+}
+
+/// a coastal tile a reasonable distance from `near` that is not too close to any other port
+fn find_port_site(
+    history: &History,
+    coast: &[Point<usize>],
+    near: Point<usize>,
+    rng: &mut RandomNumberGenerator,
+) -> Option<Point<usize>> {
+    let candidates: Vec<_> = coast
+        .iter()
+        .filter(|&&tile| tile_distance(tile, near) <= EXPANSION_RANGE)
+        .filter(|&&tile| {
+            history
+                .living_ports()
+                .all(|(_, p)| tile_distance(tile, p.location) >= MIN_PORT_DISTANCE)
+        })
+        .copied()
+        .collect();
+
+    rng.random_slice_entry(&candidates).copied()
+}
+
+/// place the capitals of up to `max_civilizations` civilizations on the coast, as far apart from
+/// each other as we can reasonably manage
+fn place_civilizations(
+    world: &FullWorld,
+    coast: &[Point<usize>],
+    rng: &mut RandomNumberGenerator,
+    max: u8,
+) -> History {
+    let mut history = History::default();
+    let dimensions = world.dimensions();
+    // try to keep civilizations spread out, but settle for closer if the world is small
+    let mut spacing = (dimensions.width as usize + dimensions.height as usize) / 4;
+
+    while history.civilizations.len() < max as usize && spacing >= MIN_PORT_DISTANCE {
+        let candidates: Vec<_> = coast
+            .iter()
+            .filter(|&&tile| {
+                history
+                    .ports
+                    .iter()
+                    .all(|p| tile_distance(tile, p.location) >= spacing)
+            })
+            .copied()
+            .collect();
+
+        match rng.random_slice_entry(&candidates) {
+            Some(&location) => {
+                let civ = history.civilizations.len();
+                let capital = history.add_port(names::port(rng), location, civ, 0);
+                history.ports[capital].population = CAPITAL_POPULATION;
+
+                history.civilizations.push(Civilization {
+                    name: names::civilization(rng),
+                    capital,
+                    founded: 0,
+                    fallen: None,
+                });
+                history.push_event(0, EventKind::CivilizationFounded { civ, capital });
+            }
+            None => spacing /= 2,
+        }
+    }
+
+    let civs = history.civilizations.len();
+    history.relations = vec![vec![Relation::Peace; civs]; civs];
+    history.opinions = vec![vec![0; civs]; civs];
+
+    history
+}
+
+/// ports grow towards their capacity, send out settlers and sometimes catch the plague
+fn grow(
+    history: &mut History,
+    coast: &[Point<usize>],
+    year: u32,
+    rng: &mut RandomNumberGenerator,
+    max_ports: usize,
+    intensity: f64,
+) {
+    for port in 0..history.ports.len() {
+        if !history.ports[port].is_alive() {
+            continue;
+        }
+
+        let population = history.ports[port].population;
+        let crowding = f64::from(population) / f64::from(PORT_CAPACITY);
+        history.ports[port].population = scale(population, 1. + 0.03 * (1. - crowding));
+
+        if roll(rng, 0.002, intensity) {
+            let dead = history.ports[port].population / rng.range(3, 6);
+            history.ports[port].population -= dead;
+            let civ = history.ports[port].owner;
+            history.push_event(year, EventKind::Plague { port, civ, dead });
+        }
+
+        let owner = history.ports[port].owner;
+        if history.ports[port].population >= EXPANSION_POPULATION
+            && history.living_ports().count() < max_ports
+            && roll(rng, 0.1, intensity)
+        {
+            let near = history.ports[port].location;
+
+            if let Some(site) = find_port_site(history, coast, near, rng) {
+                let new_port = history.add_port(names::port(rng), site, owner, year);
+                history.ports[new_port].population = SETTLERS;
+                history.ports[port].population -= SETTLERS;
+                history.push_event(
+                    year,
+                    EventKind::PortFounded {
+                        civ: owner,
+                        port: new_port,
+                        from: port,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// opinions drift and civilizations declare war, make peace and form alliances. `eventfulness` is
+/// how likely anything is to happen, as worked out by [`eventfulness`], and `years` is how much of
+/// a year goes by, up to 1
+pub(crate) fn diplomacy(
+    history: &mut History,
+    year: u32,
+    rng: &mut RandomNumberGenerator,
+    eventfulness: f64,
+    years: f64,
+) {
+    let civs = history.civilizations.len();
+    let intensity = eventfulness * years;
+
+    for a in 0..civs {
+        for b in (a + 1)..civs {
+            if !history.is_alive(a) || !history.is_alive(b) {
+                continue;
+            }
+
+            // neighbours squabble, everyone else slowly forgets whatever they thought of each other
+            let drift = match history.border_distance(a, b) {
+                Some(d) if d <= BORDER_DISTANCE => rng.range(-5, 2),
+                _ => rng.range(-2, 3) - history.opinion(a, b).signum(),
+            };
+            // a year's drift, over however much of one goes by
+            if roll(rng, 1., years) {
+                history.change_opinion(a, b, drift);
+            }
+
+            let opinion = history.opinions[a][b];
+            match history.relation(a, b) {
+                Relation::Peace if opinion < -40 && roll(rng, 0.2, intensity) => {
+                    // whoever is bigger feels brave enough to start it
+                    let (aggressor, defender) =
+                        if history.population_of(a) >= history.population_of(b) {
+                            (a, b)
+                        } else {
+                            (b, a)
+                        };
+                    history.set_relation(a, b, Relation::War);
+                    history.push_event(
+                        year,
+                        EventKind::WarDeclared {
+                            aggressor,
+                            defender,
+                        },
+                    );
+                }
+                Relation::Peace if opinion > 60 && roll(rng, 0.1, intensity) => {
+                    history.set_relation(a, b, Relation::Alliance);
+                    history.push_event(year, EventKind::AllianceFormed { a, b });
+                }
+                Relation::Alliance if opinion < 20 && roll(rng, 0.2, intensity) => {
+                    history.set_relation(a, b, Relation::Peace);
+                    history.push_event(year, EventKind::AllianceBroken { a, b });
+                }
+                // wars get tiring eventually, even for the people who like them
+                Relation::War if roll(rng, 0.05, years) => {
+                    history.change_opinion(a, b, 30);
+                    history.set_relation(a, b, Relation::Peace);
+                    history.push_event(year, EventKind::PeaceSigned { a, b });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// civilizations at war raid each other's ports
+fn war(history: &mut History, year: u32, rng: &mut RandomNumberGenerator, intensity: f64) {
+    let civs = history.civilizations.len();
+
+    for attacker in 0..civs {
+        for defender in 0..civs {
+            if attacker == defender
+                || history.relation(attacker, defender) != Relation::War
+                || !history.is_alive(attacker)
+                || !history.is_alive(defender)
+                || !roll(rng, 0.15, intensity)
+            {
+                continue;
+            }
+
+            // the closest enemy port to any of our ports gets hit
+            let Some((port, from_port)) = history
+                .ports_of(defender)
+                .flat_map(|(target, pt)| {
+                    history.ports_of(attacker).map(move |(from, pf)| {
+                        (tile_distance(pt.location, pf.location), target, from)
+                    })
+                })
+                .min()
+                .map(|(_, target, from)| (target, from))
+            else {
+                continue;
+            };
+
+            let captain = history.pick_captain(attacker, rng);
+            let attack = f64::from(history.ports[from_port].population)
+                * rng.range(0.5, 1.5)
+                * if captain.is_some() { 1.5 } else { 1. };
+            let defence = f64::from(history.ports[port].population) * rng.range(0.5, 1.5);
+
+            if attack > defence * 1.5 {
+                history.credit(captain);
+                history.ports[port].population /= 2;
+                history.ports[from_port].population =
+                    scale(history.ports[from_port].population, 0.9);
+
+                // small ports aren't worth keeping
+                if history.ports[port].population < SETTLERS {
+                    history.ports[port].destroyed = Some(year);
+                    history.ports[port].population = 0;
+                    history.push_event(
+                        year,
+                        EventKind::PortDestroyed {
+                            port,
+                            from: defender,
+                            by: attacker,
+                            captain,
+                        },
+                    );
+                } else {
+                    history.ports[port].owner = attacker;
+                    history.push_event(
+                        year,
+                        EventKind::PortCaptured {
+                            port,
+                            from: defender,
+                            by: attacker,
+                            captain,
+                        },
+                    );
+                }
+
+                if history.ports_of(defender).next().is_none() {
+                    history.civilizations[defender].fallen = Some(year);
+                    history.push_event(year, EventKind::CivilizationFell { civ: defender });
+                }
+            } else {
+                history.ports[from_port].population =
+                    scale(history.ports[from_port].population, 0.8);
+                history.ports[port].population = scale(history.ports[port].population, 0.95);
+                history.push_event(
+                    year,
+                    EventKind::RaidRepelled {
+                        port,
+                        defender,
+                        attacker,
+                        captain,
+                    },
+                );
+            }
+
+            history.change_opinion(attacker, defender, -10);
+        }
+    }
+}
+
+/// civilizations not at war with each other trade, which makes them like each other more
+fn trade(history: &mut History, year: u32, rng: &mut RandomNumberGenerator, intensity: f64) {
+    let civs = history.civilizations.len();
+
+    for a in 0..civs {
+        for b in 0..civs {
+            if a == b
+                || history.relation(a, b) == Relation::War
+                || !history.is_alive(a)
+                || !history.is_alive(b)
+                || !roll(rng, 0.05, intensity)
+            {
+                continue;
+            }
+
+            let ours: Vec<_> = history.ports_of(a).map(|(id, _)| id).collect();
+            let theirs: Vec<_> = history.ports_of(b).map(|(id, _)| id).collect();
+            let (Some(&from), Some(&to)) = (
+                rng.random_slice_entry(&ours),
+                rng.random_slice_entry(&theirs),
+            ) else {
+                continue;
+            };
+
+            let captain = history.pick_captain(a, rng);
+            history.credit(captain);
+            // trade makes everyone a little richer, and a little bigger
+            history.ports[from].population += history.ports[from].population / 50;
+            history.ports[to].population += history.ports[to].population / 50;
+            history.change_opinion(a, b, rng.range(1, 4));
+            history.push_event(
+                year,
+                EventKind::TradeVoyage {
+                    from,
+                    from_civ: a,
+                    to,
+                    to_civ: b,
+                    captain,
+                },
+            );
+        }
+    }
+}
+
+/// famous captains rise and die
+fn captains(history: &mut History, year: u32, rng: &mut RandomNumberGenerator, intensity: f64) {
+    for captain in 0..history.captains.len() {
+        if history.captains[captain].died.is_none() && roll(rng, 0.04, 1.) {
+            history.captains[captain].died = Some(year);
+            history.push_event(year, EventKind::CaptainDied { captain });
+        }
+    }
+
+    for civ in 0..history.civilizations.len() {
+        if history.is_alive(civ) && roll(rng, 0.03, intensity) {
+            history.captains.push(Captain {
+                name: names::captain(rng),
+                civ,
+                rose: year,
+                died: None,
+                deeds: 0,
+            });
+            let captain = history.captains.len() - 1;
+            history.push_event(year, EventKind::CaptainRose { captain });
+        }
+    }
+}
+
+/// place civilizations on `world` and simulate their history for [`GenParam::history_years`]
+///
+/// checks between years whether generation has been cancelled
+pub fn gen_history(world: &FullWorld, context: &mut GenContext) -> Result<History, WorldGenError> {
+    let params = context.params;
+    let mut rng = RandomNumberGenerator::seeded(context.rng.next_u64());
+    let intensity = eventfulness(params);
+    let coast = coastal_tiles(world);
+
+    let mut history = place_civilizations(world, &coast, &mut rng, params.max_civilizations);
+
+    info!(
+        "placed {} civilizations on {} coastal tiles",
+        history.civilizations.len(),
+        coast.len()
+    );
+
+    for year in 1..=params.history_years {
+        context.check()?;
+
+        grow(
+            &mut history,
+            &coast,
+            year,
+            &mut rng,
+            params.max_ports as usize,
+            intensity,
+        );
+        diplomacy(&mut history, year, &mut rng, intensity, 1.);
+        war(&mut history, year, &mut rng, intensity);
+        trade(&mut history, year, &mut rng, intensity);
+        captains(&mut history, year, &mut rng, intensity);
+    }
+
+    history.years = params.history_years;
+
+    debug!(
+        "simulated {} years of history, {} events, {} ports still standing",
+        history.years,
+        history.events.len(),
+        history.living_ports().count()
+    );
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{Cancel, RectDimension};
+    use crate::worldgen::{gen_full_world, GenParam};
+
+    use super::*;
+
+    fn history(seed: u64) -> History {
+        let params = GenParam {
+            seed,
+            world_size: RectDimension::new(80, 60),
+            max_civilizations: 6,
+            history_years: 120,
+            history_intensity: 255,
+            ..GenParam::default()
+        };
+
+        gen_full_world(&params, None, &Cancel::new())
+            .unwrap()
+            .history()
+            .clone()
+    }
+
+    #[test]
+    fn same_seed_same_history() {
+        let events = |history: &History| serde_json::to_string(&history.events).unwrap();
+
+        assert_eq!(events(&history(3)), events(&history(3)));
+        assert_ne!(events(&history(3)), events(&history(4)));
+    }
+
+    #[test]
+    fn destroyed_ports_stay_down() {
+        let mut destroyed = 0;
+
+        for seed in [1, 2, 3] {
+            let mut history = history(seed);
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            // everyone at everyone's throats for a good while longer, to knock more ports down
+            let civs = history.civilizations.len();
+            for a in 0..civs {
+                for b in (a + 1)..civs {
+                    history.set_relation(a, b, Relation::War);
+                }
+            }
+            for year in history.years + 1..history.years + 40 {
+                war(&mut history, year, &mut rng, 2.);
+                trade(&mut history, year, &mut rng, 2.);
+            }
+
+            for (id, port) in history.ports.iter().enumerate() {
+                let Some(year) = port.destroyed else {
+                    continue;
+                };
+                destroyed += 1;
+
+                let mut after = history
+                    .events
+                    .iter()
+                    .skip_while(|event| {
+                        !matches!(event.kind, EventKind::PortDestroyed { port, .. } if port == id)
+                    });
+                let Some(HistoricalEvent {
+                    year: when,
+                    kind: EventKind::PortDestroyed { from, .. },
+                }) = after.next()
+                else {
+                    panic!("port {id} was destroyed without it being recorded");
+                };
+                assert_eq!(*when, year);
+
+                // never taken, raided, traded with or settled from again
+                assert!(after.all(|event| !event.kind.ports().contains(&id)));
+                assert_eq!(port.owner, *from);
+                assert_eq!(port.population, 0);
+                assert!(!port.is_alive());
+                assert!(history
+                    .living_ports()
+                    .all(|(_, living)| living.location != port.location));
+            }
+        }
+
+        assert!(destroyed > 0);
+    }
+
+    #[test]
+    fn relations_stay_symmetric() {
+        let mut history = history(5);
+        let mut rng = RandomNumberGenerator::seeded(5);
+        // a few more seasons of play on top of what was generated
+        for year in history.years..history.years + 20 {
+            diplomacy(&mut history, year, &mut rng, 1., 0.25);
+        }
+
+        let civs = history.civilizations.len();
+        for a in 0..civs {
+            for b in 0..civs {
+                assert_eq!(history.relation(a, b), history.relation(b, a));
+                assert_eq!(history.opinion(a, b), history.opinion(b, a));
+            }
+        }
+    }
+
+    #[test]
+    fn four_seasons_make_a_year() {
+        let mut start = history(5);
+        let civs = start.civilizations.len();
+        for a in 0..civs {
+            for b in (a + 1)..civs {
+                start.set_relation(a, b, Relation::Peace);
+                start.change_opinion(a, b, 30 - start.opinion(a, b));
+            }
+        }
+        let mut at_war = start.clone();
+        for a in 0..civs {
+            for b in (a + 1)..civs {
+                at_war.set_relation(a, b, Relation::War);
+            }
+        }
+
+        // (total change in opinion, peace treaties), averaged over a lot of years
+        let average = |start: &History, seasons: u32| {
+            let (mut drift, mut peace) = (0., 0.);
+            for seed in 0..2000 {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                let mut history = start.clone();
+                for _ in 0..seasons {
+                    diplomacy(&mut history, 1, &mut rng, 1., 1. / f64::from(seasons));
+                }
+
+                for a in 0..civs {
+                    for b in (a + 1)..civs {
+                        drift += f64::from(history.opinion(a, b) - start.opinion(a, b));
+                    }
+                }
+                peace += history.events[start.events.len()..]
+                    .iter()
+                    .filter(|event| matches!(event.kind, EventKind::PeaceSigned { .. }))
+                    .count() as f64;
+            }
+
+            (drift / 2000., peace / 2000.)
+        };
+
+        let (year, seasons) = (average(&start, 1).0, average(&start, 4).0);
+        assert!(year < 0.);
+        assert!(
+            (seasons / year - 1.).abs() < 0.1,
+            "{seasons} against {year}"
+        );
+
+        let (year, seasons) = (average(&at_war, 1).1, average(&at_war, 4).1);
+        assert!(year > 0.);
+        assert!(
+            (seasons / year - 1.).abs() < 0.15,
+            "{seasons} against {year}"
+        );
+    }
+
+    #[test]
+    fn captains_only_get_credit_for_what_worked() {
+        for seed in [1, 2, 3] {
+            let history = history(seed);
+
+            for (id, captain) in history.captains.iter().enumerate() {
+                let deeds = history
+                    .events
+                    .iter()
+                    .filter(|event| match event.kind {
+                        EventKind::PortCaptured { captain, .. }
+                        | EventKind::PortDestroyed { captain, .. }
+                        | EventKind::TradeVoyage { captain, .. } => captain == Some(id),
+                        _ => false,
+                    })
+                    .count();
+
+                assert_eq!(captain.deeds as usize, deeds, "captain {id}");
+            }
+            assert!(history.captains.iter().any(|captain| captain.deeds > 0));
+        }
+    }
+}
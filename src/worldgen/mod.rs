@@ -1,32 +1,51 @@
 //TODO: remember to remove this later
 #![allow(unused)]
 
+pub mod climate;
+pub mod config;
+pub mod erosion;
+pub mod history;
+pub mod names;
+pub mod noise;
+pub mod tectonics;
 mod terrain;
+pub mod treasure;
 
 use log::{debug, error, info, log_enabled, trace, warn, Level};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::fmt::format;
-use std::sync::mpsc::{Receiver, Sender};
+use std::error::Error;
+use std::fmt::{format, Display};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::time::Duration;
 
-use bracket_lib::pathfinding::BaseMap;
-
-use bracket_lib::prelude::Algorithm2D;
-use bracket_lib::terminal::{Point, BLACK};
+use bracket_lib::terminal::{Point, BLACK, WHITE};
 use bracket_lib::{
     noise::{FastNoise, FractalType, NoiseType},
     random::RandomNumberGenerator,
 };
 
-use crate::helpers::{index_to_point, point_to_index, Distance};
-use crate::render::{self, Frame, RenderPacket, RenderTick, Tile, GUI};
+use crate::helpers::{Cancel, Distance, Grid};
+use crate::render::{
+    self, Camera, Frame, OffsetX, OffsetY, RenderError, RenderPacket, RenderTick, Tile, GUI,
+};
 
-use self::terrain::River;
+use self::climate::{Biome, Climate, Vector};
+use self::erosion::Erosion;
+use self::history::{History, PortId};
+use self::noise::NoisePipeline;
+use self::tectonics::Tectonics;
+use self::terrain::{River, SeaLevelError};
+use self::treasure::{Treasure, TreasureId};
 
 use super::helpers::RectDimension;
 
 pub use terrain::Map as TerrainMap;
 
+/// how much room the world gets on screen while it's being generated. bigger worlds are shrunk down
+/// to fit
+const PREVIEW_SIZE: RectDimension = RectDimension::new(100, 100);
+
 /// a Map + Civilizations, history, etc that is actually playable by a player
 pub struct Region {}
 
@@ -35,28 +54,207 @@ pub enum Poles {
     One,
     Two,
 }
+
+/// where the base height map of a world comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BaseTerrain {
+    /// layers of noise
+    Noise(NoisePipeline),
+    /// plates drifting into each other
+    Plates(Tectonics),
+}
+
+impl Default for BaseTerrain {
+    fn default() -> Self {
+        BaseTerrain::Noise(NoisePipeline::default())
+    }
+}
+/// why a world couldn't be generated
+#[derive(Debug)]
+pub enum WorldGenError {
+    /// `min_polar_tiles` is more than `max_polar_tiles`
+    PolarTilesInverted { min: u32, max: u32 },
+    /// the world isn't big enough to hold `min_polar_tiles`
+    TooManyPolarTiles { min: u32, area: usize },
+    /// `param` can't be what it is, for `reason`
+    InvalidParam { param: String, reason: String },
+    /// no sea level could be picked for the height map
+    SeaLevel(SeaLevelError),
+    /// the window showing generation went away partway through
+    Render(RenderError),
+    /// generation was asked to stop before it was done
+    Cancelled,
+}
+
+impl WorldGenError {
+    /// a [`WorldGenError::InvalidParam`]
+    pub fn invalid(param: impl Into<String>, reason: impl Into<String>) -> Self {
+        WorldGenError::InvalidParam {
+            param: param.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for WorldGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldGenError::PolarTilesInverted { min, max } => write!(
+                f,
+                "the minimum number of polar tiles ({min}) is more than the maximum ({max})"
+            ),
+            WorldGenError::TooManyPolarTiles { min, area } => write!(
+                f,
+                "a world of {area} tiles is too small for at least {min} polar tiles"
+            ),
+            WorldGenError::InvalidParam { param, reason } => write!(f, "{param} {reason}"),
+            WorldGenError::SeaLevel(e) => write!(f, "unable to decide a sea level, {e}"),
+            WorldGenError::Render(e) => write!(f, "unable to show world generation, {e}"),
+            WorldGenError::Cancelled => write!(f, "world generation was cancelled"),
+        }
+    }
+}
+
+impl Error for WorldGenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WorldGenError::SeaLevel(e) => Some(e),
+            WorldGenError::Render(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SeaLevelError> for WorldGenError {
+    fn from(e: SeaLevelError) -> Self {
+        WorldGenError::SeaLevel(e)
+    }
+}
+
+impl From<RenderError> for WorldGenError {
+    fn from(e: RenderError) -> Self {
+        WorldGenError::Render(e)
+    }
+}
+
 /// The parameters for generating a world
+///
+/// anything missing when these are read in is left at its default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GenParam {
     /// The seed to use during world generation
     pub seed: u64,
     /// one pole all around map
     // pub poles: Poles,
-    /// what percentage of the world should be water 1-100 with a unknown percent margin
-    // TODO: consider allowing setting for % water amt level variance
-    pub target_water: u8,
+    /// what percentage of the world should be under the sea, 0-100
+    pub target_water: f32,
+    /// if set, basins the sea doesn't reach fill with lakes up to the height that would put this
+    /// percentage of the world underwater. has to be above `target_water`
+    pub target_lakes: Option<f32>,
     /// the maximum amount of ports to generate. 0-255
     pub max_ports: u8,
     /// the maximum amount of civilizations to generate. 0-255 accepted, stick to 0-30
     pub max_civilizations: u8,
     /// the total size of the world
     pub world_size: RectDimension,
-    // the range of possible polar tiles
+    /// how the base height map is made
+    pub terrain: BaseTerrain,
+    /// how the base height map is worn down once it's made
+    pub erosion: Erosion,
+    /// the most tiles that can be frozen over at the poles
     pub max_polar_tiles: u32,
+    /// the fewest tiles that can be frozen over at the poles
     pub min_polar_tiles: u32,
+    /// how many years of history to simulate after civilizations are placed
+    pub history_years: u32,
+    /// how eventful history should be. 128 is "normal", 0 is nothing ever happens
+    pub history_intensity: u8,
+}
+
+impl Default for GenParam {
+    fn default() -> Self {
+        GenParam {
+            seed: 0,
+            target_water: 70.,
+            target_lakes: None,
+            max_ports: 20,
+            max_civilizations: 4,
+            world_size: RectDimension::new(100, 100),
+            terrain: BaseTerrain::default(),
+            erosion: Erosion::default(),
+            max_polar_tiles: 400,
+            min_polar_tiles: 280,
+            history_years: 250,
+            history_intensity: 128,
+        }
+    }
+}
+
+/// fine if `value` is a percentage, otherwise why it isn't
+fn check_percent(param: &str, value: f32) -> Result<(), WorldGenError> {
+    if (0. ..=100.).contains(&value) {
+        Ok(())
+    } else {
+        Err(WorldGenError::invalid(
+            param,
+            format!("should be a percentage from 0 to 100, not {value}"),
+        ))
+    }
+}
+
+impl GenParam {
+    /// check that a world can actually be made out of these parameters
+    pub fn validate(&self) -> Result<(), WorldGenError> {
+        check_percent("target_water", self.target_water)?;
+        if let Some(lakes) = self.target_lakes {
+            check_percent("target_lakes", lakes)?;
+            if lakes <= self.target_water {
+                return Err(WorldGenError::invalid(
+                    "target_lakes",
+                    format!(
+                        "should be above target_water ({}) or left out, not {lakes}",
+                        self.target_water
+                    ),
+                ));
+            }
+        }
+
+        if self.world_size.area() == 0 {
+            return Err(WorldGenError::invalid(
+                "world_size",
+                "should be at least 1 tile each way",
+            ));
+        }
+
+        if self.min_polar_tiles > self.max_polar_tiles {
+            return Err(WorldGenError::PolarTilesInverted {
+                min: self.min_polar_tiles,
+                max: self.max_polar_tiles,
+            });
+        }
+
+        let area = self.world_size.area();
+        if self.min_polar_tiles as usize > area {
+            return Err(WorldGenError::TooManyPolarTiles {
+                min: self.min_polar_tiles,
+                area,
+            });
+        }
+
+        match &self.terrain {
+            BaseTerrain::Noise(pipeline) => pipeline.validate()?,
+            BaseTerrain::Plates(tectonics) => tectonics.validate()?,
+        }
+        self.erosion.validate()?;
+
+        Ok(())
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 struct Pole {
-    frozen_tiles: Vec<(u8, u8)>,
+    frozen_tiles: Vec<(u16, u16)>,
 }
 
 impl Pole {
@@ -65,16 +263,50 @@ impl Pole {
             frozen_tiles: Vec::new(),
         }
     }
+
+    /// `frozen_tiles` is kept sorted, so this can be a binary search. pathfinding asks a lot
+    pub fn is_frozen(&self, x: usize, y: usize) -> bool {
+        let (Ok(x), Ok(y)) = (u16::try_from(x), u16::try_from(y)) else {
+            return false;
+        };
+
+        self.frozen_tiles.binary_search(&(x, y)).is_ok()
+    }
 }
 
 // todo: make this more interesting later
+#[derive(Serialize, Deserialize)]
 pub struct FullWorld {
     terrain: TerrainMap,
     rivers: Vec<River>,
-    pole: Pole, // ...
+    pole: Pole,
+    climate: Climate,
+    history: History, // ...
+    /// buried once history was done
+    treasures: Vec<Treasure>,
+    /// exactly what the world was made from, so it can be made again
+    params: GenParam,
 }
 
 impl FullWorld {
+    /// a world of nothing but `height_map` with the sea over it and weather on top, to try things
+    /// out on
+    #[cfg(test)]
+    pub fn from_height_map(height_map: Grid<f64>, params: &GenParam) -> Self {
+        let mut rng = RandomNumberGenerator::seeded(params.seed);
+        let cancel = Cancel::new();
+        let mut context = GenContext {
+            rng: &mut rng,
+            params,
+            cancel: &cancel,
+        };
+
+        let mut world = terrain::world_from_height_map(height_map, params).unwrap();
+        world.climate = climate::gen_climate(&world.terrain, &mut context);
+
+        world
+    }
+
     // mainly getters for various sub-elements
     pub fn dimensions(&self) -> RectDimension {
         self.terrain.dimensions
@@ -88,70 +320,190 @@ impl FullWorld {
     pub fn min_height(&self) -> f64 {
         self.terrain.min_height
     }
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    /// for recording what happens in play, after history has been generated
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+    pub fn terrain(&self) -> &TerrainMap {
+        &self.terrain
+    }
+    pub fn params(&self) -> &GenParam {
+        &self.params
+    }
+    pub fn treasures(&self) -> &[Treasure] {
+        &self.treasures
+    }
+    /// dig up treasure `id`, returning the gold if it was still there to be found
+    pub fn dig_up(&mut self, id: TreasureId) -> Option<u32> {
+        let treasure = self.treasures.get_mut(id).filter(|t| !t.found)?;
+        treasure.found = true;
+
+        Some(treasure.gold)
+    }
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        self.dimensions().contains(x, y)
+    }
+    pub fn is_frozen(&self, x: usize, y: usize) -> bool {
+        self.pole.is_frozen(x, y)
+    }
+    pub fn wind_at(&self, x: usize, y: usize) -> Vector {
+        self.climate.wind_at(x, y)
+    }
+    pub fn current_at(&self, x: usize, y: usize) -> Vector {
+        self.climate.current_at(x, y)
+    }
+    pub fn biome_at(&self, x: usize, y: usize) -> Biome {
+        self.climate.biome_at(x, y)
+    }
+    /// the standing port at (x, y), if there is one
+    pub fn port_at(&self, x: usize, y: usize) -> Option<PortId> {
+        self.history
+            .living_ports()
+            .find(|(_, port)| port.location == (x, y))
+            .map(|(id, _)| id)
+    }
+    /// whether a ship can be at (x, y). open water that isn't frozen over, or a port
+    pub fn is_navigable(&self, x: usize, y: usize) -> bool {
+        self.in_bounds(x, y)
+            && !self.is_frozen(x, y)
+            && (self.terrain.is_sea(x, y) || self.port_at(x, y).is_some())
+    }
+}
+
+const PORT_CHAR: char = 'o';
+
+/// build a frame of the stretch of the map `camera` can see, with every standing port drawn on top
+pub fn world_frame(world: &FullWorld, camera: Camera) -> Frame {
+    let tiles = Grid::from_fn(camera.view, |x, y| {
+        terrain_tile(world, world.terrain.height_map[camera.to_map((x, y))])
+    });
+    let mut frame = Frame { to_render: tiles };
+
+    for (_, port) in world.history.living_ports() {
+        frame.set_on_map(camera, port.location, Tile::new(PORT_CHAR, WHITE, BLACK));
+    }
+
+    frame
+}
+
+/// build a frame of the whole map shrunk down to fit in `space`, with every standing port drawn on
+/// top. a map that already fits is left the size it is
+pub fn world_overview(world: &FullWorld, space: RectDimension) -> Frame {
+    let dimensions = world.dimensions();
+    // every tile of the overview stands for a square this many tiles on a side
+    let scale = (dimensions.width.div_ceil(space.width.max(1)))
+        .max(dimensions.height.div_ceil(space.height.max(1)))
+        .max(1) as usize;
+    let shrunk = RectDimension::new(
+        dimensions.width.div_ceil(scale as u16),
+        dimensions.height.div_ceil(scale as u16),
+    );
+
+    let tiles = Grid::from_fn(shrunk, |x, y| {
+        terrain_tile(world, world.terrain.height_map[(x * scale, y * scale)])
+    });
+    let mut frame = Frame { to_render: tiles };
+
+    for (_, port) in world.history.living_ports() {
+        let (x, y) = port.location;
+        frame.set(x / scale, y / scale, Tile::new(PORT_CHAR, WHITE, BLACK));
+    }
+
+    frame
 }
 
-fn render_world(world: &FullWorld, sender: &mut Sender<RenderPacket>) -> () {
+/// how a tile of height `h` looks on the map
+fn terrain_tile(world: &FullWorld, h: f64) -> Tile {
     const MOUNTAIN_CHAR: char = '^';
     const LAND_CHAR: char = '-';
     const SEA_CHAR: char = '~';
 
     let is_sea = |h: f64| world.terrain.sea_level >= h;
 
-    let tiles: Vec<_> = Iterator::zip(world.terrain.height_map.iter(), 0..)
-        .map(|(&h, p)| {
-            let (x, y) = world.terrain.dimensions.index_to_point(p);
-
-            let mut rc = ' ';
-            let bg = BLACK;
-            let fg;
-
-            if is_sea(h) {
-                rc = SEA_CHAR;
-
-                fg = bracket_lib::color::RGBA::from_f32(
-                    0.,
-                    0.,
-                    (1.0 - Distance::distance(world.terrain.sea_level, h)
-                        / Distance::distance(world.terrain.min_height, world.terrain.sea_level))
-                        as f32,
-                    1.,
-                );
-            } else {
-                // TODO: figure out difference between mountain & land
-                let height = 0.5
-                    + Distance::distance(world.terrain.sea_level, h)
-                        / Distance::distance(world.terrain.max_height, world.terrain.sea_level);
-
-                rc = MOUNTAIN_CHAR;
-
-                fg = bracket_lib::color::RGBA::from_f32(1., 0.75, 0., height as f32);
-            }
+    let mut rc = ' ';
+    let bg = BLACK;
+    let fg;
+
+    if is_sea(h) {
+        rc = SEA_CHAR;
+
+        fg = bracket_lib::color::RGBA::from_f32(
+            0.,
+            0.,
+            (1.0 - Distance::distance(world.terrain.sea_level, h)
+                / Distance::distance(world.terrain.min_height, world.terrain.sea_level))
+                as f32,
+            1.,
+        );
+    } else {
+        // TODO: figure out difference between mountain & land
+        let height = 0.5
+            + Distance::distance(world.terrain.sea_level, h)
+                / Distance::distance(world.terrain.max_height, world.terrain.sea_level);
+
+        rc = MOUNTAIN_CHAR;
+
+        fg = bracket_lib::color::RGBA::from_f32(1., 0.75, 0., height as f32);
+    }
 
-            Tile::new(rc, fg, bg)
+    Tile::new(rc, fg, bg)
 
-            //TODO: implement rivers
-        })
-        .collect();
+    //TODO: implement rivers
+}
 
+fn render_world(world: &FullWorld, sender: &mut Sender<RenderPacket>) -> Result<(), RenderError> {
     // send tilemap
+    sender.send(RenderPacket::NewFrame(world_overview(world, PREVIEW_SIZE)))?;
 
-    match sender.send(RenderPacket::NewFrame(Frame {
-        dimensions: world.terrain.dimensions,
-        to_render: tiles,
-    })) {
-        Ok(_) => {}
-        Err(e) => {
-            error!("Unable to send message to render thread. Error: {}", e);
-        }
-    };
-
-    // todo!()
+    Ok(())
 }
 
 /// the mutable context necessary for all world generation functions
 pub struct GenContext<'a, 'b> {
     rng: &'a mut RandomNumberGenerator,
     params: &'b GenParam,
+    cancel: &'b Cancel,
+}
+
+impl GenContext<'_, '_> {
+    /// stop here with [`WorldGenError::Cancelled`] if generation has been cancelled
+    fn check(&self) -> Result<(), WorldGenError> {
+        stop_if_cancelled(self.cancel)
+    }
+}
+
+fn stop_if_cancelled(cancel: &Cancel) -> Result<(), WorldGenError> {
+    if cancel.is_cancelled() {
+        Err(WorldGenError::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// catch up on anything the renderer has said, cancelling generation if the window has gone away
+///
+/// keys pressed while the world is generating are dropped
+fn checkpoint(
+    context: &GenContext,
+    receiver: Option<&Receiver<RenderTick>>,
+) -> Result<(), WorldGenError> {
+    if let Some(receiver) = receiver {
+        loop {
+            match receiver.try_recv() {
+                Ok(RenderTick::LoopClosed) | Err(TryRecvError::Disconnected) => {
+                    context.cancel.cancel();
+                    break;
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+    }
+
+    context.check()
 }
 
 /// A function to generate a whole world, starting with terrain and geography and going all the way
@@ -159,109 +511,213 @@ pub struct GenContext<'a, 'b> {
 ///
 /// put Some(rx) for [render] in order to receive render-able worlds back during certain stages
 ///
+/// generation stops early with [`WorldGenError::Cancelled`] once `cancel` is set, or once the
+/// window closes if there is one. it's checked between stages and as it goes in the longer ones
+///
 /// this function coordinates the generation of worlds and the random number generation involved,
 /// allowing us to make deterministic worlds more easily.
 pub fn gen_full_world(
-    params: GenParam,
-    mut channels: Option<(Sender<RenderPacket>, Receiver<RenderTick>)>,
-) -> FullWorld {
+    params: &GenParam,
+    mut channels: Option<(&mut Sender<RenderPacket>, &Receiver<RenderTick>)>,
+    cancel: &Cancel,
+) -> Result<FullWorld, WorldGenError> {
+    params.validate()?;
+
     let mut rng = RandomNumberGenerator::seeded(params.seed);
     let mut context = GenContext {
         rng: &mut rng,
-        params: &params,
+        params,
+        cancel,
     };
-    let title_id;
+    let input = channels.as_ref().map(|(_, receiver)| *receiver);
+    let mut gui_ids = Vec::new();
 
     // add title and seed
 
-    match &mut channels {
-        Some((sender, receiver)) => {
-            // build title frame
-            // build seed frame
-            // register 2 frames
-            // wait for response
-            // continue?
-
-            // set up gui
-
-            // todo: update title as progresses
-            let title_frame = render::string_to_frame("Generating world!".into());
-            let seed_frame = render::string_to_frame(format!("Seed: {}", params.seed));
-
-            let title_gui = GUI {
-                offset: (0, 1),
-                to_render: title_frame,
-            };
-
-            let seed_gui = GUI {
-                offset: (0, -1),
-                to_render: seed_frame,
-            };
-
-            let gui_vec = vec![Some(title_gui), Some(seed_gui)];
-
-            sender.send(RenderPacket::RegisterGUIs(0, gui_vec));
-
-            title_id = receiver.recv_timeout(Duration::from_secs(3)).unwrap();
-        }
-        None => {}
+    if let Some((sender, receiver)) = &mut channels {
+        // build title frame
+        // build seed frame
+        // register 2 frames
+        // wait for response
+        // continue?
+
+        // set up gui
+
+        // todo: update title as progresses
+        let title_frame = render::string_to_frame("Generating world!");
+        let seed_frame = render::string_to_frame(&format!("Seed: {}", params.seed));
+
+        let title_gui = GUI {
+            offset: (OffsetX::Left(0), OffsetY::Above(1)),
+            to_render: Some(title_frame),
+        };
+
+        let seed_gui = GUI {
+            offset: (OffsetX::Left(0), OffsetY::Below(3)),
+            to_render: Some(seed_frame),
+        };
+
+        let gui_vec = vec![Some(title_gui), Some(seed_gui)];
+
+        sender
+            .send(RenderPacket::RegisterGUIs(0, gui_vec))
+            .map_err(RenderError::from)?;
+
+        // skip over any keys pressed while we wait
+        gui_ids = loop {
+            match receiver
+                .recv_timeout(Duration::from_secs(3))
+                .map_err(RenderError::from)?
+            {
+                RenderTick::RegisteredGUIs(_, ids) => break ids,
+                RenderTick::LoopClosed => {
+                    cancel.cancel();
+                    return Err(WorldGenError::Cancelled);
+                }
+                _ => {}
+            }
+        };
     }
 
+    checkpoint(&context, input)?;
+
     // generate the base terrain of the map
-    let mut base_map = terrain::gen_base_map(&mut context);
+    let mut base_map = match &params.terrain {
+        BaseTerrain::Noise(pipeline) => terrain::gen_base_map(pipeline, &mut context)?,
+        BaseTerrain::Plates(tectonics) => terrain::gen_plate_map(tectonics, &mut context)?,
+    };
 
     // fill in omni-present things like poles, etc here
 
+    checkpoint(&context, input)?;
     add_pole(&mut base_map, &mut context);
 
     // render base map
-    match &mut channels {
-        Some((sender, _)) => render_world(&base_map, sender),
-        None => {}
+    if let Some((sender, _)) = &mut channels {
+        render_world(&base_map, sender)?;
     }
 
-    let eroded_map = terrain::erode(&base_map, &mut rng);
+    checkpoint(&context, input)?;
+    base_map.terrain.height_map = terrain::erode(&base_map, &params.erosion, &mut context)?;
+    // erosion moved the coast and reshaped the basins, so the water has to be found all over again
+    base_map.terrain.flood(params)?;
+
+    checkpoint(&context, input)?;
+    base_map.climate = climate::gen_climate(&base_map.terrain, &mut context);
+
+    // civilizations and everything they got up to
+    checkpoint(&context, input)?;
+    base_map.history = history::gen_history(&base_map, &mut context)?;
+    checkpoint(&context, input)?;
+    base_map.treasures = treasure::gen_treasure(&base_map, &mut context);
+
+    // done generating, take the title and seed back down and show off the finished world
+    if let Some((sender, _)) = &mut channels {
+        for id in gui_ids {
+            sender
+                .send(RenderPacket::UpdateGUI { id, update: None })
+                .map_err(RenderError::from)?;
+        }
+
+        render_world(&base_map, sender)?;
+    }
 
-    todo!();
+    Ok(base_map)
 }
 
-fn add_pole(base_map: &mut FullWorld, context: &mut GenContext) -> () {
-    let mut polar_tiles = HashSet::<(u8, u8)>::new();
+/// freeze between `min_polar_tiles` and `max_polar_tiles` tiles over, in two ice caps spreading
+/// out from somewhere along the top and bottom edges of the map
+fn add_pole(base_map: &mut FullWorld, context: &mut GenContext) {
+    let mut polar_tiles = HashSet::<(u16, u16)>::new();
     let mut rng = RandomNumberGenerator::seeded(context.rng.next_u64());
+    let RectDimension { width, height } = base_map.dimensions();
+
+    let (min, max) = (
+        context.params.min_polar_tiles,
+        context.params.max_polar_tiles,
+    );
+    let wanted = (rng.range(min, max.saturating_add(1)) as usize).min(base_map.dimensions().area());
+
+    // how many rows in from the top or bottom a tile is
+    let depth = |(_, y): (u16, u16)| y.min(height - 1 - y);
+
+    let mut edge = vec![(rng.range(0, width), 0), (rng.range(0, width), height - 1)];
+    while polar_tiles.len() < wanted {
+        // of a few tiles along the edge of the ice, the one furthest north or south freezes over,
+        // so the caps spread along the edges of the map more than they reach in from them
+        let Some(i) = (0..3)
+            .filter(|_| !edge.is_empty())
+            .map(|_| rng.range(0, edge.len()))
+            .min_by_key(|&i| depth(edge[i]))
+        else {
+            break;
+        };
+        let (x, y) = edge.swap_remove(i);
+        if !polar_tiles.insert((x, y)) {
+            continue;
+        }
 
-    // surround edge of map
-
-    // top and bottom edge
-    for x in 0..base_map.dimensions().width {
-        let top_point = (x, 0);
-        let bottom_point = (x, base_map.dimensions().height);
-
-        polar_tiles.insert(top_point);
-        polar_tiles.insert(bottom_point);
-    }
-    for y in 0..base_map.dimensions().height {
-        let top_point = (0, y);
-        let bottom_point = (base_map.dimensions().width, y);
-
-        polar_tiles.insert(top_point);
-        polar_tiles.insert(bottom_point);
+        let around = [
+            x.checked_sub(1).map(|x| (x, y)),
+            (x + 1 < width).then_some((x + 1, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            (y + 1 < height).then_some((x, y + 1)),
+        ];
+        edge.extend(
+            around
+                .into_iter()
+                .flatten()
+                .filter(|tile| !polar_tiles.contains(tile)),
+        );
     }
 
-    // start on inner edge, roll for points
-    // to calculate do something like
-    // (#Surrounding polars) * mp + (closeness to edge) * mp - (closeness to max polar tiles) * mp > roll
-
-    let mut x_border = 1;
-    let mut y_border = 1;
-    while x_border < base_map.dimensions().width / 2 && y_border < base_map.dimensions().height / 2
-    {
-        // do stuff with border here
+    // sorted so the order doesn't depend on the hash set
+    let mut frozen_tiles: Vec<_> = polar_tiles.into_iter().collect();
+    frozen_tiles.sort_unstable();
+    base_map.pole.frozen_tiles = frozen_tiles;
+}
 
-        if x_border < base_map.dimensions().width / 2 {
-            x_border += 1;
-        }
-        if y_border < base_map.dimensions().height / 2 {
-            y_border += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ice_caps_keep_to_the_polar_tile_bounds() {
+        for (seed, (min, max)) in [(1, (280, 400)), (2, (0, 0)), (3, (50, 60)), (4, (900, 900))] {
+            let params = GenParam {
+                seed,
+                world_size: RectDimension::new(120, 40),
+                min_polar_tiles: min,
+                max_polar_tiles: max,
+                ..GenParam::default()
+            };
+            params.validate().unwrap();
+            let heights = Grid::filled(params.world_size, -10.);
+            let mut world = FullWorld::from_height_map(heights, &params);
+
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let cancel = Cancel::new();
+            add_pole(
+                &mut world,
+                &mut GenContext {
+                    rng: &mut rng,
+                    params: &params,
+                    cancel: &cancel,
+                },
+            );
+
+            let frozen = &world.pole.frozen_tiles;
+            assert!(
+                (min as usize..=max as usize).contains(&frozen.len()),
+                "{} frozen, wanted {min} to {max}",
+                frozen.len()
+            );
+            assert!(frozen.windows(2).all(|pair| pair[0] < pair[1]));
+            if max > 0 {
+                // both caps start out on the edge
+                assert!(frozen.iter().any(|&(_, y)| y == 0));
+                assert!(frozen.iter().any(|&(_, y)| y == 39));
+            }
         }
     }
 }
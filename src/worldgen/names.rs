@@ -0,0 +1,117 @@
+//! a very small syllable based name generator
+//!
+//! everything takes an rng so names stay deterministic for a given world seed
+
+use bracket_lib::random::RandomNumberGenerator;
+
+const ONSETS: [&str; 24] = [
+    "b", "br", "c", "d", "dr", "f", "g", "gr", "h", "k", "l", "m", "n", "p", "r", "s", "sh", "st",
+    "t", "th", "tr", "v", "w", "z",
+];
+const VOWELS: [&str; 10] = ["a", "e", "i", "o", "u", "ae", "ia", "ou", "ei", "y"];
+const CODAS: [&str; 12] = ["", "", "", "n", "r", "s", "l", "th", "rn", "st", "x", "m"];
+
+const CIV_SUFFIXES: [&str; 6] = ["ia", "and", "or", "ene", "ica", "ar"];
+const PORT_SUFFIXES: [&str; 8] = [
+    " Harbor", " Bay", " Cove", "port", "mouth", " Landing", "haven", " Point",
+];
+const CAPTAIN_EPITHETS: [&str; 10] = [
+    "the Red",
+    "the Bold",
+    "Blackbeard",
+    "the Drowned",
+    "Ironhand",
+    "the Lucky",
+    "One-Eye",
+    "the Merciless",
+    "Saltbeard",
+    "the Younger",
+];
+
+const SHIP_ADJECTIVES: [&str; 10] = [
+    "Black",
+    "Salty",
+    "Wandering",
+    "Crimson",
+    "Silent",
+    "Golden",
+    "Restless",
+    "Grey",
+    "Lucky",
+    "Howling",
+];
+const SHIP_NOUNS: [&str; 10] = [
+    "Gull",
+    "Serpent",
+    "Widow",
+    "Tide",
+    "Marauder",
+    "Kraken",
+    "Fortune",
+    "Albatross",
+    "Squall",
+    "Maiden",
+];
+
+/// pick a random entry from a non-empty constant slice
+fn pick<'a>(rng: &mut RandomNumberGenerator, from: &[&'a str]) -> &'a str {
+    rng.random_slice_entry(from).copied().unwrap_or_default()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// a single capitalized word made of between `min` and `max` (inclusive) syllables
+pub fn word(rng: &mut RandomNumberGenerator, min: i32, max: i32) -> String {
+    let syllables = rng.range(min, max + 1);
+    let mut word = String::new();
+
+    for _ in 0..syllables {
+        word.push_str(pick(rng, &ONSETS));
+        word.push_str(pick(rng, &VOWELS));
+    }
+    word.push_str(pick(rng, &CODAS));
+
+    capitalize(&word)
+}
+
+/// the name of a civilization, nation or people
+pub fn civilization(rng: &mut RandomNumberGenerator) -> String {
+    let mut name = word(rng, 1, 2);
+    name.push_str(pick(rng, &CIV_SUFFIXES));
+
+    name
+}
+
+/// the name of a port or settlement
+pub fn port(rng: &mut RandomNumberGenerator) -> String {
+    let mut name = word(rng, 1, 2);
+    name.push_str(pick(rng, &PORT_SUFFIXES));
+
+    name
+}
+
+/// a first and last name
+pub fn person(rng: &mut RandomNumberGenerator) -> String {
+    format!("{} {}", word(rng, 1, 2), word(rng, 2, 3))
+}
+
+/// a person's name, plus an epithet befitting a famous captain
+pub fn captain(rng: &mut RandomNumberGenerator) -> String {
+    format!("{} {}", word(rng, 1, 2), pick(rng, &CAPTAIN_EPITHETS))
+}
+
+/// the name of a ship
+pub fn ship(rng: &mut RandomNumberGenerator) -> String {
+    format!(
+        "the {} {}",
+        pick(rng, &SHIP_ADJECTIVES),
+        pick(rng, &SHIP_NOUNS)
+    )
+}
@@ -0,0 +1,510 @@
+//! the noise the base height map is built from
+//!
+//! a pipeline is a list of layers run one after another over every tile. most add noise to the
+//! height built up so far, a warp pushes around where the layers after it sample from, and masks
+//! shape whatever is already there. the presets are just pipelines put together ahead of time
+
+use std::fmt::Display;
+
+use bracket_lib::noise::{FastNoise, FractalType, NoiseType};
+use bracket_lib::random::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Grid, RectDimension};
+
+use super::WorldGenError;
+
+/// how many tiles across one unit of noise space is. both axes are sampled the same way, so land
+/// comes out the same size on any world and bigger worlds just have more of it
+const NOISE_TILES: f32 = 100.;
+/// pipelines give heights of around -1 to 1, which get stretched out to this
+const HEIGHT_SCALE: f64 = 200.;
+
+/// the kind of noise underneath a layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Basis {
+    Perlin,
+    Simplex,
+    Value,
+    Cubic,
+    /// blobs, like cracked mud
+    Cellular,
+}
+
+/// how octaves of noise are stacked on top of each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fractal {
+    /// a single octave, nothing stacked
+    Single,
+    /// rolling hills
+    Fbm,
+    /// puffy and rounded
+    Billow,
+    /// sharp ridges, for mountain ranges
+    Ridged,
+}
+
+/// one source of noise, sampled somewhere between -1 and 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Noise {
+    pub basis: Basis,
+    pub fractal: Fractal,
+    /// ignored for [`Fractal::Single`] and cellular noise
+    pub octaves: u8,
+    /// how many features fit in a unit of noise space. higher is smaller and busier
+    pub frequency: f32,
+    /// how much each octave counts compared to the one before it
+    pub gain: f32,
+    /// how much finer each octave is than the one before it
+    pub lacunarity: f32,
+}
+
+impl Noise {
+    /// fractal noise with the usual gain and lacunarity
+    pub fn fractal(basis: Basis, fractal: Fractal, octaves: u8, frequency: f32) -> Self {
+        Noise {
+            basis,
+            fractal,
+            octaves,
+            frequency,
+            gain: 0.5,
+            lacunarity: 2.,
+        }
+    }
+
+    fn build(&self, seed: u64) -> FastNoise {
+        let mut noise = FastNoise::seeded(seed);
+        let fractal = self.fractal != Fractal::Single;
+
+        noise.set_noise_type(match self.basis {
+            Basis::Perlin if fractal => NoiseType::PerlinFractal,
+            Basis::Perlin => NoiseType::Perlin,
+            Basis::Simplex if fractal => NoiseType::SimplexFractal,
+            Basis::Simplex => NoiseType::Simplex,
+            Basis::Value if fractal => NoiseType::ValueFractal,
+            Basis::Value => NoiseType::Value,
+            Basis::Cubic if fractal => NoiseType::CubicFractal,
+            Basis::Cubic => NoiseType::Cubic,
+            Basis::Cellular => NoiseType::Cellular,
+        });
+        noise.set_fractal_type(match self.fractal {
+            Fractal::Single | Fractal::Fbm => FractalType::FBM,
+            Fractal::Billow => FractalType::Billow,
+            Fractal::Ridged => FractalType::RigidMulti,
+        });
+        noise.set_fractal_octaves(i32::from(self.octaves.max(1)));
+        noise.set_fractal_gain(self.gain);
+        noise.set_fractal_lacunarity(self.lacunarity);
+        noise.set_frequency(self.frequency);
+
+        noise
+    }
+}
+
+/// a step of a [`NoisePipeline`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Layer {
+    /// add `noise` times `weight` to the height
+    Add { noise: Noise, weight: f32 },
+    /// add `noise` times `weight`, but only as much as the height so far is above 0, so ranges run
+    /// through the middle of the land rather than out at sea. meant for ridged noise
+    Mountains { noise: Noise, weight: f32 },
+    /// push where every layer after this one samples from by up to `strength` tiles, for twistier
+    /// coasts
+    Warp { noise: Noise, strength: f32 },
+    /// sink everything outside of big blobs of `noise` above `threshold` down to the sea floor.
+    /// `sharpness` is how quickly the blobs' edges drop away
+    Continents {
+        noise: Noise,
+        threshold: f32,
+        sharpness: f32,
+    },
+    /// drag heights down to `depth` at the edges of the map, fading out `width` of the way in
+    /// (as a fraction of the shorter side), so land doesn't run off the edge
+    Falloff { width: f32, depth: f32 },
+}
+
+/// the pipelines that come ready made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Preset {
+    /// a single layer of rolling noise, like worlds have always had
+    #[default]
+    Classic,
+    /// scattered islands, big and small
+    Archipelago,
+    /// one great landmass in the middle of the sea
+    Pangaea,
+    /// a handful of big continents with mountain ranges
+    Continents,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::Classic,
+        Preset::Archipelago,
+        Preset::Pangaea,
+        Preset::Continents,
+    ];
+
+    /// the preset going by `name`, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.to_string().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn pipeline(self) -> NoisePipeline {
+        use Basis::{Cellular, Perlin, Simplex};
+        use Fractal::{Fbm, Ridged};
+
+        let layers = match self {
+            Preset::Classic => vec![Layer::Add {
+                noise: Noise {
+                    basis: Perlin,
+                    fractal: Fbm,
+                    octaves: 10,
+                    frequency: 2.,
+                    gain: 0.5,
+                    lacunarity: 3.,
+                },
+                weight: 1.,
+            }],
+            Preset::Archipelago => vec![
+                Layer::Warp {
+                    noise: Noise::fractal(Simplex, Fbm, 3, 2.),
+                    strength: 4.,
+                },
+                Layer::Add {
+                    noise: Noise::fractal(Perlin, Fbm, 6, 5.),
+                    weight: 1.,
+                },
+                // islands come in clusters, with open sea between them
+                Layer::Add {
+                    noise: Noise::fractal(Cellular, Fbm, 1, 1.5),
+                    weight: 0.3,
+                },
+                Layer::Falloff {
+                    width: 0.05,
+                    depth: 1.,
+                },
+            ],
+            Preset::Pangaea => vec![
+                Layer::Warp {
+                    noise: Noise::fractal(Simplex, Fbm, 3, 1.),
+                    strength: 10.,
+                },
+                Layer::Add {
+                    noise: Noise::fractal(Perlin, Fbm, 8, 1.5),
+                    weight: 0.6,
+                },
+                Layer::Mountains {
+                    noise: Noise::fractal(Simplex, Ridged, 4, 2.5),
+                    weight: 0.6,
+                },
+                // reaching all the way to the middle, so there's only room for one landmass
+                Layer::Falloff {
+                    width: 0.5,
+                    depth: 1.5,
+                },
+            ],
+            Preset::Continents => vec![
+                Layer::Warp {
+                    noise: Noise::fractal(Simplex, Fbm, 3, 1.5),
+                    strength: 8.,
+                },
+                Layer::Add {
+                    noise: Noise::fractal(Perlin, Fbm, 8, 2.),
+                    weight: 1.,
+                },
+                Layer::Mountains {
+                    noise: Noise::fractal(Simplex, Ridged, 4, 3.),
+                    weight: 0.8,
+                },
+                Layer::Continents {
+                    noise: Noise::fractal(Perlin, Fbm, 3, 0.7),
+                    threshold: -0.05,
+                    sharpness: 6.,
+                },
+                Layer::Falloff {
+                    width: 0.08,
+                    depth: 1.,
+                },
+            ],
+        };
+
+        NoisePipeline { layers }
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Preset::Classic => "classic",
+            Preset::Archipelago => "archipelago",
+            Preset::Pangaea => "pangaea",
+            Preset::Continents => "continents",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// how to make the base height map of a world, layer by layer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoisePipeline {
+    pub layers: Vec<Layer>,
+}
+
+impl Default for NoisePipeline {
+    fn default() -> Self {
+        Preset::default().pipeline()
+    }
+}
+
+/// a layer with its noise seeded and ready to sample
+enum Built {
+    Add(FastNoise, f32),
+    Mountains(FastNoise, f32),
+    Warp(FastNoise, FastNoise, f32),
+    Continents(FastNoise, f32, f32),
+    Falloff(f32, f32),
+}
+
+/// 0 below `edge0`, 1 above `edge1` and a smooth curve in between
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+
+    t * t * (3. - 2. * t)
+}
+
+impl Noise {
+    fn validate(&self, param: &str) -> Result<(), WorldGenError> {
+        for (name, value) in [
+            ("frequency", self.frequency),
+            ("gain", self.gain),
+            ("lacunarity", self.lacunarity),
+        ] {
+            if !value.is_finite() || value <= 0. {
+                return Err(WorldGenError::invalid(
+                    format!("{param} {name}"),
+                    format!("should be above 0, not {value}"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// an error unless `value` is an actual number
+fn finite(param: &str, name: &str, value: f32) -> Result<(), WorldGenError> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(WorldGenError::invalid(
+            format!("{param} {name}"),
+            format!("should be a number, not {value}"),
+        ))
+    }
+}
+
+impl NoisePipeline {
+    /// check every layer could make a height map
+    pub fn validate(&self) -> Result<(), WorldGenError> {
+        for (i, layer) in self.layers.iter().enumerate() {
+            let param = format!("terrain layer {}", i + 1);
+
+            match layer {
+                Layer::Add { noise, weight } | Layer::Mountains { noise, weight } => {
+                    noise.validate(&param)?;
+                    finite(&param, "weight", *weight)?;
+                }
+                Layer::Warp { noise, strength } => {
+                    noise.validate(&param)?;
+                    finite(&param, "strength", *strength)?;
+                }
+                Layer::Continents {
+                    noise,
+                    threshold,
+                    sharpness,
+                } => {
+                    noise.validate(&param)?;
+                    finite(&param, "threshold", *threshold)?;
+                    if !(sharpness.is_finite() && *sharpness > 0.) {
+                        return Err(WorldGenError::invalid(
+                            format!("{param} sharpness"),
+                            format!("should be above 0, not {sharpness}"),
+                        ));
+                    }
+                }
+                Layer::Falloff { width, depth } => {
+                    if !(*width > 0. && *width <= 0.5) {
+                        return Err(WorldGenError::invalid(
+                            format!("{param} width"),
+                            format!("should be above 0 and at most 0.5, not {width}"),
+                        ));
+                    }
+                    finite(&param, "depth", *depth)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// run every layer over every tile of a world of `dimensions`
+    pub fn height_map(
+        &self,
+        dimensions: RectDimension,
+        rng: &mut RandomNumberGenerator,
+    ) -> Grid<f64> {
+        // every layer is seeded up front, in order, so the same seed always makes the same map
+        let built: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| match layer {
+                Layer::Add { noise, weight } => Built::Add(noise.build(rng.next_u64()), *weight),
+                Layer::Mountains { noise, weight } => {
+                    Built::Mountains(noise.build(rng.next_u64()), *weight)
+                }
+                Layer::Warp { noise, strength } => Built::Warp(
+                    noise.build(rng.next_u64()),
+                    noise.build(rng.next_u64()),
+                    *strength / NOISE_TILES,
+                ),
+                Layer::Continents {
+                    noise,
+                    threshold,
+                    sharpness,
+                } => Built::Continents(noise.build(rng.next_u64()), *threshold, *sharpness),
+                Layer::Falloff { width, depth } => Built::Falloff(*width, *depth),
+            })
+            .collect();
+
+        let shorter = f32::from(dimensions.width.min(dimensions.height)).max(1.);
+
+        Grid::par_from_fn(dimensions, |x, y| {
+            let (mut sx, mut sy) = ((x + 1) as f32 / NOISE_TILES, (y + 1) as f32 / NOISE_TILES);
+            let mut height = 0.;
+
+            for layer in &built {
+                match layer {
+                    Built::Add(noise, weight) => height += noise.get_noise(sx, sy) * weight,
+                    Built::Mountains(noise, weight) => {
+                        let ridges = noise.get_noise(sx, sy) * 0.5 + 0.5;
+                        height += ridges * weight * height.clamp(0., 1.);
+                    }
+                    Built::Warp(along, down, strength) => {
+                        let (dx, dy) = (along.get_noise(sx, sy), down.get_noise(sx, sy));
+                        sx += dx * strength;
+                        sy += dy * strength;
+                    }
+                    Built::Continents(noise, threshold, sharpness) => {
+                        let land = smoothstep(
+                            0.,
+                            1.,
+                            (noise.get_noise(sx, sy) - threshold) * sharpness + 0.5,
+                        );
+                        height = height * land + (land - 1.);
+                    }
+                    Built::Falloff(width, depth) => {
+                        let edge = x
+                            .min(y)
+                            .min(dimensions.width as usize - 1 - x)
+                            .min(dimensions.height as usize - 1 - y);
+                        let inland = smoothstep(0., *width, edge as f32 / shorter);
+                        height = height * inland - depth * (1. - inland);
+                    }
+                }
+            }
+
+            f64::from(height) * HEIGHT_SCALE
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_go_by_name() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(&preset.to_string()), Some(preset));
+        }
+        assert_eq!(Preset::from_name(" Pangaea "), Some(Preset::Pangaea));
+        assert_eq!(Preset::from_name("ARCHIPELAGO"), Some(Preset::Archipelago));
+        assert_eq!(Preset::from_name("atlantis"), None);
+        assert_eq!(Preset::from_name(""), None);
+    }
+
+    #[test]
+    fn broken_layers_are_invalid() {
+        let invalid = |layer: Layer| {
+            matches!(
+                NoisePipeline {
+                    layers: vec![layer]
+                }
+                .validate(),
+                Err(WorldGenError::InvalidParam { .. })
+            )
+        };
+        let noise = || Noise::fractal(Basis::Perlin, Fractal::Fbm, 3, 1.);
+
+        assert!(invalid(Layer::Add {
+            noise: Noise {
+                frequency: 0.,
+                ..noise()
+            },
+            weight: 1.,
+        }));
+        assert!(invalid(Layer::Mountains {
+            noise: noise(),
+            weight: f32::NAN,
+        }));
+        assert!(invalid(Layer::Warp {
+            noise: noise(),
+            strength: f32::INFINITY,
+        }));
+        assert!(invalid(Layer::Continents {
+            noise: noise(),
+            threshold: f32::NAN,
+            sharpness: 6.,
+        }));
+        assert!(invalid(Layer::Continents {
+            noise: noise(),
+            threshold: 0.,
+            sharpness: f32::NAN,
+        }));
+        assert!(invalid(Layer::Continents {
+            noise: noise(),
+            threshold: 0.,
+            sharpness: -1.,
+        }));
+        assert!(invalid(Layer::Falloff {
+            width: 0.6,
+            depth: 1.,
+        }));
+        assert!(invalid(Layer::Falloff {
+            width: 0.1,
+            depth: f32::NAN,
+        }));
+    }
+
+    #[test]
+    fn presets_make_finite_heights_on_any_shape() {
+        for preset in Preset::ALL {
+            let pipeline = preset.pipeline();
+            assert!(pipeline.validate().is_ok(), "{preset} is invalid");
+
+            let dimensions = RectDimension::new(90, 35);
+            let map = pipeline.height_map(dimensions, &mut RandomNumberGenerator::seeded(4));
+            assert_eq!(map.dimensions(), dimensions);
+            assert!(
+                map.iter().all(|height| height.is_finite()),
+                "{preset} made a height that isn't a number"
+            );
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! base terrain made by plate tectonics rather than straight noise
+//!
+//! the world is split into plates, each either continental crust that sits high or oceanic crust
+//! that sits low, and each drifting its own way. where plates grind into each other the crust
+//! piles up into mountain ranges or dives into trenches, and where they pull apart it splits into
+//! rifts and ridges. a little noise over the top keeps it from looking too neat
+//!
+//! the plates never actually move. the map is a single snapshot, and a plate's velocity only
+//! decides what each of its boundaries turns into, not where the boundaries are
+
+use std::collections::VecDeque;
+
+use bracket_lib::noise::{FastNoise, FractalType, NoiseType};
+use bracket_lib::random::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Grid, Neighbours, Point, RectDimension, Wrap};
+
+use super::WorldGenError;
+
+/// how many tiles across one unit of noise space is, for the detail noise
+const NOISE_TILES: f32 = 100.;
+/// heights come out around -1 to 1, which get stretched out to this. the same as noise pipelines
+const HEIGHT_SCALE: f64 = 200.;
+/// how high continental crust sits, before anything happens to it
+const CONTINENT_HEIGHT: f32 = 0.35;
+/// how low oceanic crust sits, before anything happens to it
+const OCEAN_HEIGHT: f32 = -0.5;
+/// plates closing or opening slower than this are just sliding past each other, and leave the
+/// boundary alone
+const MIN_STRESS: f32 = 0.1;
+
+/// how to make the base height map of a world out of plates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tectonics {
+    /// how many plates the world is split into
+    pub plates: u8,
+    /// roughly what fraction of plates carry continents, 0-1
+    pub continental: f32,
+    /// how high the tallest mountain ranges get
+    pub mountain_height: f32,
+    /// how deep rifts and trenches get
+    pub rift_depth: f32,
+    /// how many tiles in from a boundary mountains, rifts and island arcs reach
+    pub boundary_width: f32,
+    /// how much noise goes over the top, for coastlines and hills
+    pub roughness: f32,
+}
+
+impl Default for Tectonics {
+    fn default() -> Self {
+        Tectonics {
+            plates: 12,
+            continental: 0.4,
+            mountain_height: 0.8,
+            rift_depth: 0.5,
+            boundary_width: 14.,
+            roughness: 0.4,
+        }
+    }
+}
+
+/// one plate of crust
+struct Plate {
+    /// where the plate grew out from
+    seed: (f32, f32),
+    /// which way and how fast it's drifting, about 1 at most
+    velocity: (f32, f32),
+    continental: bool,
+    /// how heavy the crust is. when two oceanic plates meet, the heavier one dives under the other
+    density: f32,
+    /// how high the plate's crust sits away from any boundary
+    height: f32,
+}
+
+/// what happens to the crust on one side of a boundary
+#[derive(Debug, Clone, Copy)]
+enum Boundary {
+    /// crust piling up, highest right at the boundary
+    Mountains(f32),
+    /// crust dragged down, only right at the boundary
+    Trench(f32),
+    /// a chain of volcanic islands a little way back from the boundary
+    Arc(f32),
+    /// crust pulled apart into a valley, or a new sea if it goes deep enough
+    Rift(f32),
+    /// new crust welling up along the sea floor
+    Ridge(f32),
+}
+
+impl Boundary {
+    /// how much the boundary moves a tile `distance` tiles in from it by
+    fn height(self, distance: f32, width: f32) -> f32 {
+        // 1 at the boundary, falling away smoothly to 0 at `width`
+        let bell = |distance: f32, width: f32| {
+            let t = (distance / width).min(1.);
+            (1. - t * t) * (1. - t * t)
+        };
+
+        match self {
+            Boundary::Mountains(strength) => strength * bell(distance, width),
+            Boundary::Trench(strength) => -strength * bell(distance, width * 0.3),
+            Boundary::Arc(strength) => strength * bell((distance - width * 0.5).abs(), width * 0.3),
+            Boundary::Rift(strength) => -strength * bell(distance, width * 0.5),
+            Boundary::Ridge(strength) => strength * bell(distance, width * 0.4),
+        }
+    }
+}
+
+/// a fractal noise generator of `noise_type` with `octaves` octaves
+fn fractal(seed: u64, noise_type: NoiseType, octaves: i32, frequency: f32) -> FastNoise {
+    let mut noise = FastNoise::seeded(seed);
+
+    noise.set_noise_type(noise_type);
+    noise.set_fractal_type(FractalType::FBM);
+    noise.set_fractal_octaves(octaves);
+    noise.set_fractal_gain(0.5);
+    noise.set_fractal_lacunarity(2.);
+    noise.set_frequency(frequency);
+
+    noise
+}
+
+impl Tectonics {
+    /// check a world could be split up this way
+    pub fn validate(&self) -> Result<(), WorldGenError> {
+        if self.plates == 0 {
+            return Err(WorldGenError::invalid("plates", "should be at least 1"));
+        }
+        if !(0. ..=1.).contains(&self.continental) {
+            return Err(WorldGenError::invalid(
+                "continental",
+                format!("should be from 0 to 1, not {}", self.continental),
+            ));
+        }
+        if !(self.boundary_width > 0. && self.boundary_width.is_finite()) {
+            return Err(WorldGenError::invalid(
+                "boundary_width",
+                format!("should be above 0, not {}", self.boundary_width),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// split a world of `dimensions` into plates, work out where they push into each other and
+    /// give back the height map that leaves behind
+    pub fn height_map(
+        &self,
+        dimensions: RectDimension,
+        rng: &mut RandomNumberGenerator,
+    ) -> Grid<f64> {
+        let (width, height) = (f32::from(dimensions.width), f32::from(dimensions.height));
+        let plates = self.seed_plates(usize::from(self.plates.max(1)), width, height, rng);
+
+        self.raise(&plates, dimensions, rng)
+    }
+
+    /// the height map left behind by `plates`
+    fn raise(
+        &self,
+        plates: &[Plate],
+        dimensions: RectDimension,
+        rng: &mut RandomNumberGenerator,
+    ) -> Grid<f64> {
+        let (width, height) = (f32::from(dimensions.width), f32::from(dimensions.height));
+        // roughly how far apart plates are, so the shapes come out the same on any size of world
+        let spacing = (width * height / plates.len() as f32).sqrt();
+
+        // everything is seeded up front so the same seed always makes the same map
+        let warp_x = fractal(rng.next_u64(), NoiseType::SimplexFractal, 3, 1.5);
+        let warp_y = fractal(rng.next_u64(), NoiseType::SimplexFractal, 3, 1.5);
+        let detail = fractal(rng.next_u64(), NoiseType::PerlinFractal, 6, 2.);
+        let islands = fractal(rng.next_u64(), NoiseType::SimplexFractal, 2, 8.);
+
+        // every tile belongs to the nearest plate, measured from somewhere a little off from the
+        // tile so the boundaries wander rather than running dead straight
+        let owner = Grid::par_from_fn(dimensions, |x, y| {
+            let (sx, sy) = (x as f32 / spacing, y as f32 / spacing);
+            let px = x as f32 + warp_x.get_noise(sx, sy) * spacing * 0.4;
+            let py = y as f32 + warp_y.get_noise(sx, sy) * spacing * 0.4;
+
+            plates
+                .iter()
+                .enumerate()
+                .map(|(i, plate)| {
+                    let (dx, dy) = (plate.seed.0 - px, plate.seed.1 - py);
+                    (i, dx * dx + dy * dy)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(0, |(i, _)| i)
+        });
+
+        let boundaries = self.drift(plates, &owner);
+
+        // the crust itself, softened so continents slope down into the sea rather than dropping
+        // off a cliff
+        let crust = owner.map(|&plate| plates[plate].height);
+        #[allow(clippy::cast_sign_loss)]
+        let crust = blur(&crust, (self.boundary_width * 0.5).round().max(0.) as usize);
+
+        Grid::par_from_fn(dimensions, |x, y| {
+            let (sx, sy) = ((x + 1) as f32 / NOISE_TILES, (y + 1) as f32 / NOISE_TILES);
+            let mut height = crust[(x, y)] + detail.get_noise(sx, sy) * self.roughness;
+
+            if let Some((boundary, distance)) = boundaries[(x, y)] {
+                let mut lift = boundary.height(distance, self.boundary_width);
+
+                // arcs are strings of islands, not a wall
+                if matches!(boundary, Boundary::Arc(_)) {
+                    lift *= (islands.get_noise(sx, sy) * 2. + 0.5).clamp(0., 1.);
+                }
+
+                height += lift;
+            }
+
+            f64::from(height) * HEIGHT_SCALE
+        })
+    }
+
+    /// scatter `count` plates over the world, each drifting its own way
+    fn seed_plates(
+        &self,
+        count: usize,
+        width: f32,
+        height: f32,
+        rng: &mut RandomNumberGenerator,
+    ) -> Vec<Plate> {
+        // at least one continent, or there'd be nowhere to put anybody
+        #[allow(clippy::cast_sign_loss)]
+        let continents =
+            ((count as f32 * self.continental.clamp(0., 1.)).round() as usize).clamp(1, count);
+
+        (0..count)
+            .map(|i| {
+                let angle = rng.range(0., std::f32::consts::TAU);
+                let speed = rng.range(0.2, 1.);
+                let continental = i < continents;
+                let base = if continental {
+                    CONTINENT_HEIGHT
+                } else {
+                    OCEAN_HEIGHT
+                };
+
+                Plate {
+                    seed: (rng.range(0., width), rng.range(0., height)),
+                    velocity: (angle.cos() * speed, angle.sin() * speed),
+                    continental,
+                    density: rng.range(0., 1.),
+                    height: base + rng.range(-0.1, 0.1),
+                }
+            })
+            .collect()
+    }
+
+    /// work out what every boundary between plates turns into as the plates drift, then spread
+    /// each out over the tiles near it on its own side. gives the nearest boundary to every tile
+    /// close enough to one, and how far away it is
+    fn drift(&self, plates: &[Plate], owner: &Grid<usize>) -> Grid<Option<(Boundary, f32)>> {
+        let mut near = Grid::filled(owner.dimensions(), None);
+        let mut queue = VecDeque::new();
+
+        for (x, y) in owner.points() {
+            let plate = owner[(x, y)];
+            let Some(other) = owner
+                .neighbours((x, y), Neighbours::Four, Wrap::Never)
+                .map(|point| owner[point])
+                .find(|&other| other != plate)
+            else {
+                continue;
+            };
+
+            if let Some(boundary) = self.boundary(&plates[plate], &plates[other]) {
+                near[(x, y)] = Some((boundary, 0.));
+                queue.push_back((x, y));
+            }
+        }
+
+        // arcs sit furthest back from their boundary, so that's as far as anything has to reach
+        let reach = self.boundary_width * 0.8;
+
+        while let Some((x, y)) = queue.pop_front() {
+            let Some((boundary, distance)) = near[(x, y)] else {
+                continue;
+            };
+            if distance + 1. > reach {
+                continue;
+            }
+
+            for (nx, ny) in owner.neighbours((x, y), Neighbours::Four, Wrap::Never) {
+                // boundaries only push on their own plate, the other side has its own
+                if owner[(nx, ny)] == owner[(x, y)] && near[(nx, ny)].is_none() {
+                    near[(nx, ny)] = Some((boundary, distance + 1.));
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        near
+    }
+
+    /// what `plate` does along its edge with `other`, if anything
+    fn boundary(&self, plate: &Plate, other: &Plate) -> Option<Boundary> {
+        // which way the other plate is from this one
+        let (nx, ny) = (other.seed.0 - plate.seed.0, other.seed.1 - plate.seed.1);
+        let length = (nx * nx + ny * ny).sqrt().max(f32::EPSILON);
+        let (nx, ny) = (nx / length, ny / length);
+
+        // how fast the plates are closing on each other, negative when they're pulling apart
+        let closing = ((plate.velocity.0 - other.velocity.0) * nx
+            + (plate.velocity.1 - other.velocity.1) * ny)
+            * 0.5;
+
+        if closing.abs() < MIN_STRESS {
+            return None;
+        }
+
+        let mountains = self.mountain_height * closing.abs();
+        let rifts = self.rift_depth * closing.abs();
+
+        Some(match (closing > 0., plate.continental, other.continental) {
+            // two continents crumpling into each other, or an ocean diving under a continent,
+            // both push up ranges on the land
+            (true, true, _) => Boundary::Mountains(mountains),
+            // the lighter ocean rides over the heavier one, throwing up volcanoes as it goes
+            (true, false, false) if plate.density < other.density => Boundary::Arc(mountains),
+            (true, false, _) => Boundary::Trench(rifts),
+            (false, true, _) => Boundary::Rift(rifts),
+            (false, false, _) => Boundary::Ridge(rifts * 0.4),
+        })
+    }
+}
+
+/// average every tile with the ones up to `radius` away, a couple of times over so it comes out
+/// smooth rather than boxy
+fn blur(grid: &Grid<f32>, radius: usize) -> Grid<f32> {
+    if radius == 0 {
+        return grid.clone();
+    }
+
+    let dimensions = grid.dimensions();
+    let mut blurred = grid.clone();
+
+    for _ in 0..2 {
+        let along = Grid::par_from_fn(dimensions, |x, y| {
+            let row = blurred.row(y).unwrap_or_default();
+            let span = &row[x.saturating_sub(radius)..(x + radius + 1).min(row.len())];
+            span.iter().sum::<f32>() / span.len() as f32
+        });
+        blurred = Grid::par_from_fn(dimensions, |x, y| {
+            let (top, bottom) = (
+                y.saturating_sub(radius),
+                (y + radius + 1).min(dimensions.height as usize),
+            );
+            (top..bottom).map(|y| along[(x, y)]).sum::<f32>() / (bottom - top) as f32
+        });
+    }
+
+    blurred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_settings_are_invalid() {
+        let invalid = |tectonics: Tectonics| {
+            matches!(
+                tectonics.validate(),
+                Err(WorldGenError::InvalidParam { .. })
+            )
+        };
+
+        assert!(Tectonics::default().validate().is_ok());
+        assert!(invalid(Tectonics {
+            plates: 0,
+            ..Tectonics::default()
+        }));
+        assert!(invalid(Tectonics {
+            continental: 1.5,
+            ..Tectonics::default()
+        }));
+        assert!(invalid(Tectonics {
+            continental: f32::NAN,
+            ..Tectonics::default()
+        }));
+        assert!(invalid(Tectonics {
+            boundary_width: 0.,
+            ..Tectonics::default()
+        }));
+        assert!(invalid(Tectonics {
+            boundary_width: f32::INFINITY,
+            ..Tectonics::default()
+        }));
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let dimensions = RectDimension::new(70, 40);
+        let map = |seed| {
+            Tectonics::default().height_map(dimensions, &mut RandomNumberGenerator::seeded(seed))
+        };
+
+        let first = map(9);
+        assert!(first.iter().all(|height| height.is_finite()));
+        assert!(first
+            .iter()
+            .map(|h| h.to_bits())
+            .eq(map(9).iter().map(|h| h.to_bits())));
+        assert!(!first
+            .iter()
+            .map(|h| h.to_bits())
+            .eq(map(10).iter().map(|h| h.to_bits())));
+    }
+
+    #[test]
+    fn colliding_continents_raise_mountains() {
+        let tectonics = Tectonics {
+            roughness: 0.,
+            boundary_width: 8.,
+            ..Tectonics::default()
+        };
+        let continent = |x: f32, heading: f32| Plate {
+            seed: (x, 20.),
+            velocity: (heading, 0.),
+            continental: true,
+            density: 0.5,
+            height: CONTINENT_HEIGHT,
+        };
+        let plates = [continent(15., 1.), continent(45., -1.)];
+        assert!(matches!(
+            tectonics.boundary(&plates[0], &plates[1]),
+            Some(Boundary::Mountains(_))
+        ));
+
+        let map = tectonics.raise(
+            &plates,
+            RectDimension::new(60, 40),
+            &mut RandomNumberGenerator::seeded(2),
+        );
+        let mean = |xs: std::ops::Range<usize>| {
+            let heights: Vec<f64> = xs
+                .flat_map(|x| (0..40).map(move |y| (x, y)))
+                .map(|point| map[point])
+                .collect();
+            heights.iter().sum::<f64>() / heights.len() as f64
+        };
+
+        // the boundary wanders a little either side of halfway
+        let range = mean(27..33);
+        let interior = f64::midpoint(mean(0..8), mean(52..60));
+        assert!(
+            range > interior + 20.,
+            "the boundary is at {range} and the interiors at {interior}"
+        );
+    }
+}
@@ -1,174 +1,496 @@
+use std::error::Error;
+use std::fmt::Display;
+
 use bracket_lib::{
-    prelude::{Algorithm2D, BaseMap, FastNoise, FractalType, NoiseType},
+    prelude::{Algorithm2D, BaseMap},
     random::RandomNumberGenerator,
-    terminal::Point,
 };
-use log::{debug, log_enabled, trace, warn, Level};
+use log::{debug, info, log_enabled, trace, warn, Level};
+use serde::{Deserialize, Serialize};
 
-use crate::helpers::{Distance, RectDimension};
+use crate::helpers::{Distance, Grid, Neighbours, Point, RectDimension, Wrap};
 
-use super::{FullWorld, GenContext, GenParam, Pole};
+use super::erosion::Erosion;
+use super::noise::NoisePipeline;
+use super::tectonics::Tectonics;
+use super::{Climate, FullWorld, GenContext, GenParam, History, Pole, WorldGenError};
 
 /// a river or body of water
 /// TODO: better format, use lines?
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct River {
     // which tiles are covered by the water form
     covered_tiles: Vec<u32>,
 }
 
 /// a static map of a world and its terrain
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_field_names)]
 pub struct Map {
     pub dimensions: RectDimension,
     /// the level at which something goes into the sea/underwater
     pub sea_level: f64,
+    /// the level inland basins fill up to, if they have their own
+    #[serde(default)]
+    pub lake_level: Option<f64>,
     pub min_height: f64,
     pub max_height: f64,
-    pub height_map: Vec<f64>,
+    pub height_map: Grid<f64>,
     pub rivers: Vec<River>,
 }
 
-/// Does a binary search of sea levels until amount of world covered by sea is withing a reasonable distance from `wanted_percent`
-///
-/// Should only error if a NaN is present in the input, or if the algorithm is bugged.
-/// allows variance of `1./ height_map.len()` in order to ensure that the wanted percent is
-/// actually achievable
-fn decide_sea_level(height_map: &[f64], wanted_percent: f64) -> Result<f64, ()> {
-    let mut min = height_map.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let mut max = height_map.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-    let lowest_percent = wanted_percent - 1. / height_map.len() as f64;
-    let highest_percent = wanted_percent + 1. / height_map.len() as f64;
-
-    let percent_uw = |sl: &f64| {
-        height_map
+impl Map {
+    /// fill the height map with water to hit `params`' targets, replacing any sea, lakes and
+    /// height range it had. needs doing again whenever the height map changes
+    pub fn flood(&mut self, params: &GenParam) -> Result<(), SeaLevelError> {
+        let wanted = f64::from(params.target_water) / 100.;
+        let sea = decide_sea_level(self.height_map.as_slice(), wanted)?;
+        info!(
+            "sea level {} covers {:.2}% of the world, wanted {:.2}%",
+            sea.level,
+            sea.water * 100.,
+            wanted * 100.
+        );
+
+        // lakes sit in basins up to their own level, without ever joining up with the sea
+        let (lake_level, rivers) = match params.target_lakes {
+            Some(target_lakes) => {
+                let lake_level =
+                    decide_sea_level(self.height_map.as_slice(), f64::from(target_lakes) / 100.)?
+                        .level;
+                let lakes = fill_lakes(&self.height_map, sea.level, lake_level);
+                debug!("filled {} lakes up to {lake_level}", lakes.len());
+
+                (Some(lake_level), lakes)
+            }
+            None => (None, Vec::new()),
+        };
+
+        self.sea_level = sea.level;
+        self.lake_level = lake_level;
+        self.rivers = rivers;
+        self.min_height = self.height_map.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+        self.max_height = self
+            .height_map
             .iter()
-            .fold(0., |t, h| if h <= sl { t + 1. } else { t })
-            / (height_map.len() as f64)
-    };
+            .fold(f64::NEG_INFINITY, |a, &b| a.max(b));
 
-    loop {
-        // mid != min
-        #[allow(clippy::similar_names)]
-        let mid = (min + max) * 0.5;
-
-        let percent_uw = percent_uw(&mid);
-        if (percent_uw > highest_percent) {
-            trace!(
-                "decreasing uw%: {}\nmid: {}\nmax: {}\nmin: {}\nwanted percent: {}-{}",
-                percent_uw,
-                mid,
-                max,
-                min,
-                lowest_percent,
-                highest_percent
-            );
-            max = mid;
-        } else if (percent_uw < lowest_percent) {
-            trace!(
-                "increasing uw%: {}\nmid: {}\nmax: {}\nmin: {}\nwanted percent: {}-{}",
-                percent_uw,
-                mid,
-                max,
-                min,
-                lowest_percent,
-                highest_percent
-            );
-            min = mid;
-        } else {
-            return Ok(mid);
+        Ok(())
+    }
+
+    /// whether the tile at (x, y) is at or below sea level
+    pub fn is_sea(&self, x: usize, y: usize) -> bool {
+        self.height_map[(x, y)] <= self.sea_level
+    }
+
+    /// how far below sea level the tile at (x, y) is. negative on land
+    pub fn depth(&self, x: usize, y: usize) -> f64 {
+        self.sea_level - self.height_map[(x, y)]
+    }
+
+    /// the depth of the deepest point on the map
+    pub fn max_depth(&self) -> f64 {
+        self.sea_level - self.min_height
+    }
+
+    /// every land tile with at least one sea tile around it
+    pub fn coastal_tiles(&self) -> Vec<Point<usize>> {
+        self.height_map
+            .points()
+            .filter(|&(x, y)| {
+                !self.is_sea(x, y)
+                    && self
+                        .height_map
+                        .neighbours((x, y), Neighbours::Eight, Wrap::Never)
+                        .any(|(nx, ny)| self.is_sea(nx, ny))
+            })
+            .collect()
+    }
+
+    /// every separate piece of land, as the tiles making it up. tiles only touching diagonally
+    /// still count as the same piece
+    pub fn landmasses(&self) -> Vec<Vec<Point<usize>>> {
+        let mut seen = Grid::filled(self.dimensions, false);
+        let mut landmasses = Vec::new();
+
+        for (x, y) in self.height_map.points() {
+            if seen[(x, y)] || self.is_sea(x, y) {
+                continue;
+            }
+
+            // flood out from here over everything that isn't sea
+            let mut landmass = Vec::new();
+            let mut stack = vec![(x, y)];
+            seen[(x, y)] = true;
+            while let Some(point) = stack.pop() {
+                landmass.push(point);
+
+                for (nx, ny) in self
+                    .height_map
+                    .neighbours(point, Neighbours::Eight, Wrap::Never)
+                {
+                    if !seen[(nx, ny)] && !self.is_sea(nx, ny) {
+                        seen[(nx, ny)] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+
+            landmasses.push(landmass);
         }
 
-        //TODO: yeah, this is broken idc because it seems like it still ends up with a close enough
-        // percent anyways
+        landmasses
+    }
+}
+
+/// why a sea level couldn't be picked for a height map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeaLevelError {
+    /// there were no tiles to put the sea over
+    Empty,
+    /// a height was NaN or infinite, so there's no telling what's above or below it
+    NotFinite,
+}
 
-        if (min > max) {
-            warn!("minimum of {} greater than maximum of {}", min, max);
+impl Display for SeaLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeaLevelError::Empty => write!(f, "the height map has no tiles in it"),
+            SeaLevelError::NotFinite => write!(f, "the height map has a NaN or infinite height"),
         }
+    }
+}
+
+impl Error for SeaLevelError {}
 
-        assert!(min < max, "min gt max");
+/// a level to fill a height map with water up to, and how much of it that actually covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeaLevel {
+    /// every tile at or below this is underwater
+    pub level: f64,
+    /// the fraction of tiles underwater, 0-1
+    pub water: f64,
+}
+
+/// picks the level that puts as close to `wanted_percent` (0-1) of `height_map` underwater as it
+/// can. that's exact unless a lot of tiles share the same height right where the level would go, in
+/// which case it's whichever side of them comes closer
+fn decide_sea_level(height_map: &[f64], wanted_percent: f64) -> Result<SeaLevel, SeaLevelError> {
+    if height_map.is_empty() {
+        return Err(SeaLevelError::Empty);
+    }
+    if height_map.iter().any(|h| !h.is_finite()) {
+        return Err(SeaLevelError::NotFinite);
     }
 
-    Err(())
+    let len = height_map.len();
+    let underwater = |level: f64| height_map.iter().filter(|&&h| h <= level).count();
+    #[allow(clippy::cast_sign_loss)]
+    let wanted = ((wanted_percent.clamp(0., 1.) * len as f64).round() as usize).min(len);
+
+    let lowest = height_map.iter().copied().fold(f64::INFINITY, f64::min);
+    let level = if wanted == 0 {
+        // under everything
+        lowest - 1.
+    } else {
+        // the height of the `wanted`th lowest tile, and every tile lower than it
+        let mut heights = height_map.to_vec();
+        let (lower, &mut at, _) = heights.select_nth_unstable_by(wanted - 1, f64::total_cmp);
+
+        // tiles tied with it all go under together, so see if leaving them all out comes closer
+        let with_ties = underwater(at);
+        let below = lower
+            .iter()
+            .copied()
+            .filter(|&h| h < at)
+            .fold(None, |max: Option<f64>, h| {
+                Some(max.map_or(h, |max| max.max(h)))
+            })
+            .unwrap_or(lowest - 1.);
+        let without_ties = underwater(below);
+
+        if with_ties - wanted <= wanted - without_ties {
+            at
+        } else {
+            below
+        }
+    };
+
+    Ok(SeaLevel {
+        level,
+        water: underwater(level) as f64 / len as f64,
+    })
 }
 
-pub fn gen_base_map<'a, 'b>(context: &mut GenContext) -> FullWorld {
-    let params = context.params;
-    let mut rng = &mut context.rng;
-    let (h, w) = (params.world_size.height, params.world_size.width);
-    let mut noise = FastNoise::seeded(rng.next_u64());
-
-    noise.set_noise_type(NoiseType::PerlinFractal);
-    noise.set_fractal_type(FractalType::FBM);
-    noise.set_fractal_octaves(10);
-    noise.set_fractal_gain(0.5);
-    noise.set_fractal_lacunarity(3.0);
-    noise.set_frequency(2.0);
-
-    //TODO: there's got to be a more elegant way to do all this
-    let mut height_map = Vec::new();
-    // less allocation /shrug
-    height_map.reserve_exact(h as usize * w as usize);
-
-    for y in 0..w {
-        for x in 0..h {
-            let n = noise.get_noise(((x + 1) as f32) / h as f32 * 2., ((y + 1) as f32) / 100.)
-                * w as f32
-                * 2.;
-
-            height_map.push(n as f64);
+/// every basin at or below `lake_level` that the sea doesn't reach into, each as its own body of
+/// water
+fn fill_lakes(height_map: &Grid<f64>, sea_level: f64, lake_level: f64) -> Vec<River> {
+    let mut seen = Grid::filled(height_map.dimensions(), false);
+    let mut lakes = Vec::new();
+
+    for (x, y) in height_map.points() {
+        if seen[(x, y)] || height_map[(x, y)] > lake_level {
+            continue;
+        }
+
+        // flood out over everything low enough, noting if any of it is already the sea
+        let mut basin = Vec::new();
+        let mut reaches_sea = false;
+        let mut stack = vec![(x, y)];
+        seen[(x, y)] = true;
+        while let Some(point) = stack.pop() {
+            basin.push(point);
+            reaches_sea |= height_map[point] <= sea_level;
+
+            for next in height_map.neighbours(point, Neighbours::Four, Wrap::Never) {
+                if !seen[next] && height_map[next] <= lake_level {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+
+        if !reaches_sea {
+            let covered_tiles = basin
+                .into_iter()
+                .filter_map(|(x, y)| height_map.index(x, y))
+                .filter_map(|i| u32::try_from(i).ok())
+                .collect();
+            lakes.push(River { covered_tiles });
         }
     }
 
-    let sea_level = decide_sea_level(&height_map, (params.target_water as f64) / 255.).unwrap();
+    lakes
+}
 
-    let mut min_height = height_map.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let mut max_height = height_map.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+/// make the base of a world out of layers of noise
+pub fn gen_base_map(
+    pipeline: &NoisePipeline,
+    context: &mut GenContext,
+) -> Result<FullWorld, SeaLevelError> {
+    let height_map = pipeline.height_map(context.params.world_size, context.rng);
 
-    let ret = Map {
+    world_from_height_map(height_map, context.params)
+}
+
+/// make the base of a world out of drifting plates
+pub fn gen_plate_map(
+    tectonics: &Tectonics,
+    context: &mut GenContext,
+) -> Result<FullWorld, SeaLevelError> {
+    let height_map = tectonics.height_map(context.params.world_size, context.rng);
+
+    world_from_height_map(height_map, context.params)
+}
+
+/// pick a sea level for `height_map` and wrap it up as a world with nothing else in it yet
+pub(super) fn world_from_height_map(
+    height_map: Grid<f64>,
+    params: &GenParam,
+) -> Result<FullWorld, SeaLevelError> {
+    let mut ret = Map {
         dimensions: params.world_size,
         height_map,
         rivers: Vec::new(),
-        sea_level,
-        max_height,
-        min_height,
+        sea_level: 0.,
+        lake_level: None,
+        max_height: 0.,
+        min_height: 0.,
     };
+    ret.flood(params)?;
 
-    FullWorld {
+    Ok(FullWorld {
         terrain: ret,
         rivers: Vec::new(),
         pole: Pole::new(),
+        climate: Climate::default(),
+        history: History::default(),
+        treasures: Vec::new(),
+        params: params.clone(),
+    })
+}
+
+/// erode a map down, running each of `erosion`'s passes over it in turn
+pub fn erode(
+    map: &FullWorld,
+    erosion: &Erosion,
+    context: &mut GenContext,
+) -> Result<Grid<f64>, WorldGenError> {
+    let mut generator = RandomNumberGenerator::seeded(context.rng.next_u64());
+    let mut height_map = map.terrain.height_map.clone();
+    let wanted = f64::from(context.params.target_water) / 100.;
+    let mut sea_level = map.terrain.sea_level;
+
+    for pass in &erosion.passes {
+        debug!("running erosion pass {pass:?}");
+        pass.run(
+            &mut height_map,
+            sea_level,
+            &map.pole,
+            &mut generator,
+            context.cancel,
+        )?;
+
+        // the pass moved ground across the coast, so the next one gets the sea where it is now
+        sea_level = decide_sea_level(height_map.as_slice(), wanted)?.level;
     }
+
+    Ok(height_map)
 }
 
-/// erode a map down
-/// updates heightmap & rivers(?)
-pub fn erode(map: &FullWorld, master_generator: &mut RandomNumberGenerator) -> Vec<f64> {
-    // most importantly, seeding infrastructure is not in place
+#[cfg(test)]
+// sea levels are picked straight out of the heights, so they should match exactly
+#[allow(clippy::float_cmp, clippy::cast_sign_loss)]
+mod tests {
+    use proptest::prelude::*;
 
-    let mut generator = RandomNumberGenerator::seeded(master_generator.next_u64());
+    use super::*;
 
-    // to do first: random rain erosion tile-by tile
-    // glaciers?
-    // next, pathfind rivers, etc
-    // erode around rivers?
-    // run a little more erosion
+    #[test]
+    fn finished_worlds_hit_the_target() {
+        for target_water in [40., 70.] {
+            let params = GenParam {
+                seed: 9,
+                target_water,
+                target_lakes: Some(target_water + 5.),
+                world_size: RectDimension::new(80, 60),
+                history_years: 5,
+                ..GenParam::default()
+            };
+            let world = super::super::gen_full_world(&params, None, &crate::helpers::Cancel::new())
+                .unwrap();
+            let terrain = &world.terrain;
 
-    let mut ret_hmap = map.terrain.height_map.clone();
+            let underwater = terrain
+                .height_map
+                .iter()
+                .filter(|&&h| h <= terrain.sea_level)
+                .count();
+            let water = underwater as f64 / terrain.height_map.len() as f64;
+            assert!(
+                (water - f64::from(target_water) / 100.).abs()
+                    <= 1. / terrain.height_map.len() as f64,
+                "{water} underwater, wanted {target_water}%"
+            );
 
-    // rain erosion
-    let passes_per_tile = 0.5; // 0.5 erosion passes per tile
-    let total_passes = { (map.terrain.height_map.len() as f64 * passes_per_tile).round() } as u64;
+            // the lakes are the ones in the eroded map, not the one before it
+            let lake_level = terrain.lake_level.unwrap();
+            assert_eq!(
+                terrain.rivers.len(),
+                fill_lakes(&terrain.height_map, terrain.sea_level, lake_level).len()
+            );
+        }
+    }
 
-    debug!(
-        "running {} rain erosion passes for {} total tiles",
-        total_passes,
-        map.terrain.height_map.len()
-    );
+    #[test]
+    fn sea_level_hits_the_target() {
+        let heights: Vec<_> = (0..10).map(f64::from).collect();
+        let sea = decide_sea_level(&heights, 0.3).unwrap();
 
-    for _ in 0..total_passes {
-        // choose a random tile
+        assert_eq!(sea.level, 2.);
+        assert!((sea.water - 0.3).abs() < f64::EPSILON);
     }
 
-    todo!()
+    #[test]
+    fn sea_level_at_the_extremes() {
+        let heights = [3., -1., 7., 2.];
+
+        let dry = decide_sea_level(&heights, 0.).unwrap();
+        assert!(dry.level < -1.);
+        assert!(dry.water.abs() < f64::EPSILON);
+
+        let flooded = decide_sea_level(&heights, 1.).unwrap();
+        assert_eq!(flooded.level, 7.);
+        assert!((flooded.water - 1.).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn ties_go_to_the_closer_side() {
+        // a quarter can't be had, it's either none of the 5s or all of them
+        let heights = [1., 5., 5., 5., 5., 5., 5., 9.];
+
+        let sea = decide_sea_level(&heights, 0.25).unwrap();
+        assert_eq!(sea.level, 1.);
+        assert!((sea.water - 0.125).abs() < f64::EPSILON);
+
+        let sea = decide_sea_level(&heights, 0.7).unwrap();
+        assert_eq!(sea.level, 5.);
+        assert!((sea.water - 0.875).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn bad_height_maps_are_errors() {
+        assert_eq!(decide_sea_level(&[], 0.5), Err(SeaLevelError::Empty));
+        assert_eq!(
+            decide_sea_level(&[1., f64::NAN, 2.], 0.5),
+            Err(SeaLevelError::NotFinite)
+        );
+        assert_eq!(
+            decide_sea_level(&[f64::NEG_INFINITY], 0.5),
+            Err(SeaLevelError::NotFinite)
+        );
+    }
+
+    #[test]
+    fn lakes_stay_out_of_the_sea() {
+        // sea down the left, a basin in the middle and a dip running into the sea on the right
+        #[rustfmt::skip]
+        let heights = [
+            0., 9., 9., 9., 9., 9.,
+            0., 9., 4., 9., 9., 9.,
+            0., 9., 9., 9., 9., 4.,
+            0., 4., 4., 4., 4., 4.,
+        ];
+        let height_map = Grid::from_vec(RectDimension::new(6, 4), heights.to_vec()).unwrap();
+
+        let lakes = fill_lakes(&height_map, 1., 5.);
+
+        assert_eq!(lakes.len(), 1);
+        assert_eq!(lakes[0].covered_tiles, [8]);
+    }
+
+    proptest! {
+        #[test]
+        fn sea_level_is_exact_without_ties(
+            heights in prop::collection::hash_set(-100_000i32..100_000, 1..300),
+            wanted in 0f64..=1.,
+        ) {
+            let heights: Vec<_> = heights.into_iter().map(f64::from).collect();
+            let sea = decide_sea_level(&heights, wanted).unwrap();
+
+            let underwater = heights.iter().filter(|&&h| h <= sea.level).count();
+            prop_assert_eq!(underwater, (wanted * heights.len() as f64).round() as usize);
+        }
+
+        #[test]
+        fn sea_level_reports_what_it_covers(
+            heights in prop::collection::vec(-20i32..20, 1..300),
+            wanted in 0f64..=1.,
+        ) {
+            let heights: Vec<_> = heights.into_iter().map(f64::from).collect();
+            let sea = decide_sea_level(&heights, wanted).unwrap();
+
+            let underwater = heights.iter().filter(|&&h| h <= sea.level).count();
+            prop_assert_eq!(sea.water, underwater as f64 / heights.len() as f64);
+
+            // the level goes either just under or just over the tiles tied where it should be,
+            // whichever comes closer
+            let wanted = (wanted * heights.len() as f64).round() as usize;
+            if wanted == 0 {
+                prop_assert_eq!(underwater, 0);
+            } else {
+                let mut sorted = heights.clone();
+                sorted.sort_unstable_by(f64::total_cmp);
+                let tied = sorted[wanted - 1];
+                let with_ties = heights.iter().filter(|&&h| h <= tied).count();
+                let without_ties = heights.iter().filter(|&&h| h < tied).count();
+
+                prop_assert!(underwater == with_ties || underwater == without_ties);
+                prop_assert!(
+                    underwater.abs_diff(wanted)
+                        <= with_ties.abs_diff(wanted).min(without_ties.abs_diff(wanted))
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,227 @@
+//! buried treasure, and the maps leading to it
+//!
+//! once history is done, the hoards of famous captains who died and the riches carried off from
+//! sacked ports get buried on remote islands. every hoard has a map: a scrap of the coastline
+//! around it with a few landmarks and an X, but nothing saying where in the world it is
+
+use std::cmp::Reverse;
+
+use bracket_lib::random::RandomNumberGenerator;
+use bracket_lib::terminal::{RGBA, YELLOW};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{Point, RectDimension};
+use crate::render::{Frame, Tile};
+
+use super::history::{EventKind, History};
+use super::{FullWorld, GenContext};
+
+pub type TreasureId = usize;
+
+/// how many hoards get buried at most
+const MAX_TREASURES: usize = 8;
+/// landmasses bigger than this aren't remote enough to bury anything on
+const MAX_ISLAND_TILES: usize = 40;
+/// how many islands get looked at for each hoard, the most out of the way of them winning
+const ISLAND_CHOICES: usize = 3;
+/// how far out from the X a treasure map goes in every direction
+pub const MAP_RADIUS: usize = 8;
+/// how far from the middle of the map the X can be, so it doesn't give too much away
+const MAP_DRIFT: usize = 4;
+/// what a famous captain buried, before counting their deeds
+const CAPTAIN_HOARD: u32 = 200;
+/// what every deed a captain did adds to their hoard
+const GOLD_PER_DEED: u32 = 60;
+/// what got carried off from a sacked port, at most
+const SACKED_HOARD: u32 = 600;
+
+const SEA_CHAR: char = '~';
+const LAND_CHAR: char = '^';
+const PEAK_CHAR: char = 'A';
+const PORT_CHAR: char = 'o';
+const MARK_CHAR: char = 'X';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Treasure {
+    pub location: Point<usize>,
+    pub gold: u32,
+    /// who buried it, like "captain so and so of somewhere"
+    pub owner: String,
+    /// the year it was buried
+    pub buried: u32,
+    /// the top left corner of the map leading to it
+    corner: Point<usize>,
+    pub found: bool,
+}
+
+/// how many steps it takes to get from `a` to `b`, diagonals included
+fn distance(a: Point<usize>, b: Point<usize>) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// land nobody lives on that's small enough to be left alone, the loneliest first, along with how
+/// far each is from the nearest port
+fn islands(world: &FullWorld) -> Vec<(usize, Vec<Point<usize>>)> {
+    let history = &world.history;
+    let ports: Vec<_> = history.ports.iter().map(|port| port.location).collect();
+
+    let mut islands: Vec<_> = world
+        .terrain
+        .landmasses()
+        .into_iter()
+        .filter(|land| land.len() <= MAX_ISLAND_TILES)
+        .map(|land| {
+            land.into_iter()
+                .filter(|&(x, y)| !world.pole.is_frozen(x, y))
+                .collect::<Vec<_>>()
+        })
+        .filter(|land| !land.is_empty() && !land.iter().any(|tile| ports.contains(tile)))
+        .map(|land| {
+            let remoteness = land
+                .iter()
+                .flat_map(|&tile| ports.iter().map(move |&port| distance(tile, port)))
+                .min()
+                .unwrap_or(usize::MAX);
+            (remoteness, land)
+        })
+        .collect();
+    islands.sort_by_key(|&(remoteness, _)| Reverse(remoteness));
+
+    islands
+}
+
+/// the hoards worth burying: famous captains who died, and the riches of sacked ports, as (who
+/// buried it, year, gold)
+fn hoards(history: &History, rng: &mut RandomNumberGenerator) -> Vec<(String, u32, u32)> {
+    let mut hoards = Vec::new();
+
+    for event in &history.events {
+        match event.kind {
+            EventKind::CaptainDied { captain } => {
+                let captain = &history.captains[captain];
+                let civ = &history.civilizations[captain.civ].name;
+                hoards.push((
+                    format!("{} of {civ}", captain.name),
+                    event.year,
+                    CAPTAIN_HOARD + GOLD_PER_DEED * captain.deeds,
+                ));
+            }
+            EventKind::PortDestroyed { port, by, .. } => {
+                let civ = &history.civilizations[by].name;
+                hoards.push((
+                    format!("the sackers of {} from {civ}", history.ports[port].name),
+                    event.year,
+                    rng.range(SACKED_HOARD / 2, SACKED_HOARD + 1),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    // the richest make the best stories
+    hoards.sort_by_key(|&(_, _, gold)| Reverse(gold));
+    hoards.truncate(MAX_TREASURES);
+
+    hoards
+}
+
+/// bury treasure on the remote islands of a world whose history is done
+pub fn gen_treasure(world: &FullWorld, context: &mut GenContext) -> Vec<Treasure> {
+    let mut rng = RandomNumberGenerator::seeded(context.rng.next_u64());
+    let dimensions = world.dimensions();
+
+    let mut islands = islands(world);
+    let mut treasures = Vec::new();
+
+    for (owner, buried, gold) in hoards(&world.history, &mut rng) {
+        // the loneliest of a few, so they don't all end up on the same rock
+        let choices = islands.len().min(ISLAND_CHOICES);
+        if choices == 0 {
+            break;
+        }
+        let island = rng.range(0, choices);
+        let (_, land) = islands.remove(island);
+        let Some(&location) = rng.random_slice_entry(&land) else {
+            continue;
+        };
+
+        // the X never quite in the middle, and the map never hanging off the edge of the world
+        let mut start = |at: usize, size: u16| {
+            let drift = rng.range(0, MAP_DRIFT * 2 + 1);
+            (at + drift)
+                .saturating_sub(MAP_RADIUS + MAP_DRIFT)
+                .min((size as usize).saturating_sub(MAP_RADIUS * 2 + 1))
+        };
+        let corner = (
+            start(location.0, dimensions.width),
+            start(location.1, dimensions.height),
+        );
+
+        treasures.push(Treasure {
+            location,
+            gold,
+            owner,
+            buried,
+            corner,
+            found: false,
+        });
+    }
+
+    debug!("buried {} treasures", treasures.len());
+
+    treasures
+}
+
+impl Treasure {
+    /// the treasure map: the stretch of `world` around the treasure, with its landmarks and an X
+    pub fn map(&self, world: &FullWorld) -> Frame {
+        let size = MAP_RADIUS * 2 + 1;
+        let mut frame = Frame::blank(RectDimension::new(size as u16, size as u16));
+
+        // old paper and faded ink
+        let paper = RGBA::from_u8(222, 196, 145, 255);
+        let ink = RGBA::from_u8(92, 64, 36, 255);
+        let sea = RGBA::from_u8(70, 90, 110, 255);
+
+        let (cx, cy) = self.corner;
+        let terrain = &world.terrain;
+        // the highest point of the land around makes a good landmark
+        let mut peak: Option<(Point<usize>, f64)> = None;
+
+        for y in 0..size {
+            for x in 0..size {
+                let (wx, wy) = (cx + x, cy + y);
+                if !world.in_bounds(wx, wy) {
+                    continue;
+                }
+
+                let tile = if terrain.is_sea(wx, wy) {
+                    Tile::new(SEA_CHAR, sea, paper)
+                } else {
+                    let height = -terrain.depth(wx, wy);
+                    if peak.is_none_or(|(_, highest)| height > highest) {
+                        peak = Some(((x, y), height));
+                    }
+                    Tile::new(LAND_CHAR, ink, paper)
+                };
+                frame.set(x, y, tile);
+            }
+        }
+
+        if let Some(((x, y), _)) = peak {
+            frame.set(x, y, Tile::new(PEAK_CHAR, ink, paper));
+        }
+        for (_, port) in world.history.living_ports() {
+            let (px, py) = port.location;
+            if (cx..cx + size).contains(&px) && (cy..cy + size).contains(&py) {
+                frame.set(px - cx, py - cy, Tile::new(PORT_CHAR, ink, paper));
+            }
+        }
+
+        let (tx, ty) = self.location;
+        frame.set(tx - cx, ty - cy, Tile::new(MARK_CHAR, YELLOW, paper));
+
+        frame
+    }
+}